@@ -0,0 +1,277 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fs::{create_dir_all, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bands::{BandInfo, BandSegment, Bands};
+
+// the user's band plan, selected as CUSTOM_BAND_PLAN
+pub const CUSTOM_BAND_PLAN: &str = "Custom";
+
+const BUILTIN_PLANS: [&str; 3] = [
+    include_str!("plans/iaru_region_1.ron"),
+    include_str!("plans/iaru_region_2.ron"),
+    include_str!("plans/iaru_region_3.ron"),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BandPlanBand {
+    pub band: Bands,
+    pub label: String,
+    pub low: f64,
+    pub high: f64,
+    pub tx: bool, // transmit allowed
+    pub segments: Vec<BandSegment>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BandPlan {
+    pub name: String,
+    pub bands: Vec<BandPlanBand>, // in band grid order
+}
+
+impl BandPlan {
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        ron::from_str::<BandPlan>(s).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        let mut s = String::new();
+        file.read_to_string(&mut s).map_err(|e| format!("{:?}: {}", path, e))?;
+        Self::parse(&s).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                create_dir_all(parent).map_err(|e| format!("{:?}: {}", parent, e))?;
+            }
+        }
+        let mut file = File::create(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        file.write_all(s.as_bytes()).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    pub fn builtin() -> Result<Vec<BandPlan>, String> {
+        let mut plans = Vec::new();
+        for (i, s) in BUILTIN_PLANS.iter().enumerate() {
+            let plan = Self::parse(s).map_err(|e| format!("builtin plan {}: {}", i, e))?;
+            plans.push(plan);
+        }
+        Ok(plans)
+    }
+
+    // the band plan file that can be edited by the user
+    pub fn custom_path() -> PathBuf {
+        let app_name = env!("CARGO_PKG_NAME");
+        let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        config_dir.join(app_name).join("bandplan.ron")
+    }
+
+    // a builtin plan by name or the custom plan
+    pub fn find(name: &str) -> Result<BandPlan, String> {
+        if name == CUSTOM_BAND_PLAN {
+            let mut plan = Self::load(&Self::custom_path())?;
+            plan.name = CUSTOM_BAND_PLAN.to_string();
+            return Ok(plan);
+        }
+        Self::builtin()?.into_iter().find(|p| p.name == name).ok_or_else(|| format!("{} not found", name))
+    }
+
+    fn is_xvtr(band: Bands) -> bool {
+        matches!(band, Bands::XVTR1 | Bands::XVTR2 | Bands::XVTR3)
+    }
+
+    // band indexes for the band grid, transverters follow the plan unless it lists them
+    pub fn grid_bands(&self) -> Vec<usize> {
+        let mut bands: Vec<usize> = self.bands.iter().map(|b| b.band.to_usize()).collect();
+        for xvtr in [Bands::XVTR1, Bands::XVTR2, Bands::XVTR3] {
+            if !bands.contains(&xvtr.to_usize()) {
+                bands.push(xvtr.to_usize());
+            }
+        }
+        bands
+    }
+
+    // set the limits, segments and transmit permission of a receiver's bands,
+    // a plan adds new bands through the USER1 to USER8 slots
+    pub fn apply(&self, band_info: &mut Vec<BandInfo>) {
+        // bands added since the configuration was saved
+        let defaults = BandInfo::new();
+        while band_info.len() < defaults.len() {
+            band_info.push(defaults[band_info.len()].clone());
+        }
+
+        for info in band_info.iter_mut() {
+            info.enabled = Self::is_xvtr(info.band);
+            info.segments.clear();
+            if info.enabled {
+                // transverters the plan does not list may transmit
+                info.tx_allowed = true;
+            }
+        }
+
+        for band in self.bands.iter() {
+            let info = &mut band_info[band.band.to_usize()];
+            info.enabled = true;
+            info.segments = band.segments.clone();
+            info.tx_allowed = band.tx;
+            if Self::is_xvtr(band.band) {
+                // limits come from the XVTR settings
+                continue;
+            }
+            info.label = band.label.clone();
+            info.low = band.low;
+            info.high = band.high;
+            // keep the band memories inside the band after a region change
+            if info.low < info.high {
+                let (low, high) = (info.low, info.high);
+                if info.current < low || info.current > high {
+                    info.current = (low + high) / 2.0;
+                }
+                if info.ctun < low || info.ctun > high {
+                    info.ctun = info.current;
+                }
+                for stack in info.stack.iter_mut() {
+                    if stack.current < low || stack.current > high {
+                        stack.current = info.current;
+                        stack.ctun = info.current;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(name: &str) -> BandPlan {
+        BandPlan::find(name).unwrap()
+    }
+
+    fn inside(frequency: f64, low: f64, high: f64) -> bool {
+        frequency >= low && frequency <= high
+    }
+
+    #[test]
+    fn builtin_plans_parse() {
+        let plans = BandPlan::builtin().unwrap();
+        assert_eq!(plans.len(), BUILTIN_PLANS.len());
+        let names: Vec<&str> = plans.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["IARU Region 1", "IARU Region 2", "IARU Region 3"]);
+        for plan in plans.iter() {
+            assert!(!plan.bands.is_empty());
+            for band in plan.bands.iter() {
+                assert!(band.low <= band.high, "{} {}", plan.name, band.label);
+                for segment in band.segments.iter() {
+                    assert!(segment.low < segment.high && segment.low >= band.low && segment.high <= band.high, "{} {} segment {}-{}", plan.name, band.label, segment.low, segment.high);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn apply_keeps_band_memories_inside_the_limits() {
+        for plan in BandPlan::builtin().unwrap() {
+            let mut band_info = BandInfo::new();
+            // memories left outside every band
+            for info in band_info.iter_mut() {
+                info.current = info.high + 1000000.0;
+                info.ctun = info.low - 1000000.0;
+                for stack in info.stack.iter_mut() {
+                    stack.current = info.high + 1000000.0;
+                    stack.ctun = info.high + 1000000.0;
+                }
+            }
+            plan.apply(&mut band_info);
+            for band in plan.bands.iter() {
+                let info = &band_info[band.band.to_usize()];
+                assert!(info.enabled);
+                if BandPlan::is_xvtr(band.band) || band.low >= band.high {
+                    continue;
+                }
+                assert_eq!(info.tx_allowed, band.tx);
+                assert_eq!(info.label, band.label);
+                assert!(inside(info.current, band.low, band.high), "{} {} current {}", plan.name, band.label, info.current);
+                assert!(inside(info.ctun, band.low, band.high), "{} {} ctun {}", plan.name, band.label, info.ctun);
+                for stack in info.stack.iter() {
+                    assert!(inside(stack.current, band.low, band.high) && inside(stack.ctun, band.low, band.high), "{} {} stack {}", plan.name, band.label, stack.current);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn apply_moves_only_the_memories_outside_the_new_plan() {
+        let b = Bands::Band40.to_usize();
+        let mut band_info = BandInfo::new();
+        plan("IARU Region 2").apply(&mut band_info);
+        band_info[b].current = 7250000.0;
+        band_info[b].ctun = 7250000.0;
+        band_info[b].stack[1].current = 7250000.0;
+        band_info[b].stack[1].ctun = 7250000.0;
+        band_info[b].stack[2].current = 7074000.0;
+        band_info[b].stack[2].ctun = 7074000.0;
+
+        plan("IARU Region 1").apply(&mut band_info);
+        assert_eq!(band_info[b].high, 7200000.0);
+        assert_eq!(band_info[b].current, 7100000.0);
+        assert_eq!(band_info[b].ctun, 7100000.0);
+        assert_eq!(band_info[b].stack[1].current, 7100000.0);
+        assert_eq!(band_info[b].stack[2].current, 7074000.0);
+    }
+
+    #[test]
+    fn parse_errors_are_reported() {
+        assert!(BandPlan::parse("BandPlan(name: \"Broken\", bands: [").is_err());
+        assert!(BandPlan::parse("BandPlan(name: \"Empty\", bands: [])").is_ok());
+        assert!(BandPlan::find("No Such Plan").unwrap_err().contains("No Such Plan"));
+    }
+
+    #[test]
+    fn apply_adds_user_bands() {
+        let b = Bands::USER1.to_usize();
+        let mut band_info = BandInfo::new();
+        assert!(!band_info[b].enabled);
+        let mut plan = plan("IARU Region 1");
+        plan.bands.push(BandPlanBand{band: Bands::USER1, label: String::from("MW"), low: 530000.0, high: 1700000.0, tx: false, segments: Vec::new()});
+        plan.apply(&mut band_info);
+        assert!(band_info[b].enabled);
+        assert!(!band_info[b].tx_allowed);
+        assert_eq!(band_info[b].label, "MW");
+        assert_eq!(band_info[b].current, 1115000.0);
+        assert_eq!(band_info[b].stack[1].current, 1115000.0);
+        assert!(!band_info[Bands::USER2.to_usize()].enabled);
+        // shown after the plan bands, before the transverters
+        let grid = plan.grid_bands();
+        assert_eq!(grid[plan.bands.len() - 1], b);
+
+        // an older configuration without the user slots
+        band_info.truncate(Bands::USER1.to_usize());
+        plan.apply(&mut band_info);
+        assert_eq!(band_info.len(), BandInfo::new().len());
+        assert_eq!(band_info[b].label, "MW");
+    }
+}
@@ -45,6 +45,19 @@ pub enum Bands {
    XVTR1,
    XVTR2,
    XVTR3,
+   Band2200,
+   Band630,
+   Band11,
+   SWBC,
+   GEN,
+   USER1,
+   USER2,
+   USER3,
+   USER4,
+   USER5,
+   USER6,
+   USER7,
+   USER8,
 }
 
 impl Bands {
@@ -65,6 +78,19 @@ impl Bands {
             12 => Some(Bands::XVTR1),
             13 => Some(Bands::XVTR2),
             14 => Some(Bands::XVTR3),
+            15 => Some(Bands::Band2200),
+            16 => Some(Bands::Band630),
+            17 => Some(Bands::Band11),
+            18 => Some(Bands::SWBC),
+            19 => Some(Bands::GEN),
+            20 => Some(Bands::USER1),
+            21 => Some(Bands::USER2),
+            22 => Some(Bands::USER3),
+            23 => Some(Bands::USER4),
+            24 => Some(Bands::USER5),
+            25 => Some(Bands::USER6),
+            26 => Some(Bands::USER7),
+            27 => Some(Bands::USER8),
             _ => None,
         }
     }
@@ -75,6 +101,35 @@ impl Bands {
 }
 
 
+// band plan segment types, drawn on the spectrum and used for TX checks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SegmentKind {
+    Cw,
+    Digital,
+    Phone,
+    Mixed,
+    Beacon,
+    Broadcast,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BandSegment {
+    pub low: f64,
+    pub high: f64,
+    pub kind: SegmentKind,
+}
+
+// band stacking registers per band, cycled by pressing the band button again
+pub const BAND_STACK_REGISTERS: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BandStack {
+    pub current: f64,
+    pub ctun: f64,
+    pub mode: Modes,
+    pub filter: Filters,
+}
+
 // Def Serialize, Deserialize,ine a type for our callback function
 pub type BandClickCallback = Box<dyn Fn(usize)>;
 
@@ -101,69 +156,164 @@ pub struct BandInfo {
     // for xvtrs
     pub lo: f64,        // Local Oscillator
     pub lo_error: f64,  // Oscillator error
+#[serde(default)]
+    pub xvtr_rx_gain: f32,      // transverter gain in dB, removed from the displayed levels
+#[serde(default = "default_xvtr_drive_limit")]
+    pub xvtr_drive_limit: f32,  // maximum drive in percent
+#[serde(default)]
+    pub xvtr_ptt_pin: u8,       // open collector output 1..7 asserted on transmit, 0 for none
+#[serde(default)]
+    pub xvtr_rx_only: bool,
+#[serde(default)]
+    pub stack: Vec<BandStack>, // filled from the band defaults when empty
+#[serde(default)]
+    pub stack_index: usize,
+    // from the band plan
+#[serde(skip_serializing, skip_deserializing)]
+    pub enabled: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub tx_allowed: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub segments: Vec<BandSegment>,
+}
+
+fn default_xvtr_drive_limit() -> f32 {
+    100.0
 }
 
 impl BandInfo {
     pub fn new() -> Vec<BandInfo> {
-        let bands = vec![
-            BandInfo{ band: Bands::Band160, label: String::from("160"), low: 1800000.0, high: 2000000.0, current: 1900000.0, ctun: 1900000.0, filters: 0x01800040, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band80, label: String::from("80"), low: 3500000.0, high: 3800000.0, current: 3750000.0, ctun: 3750000.0, filters: 0x01400020, spectrum_low: -100.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band60, label: String::from("60"), low: 5330500.0, high: 5403500.0, current: 5365500.0, ctun: 5365500.0, filters: 0x01200020, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band40, label: String::from("40"), low: 7000000.0, high: 7300000.0, current: 7150000.0, ctun: 7150000.0, filters: 0x01200010, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band30, label: String::from("30"), low: 10100000.0, high: 10150000.0, current: 10125000.0, ctun: 10125000.0, filters: 0x01200010, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band20, label: String::from("20"), low: 14000000.0, high: 14350000.0, current: 14175000.0, ctun: 14175000.0, filters: 0x01100002, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band17, label: String::from("17"), low: 18068000.0, high: 18168000.0, current: 18118000.0, ctun: 18118000.0, filters: 0x81000002, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band15, label: String::from("15"), low: 21000000.0, high: 21450000.0, current: 21215000.0, ctun: 21215000.0, filters: 0x81000002, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band12, label: String::from("12"), low: 24890000.0, high: 24990000.0, current: 24940000.0, ctun: 24940000.0, filters: 0x41000004, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band10, label: String::from("10"), low: 28000000.0, high: 29700000.0, current: 28300000.0, ctun: 28300000.0, filters: 0x41000004, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::Band6, label: String::from("6"), low: 50000000.0, high: 54000000.0, current: 52000000.0, ctun: 52999999.0, filters: 0x21000008, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::BandWWV, label: String::from("WWV"), low: 10000000.0, high: 10000000.0, current: 10000000.0, ctun: 10000000.0, filters: 0x20001000, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::SAM, filter: Filters::F3, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0},
-            BandInfo{ band: Bands::XVTR1, label: String::from("144"), low: 144000000.0, high: 148000000.0, current: 145000000.0, ctun: 145000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 116000000.0, lo_error: 0.0},
-            BandInfo{ band: Bands::XVTR2, label: String::from("432"), low: 432000000.0, high: 436000000.0, current: 435000000.0, ctun: 435000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 404000000.0, lo_error: 0.0},
-            BandInfo{ band: Bands::XVTR3, label: String::from("XVTR3"), low: 144000000.0, high: 148000000.0, current: 145000000.0, ctun: 145000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 116000000.0, lo_error: 0.0},
+        let mut bands = vec![
+            BandInfo{ band: Bands::Band160, label: String::from("160"), low: 1800000.0, high: 2000000.0, current: 1900000.0, ctun: 1900000.0, filters: 0x01800040, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band80, label: String::from("80"), low: 3500000.0, high: 3800000.0, current: 3750000.0, ctun: 3750000.0, filters: 0x01400020, spectrum_low: -100.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band60, label: String::from("60"), low: 5330500.0, high: 5403500.0, current: 5365500.0, ctun: 5365500.0, filters: 0x01200020, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band40, label: String::from("40"), low: 7000000.0, high: 7300000.0, current: 7150000.0, ctun: 7150000.0, filters: 0x01200010, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::LSB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band30, label: String::from("30"), low: 10100000.0, high: 10150000.0, current: 10125000.0, ctun: 10125000.0, filters: 0x01200010, spectrum_low: -110.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band20, label: String::from("20"), low: 14000000.0, high: 14350000.0, current: 14175000.0, ctun: 14175000.0, filters: 0x01100002, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band17, label: String::from("17"), low: 18068000.0, high: 18168000.0, current: 18118000.0, ctun: 18118000.0, filters: 0x81000002, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band15, label: String::from("15"), low: 21000000.0, high: 21450000.0, current: 21215000.0, ctun: 21215000.0, filters: 0x81000002, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band12, label: String::from("12"), low: 24890000.0, high: 24990000.0, current: 24940000.0, ctun: 24940000.0, filters: 0x41000004, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band10, label: String::from("10"), low: 28000000.0, high: 29700000.0, current: 28300000.0, ctun: 28300000.0, filters: 0x41000004, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band6, label: String::from("6"), low: 50000000.0, high: 54000000.0, current: 52000000.0, ctun: 52999999.0, filters: 0x21000008, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::BandWWV, label: String::from("WWV"), low: 10000000.0, high: 10000000.0, current: 10000000.0, ctun: 10000000.0, filters: 0x20001000, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::SAM, filter: Filters::F3, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: false, segments: Vec::new()},
+            BandInfo{ band: Bands::XVTR1, label: String::from("144"), low: 144000000.0, high: 148000000.0, current: 145000000.0, ctun: 145000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 116000000.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::XVTR2, label: String::from("432"), low: 432000000.0, high: 436000000.0, current: 435000000.0, ctun: 435000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 404000000.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::XVTR3, label: String::from("XVTR3"), low: 144000000.0, high: 148000000.0, current: 145000000.0, ctun: 145000000.0, filters: 0x41000004, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::FMN, filter: Filters::F3, antenna: Antenna::XVTR, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 116000000.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band2200, label: String::from("2200"), low: 135700.0, high: 137800.0, current: 136000.0, ctun: 136000.0, filters: 0x01800040, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::CWU, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band630, label: String::from("630"), low: 472000.0, high: 479000.0, current: 475000.0, ctun: 475000.0, filters: 0x01800040, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::CWU, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: true, segments: Vec::new()},
+            BandInfo{ band: Bands::Band11, label: String::from("11"), low: 26965000.0, high: 27405000.0, current: 27185000.0, ctun: 27185000.0, filters: 0x41000004, spectrum_low: -130.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::AM, filter: Filters::F3, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: false, segments: Vec::new()},
+            BandInfo{ band: Bands::SWBC, label: String::from("SWBC"), low: 2300000.0, high: 26100000.0, current: 9500000.0, ctun: 9500000.0, filters: 0x20001000, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::AM, filter: Filters::F3, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: false, segments: Vec::new()},
+            BandInfo{ band: Bands::GEN, label: String::from("GEN"), low: 100000.0, high: 55000000.0, current: 6000000.0, ctun: 6000000.0, filters: 0x20001000, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::AM, filter: Filters::F3, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: true, tx_allowed: false, segments: Vec::new()},
         ];
+        // slots for the bands a band plan adds, disabled until a plan lists them
+        let mut index = bands.len();
+        while let Some(band) = Bands::from_usize(index) {
+            let label = format!("USER{}", index - Bands::USER1.to_usize() + 1);
+            bands.push(BandInfo{ band, label, low: 0.0, high: 0.0, current: 0.0, ctun: 0.0, filters: 0x20001000, spectrum_low: -120.0, spectrum_high: -60.0, waterfall_low: -130.0, waterfall_high: -80.0, mode: Modes::USB, filter: Filters::F5, antenna: Antenna::ANT1, ext_antenna: Antenna::NONE, xvtr_antenna: Antenna::NONE, tx_antenna: Antenna::ANT1, attenuation: 0, lo: 0.0, lo_error: 0.0, xvtr_rx_gain: 0.0, xvtr_drive_limit: 100.0, xvtr_ptt_pin: 0, xvtr_rx_only: false, stack: Vec::new(), stack_index: 0, enabled: false, tx_allowed: false, segments: Vec::new()});
+            index += 1;
+        }
+        for info in bands.iter_mut() {
+            info.stack = Self::stack_defaults(info);
+        }
         bands
     }
 
+    // the band default followed by the CW and digital segments of the band plan
+    fn stack_defaults(info: &BandInfo) -> Vec<BandStack> {
+        let segments: &[(f64, Modes)] = match info.band {
+            Bands::Band160 => &[(1830000.0, Modes::CWL), (1840000.0, Modes::DIGU)],
+            Bands::Band80 => &[(3520000.0, Modes::CWL), (3573000.0, Modes::DIGU)],
+            Bands::Band60 => &[(5352000.0, Modes::CWU), (5357000.0, Modes::DIGU)],
+            Bands::Band40 => &[(7020000.0, Modes::CWL), (7074000.0, Modes::DIGU)],
+            Bands::Band30 => &[(10110000.0, Modes::CWU), (10136000.0, Modes::DIGU)],
+            Bands::Band20 => &[(14020000.0, Modes::CWU), (14074000.0, Modes::DIGU)],
+            Bands::Band17 => &[(18080000.0, Modes::CWU), (18100000.0, Modes::DIGU)],
+            Bands::Band15 => &[(21020000.0, Modes::CWU), (21074000.0, Modes::DIGU)],
+            Bands::Band12 => &[(24900000.0, Modes::CWU), (24915000.0, Modes::DIGU)],
+            Bands::Band10 => &[(28020000.0, Modes::CWU), (28074000.0, Modes::DIGU)],
+            Bands::Band6 => &[(50090000.0, Modes::CWU), (50313000.0, Modes::DIGU)],
+            _ => &[],
+        };
+        let mut stack = vec![BandStack { current: info.current, ctun: info.ctun, mode: info.mode, filter: info.filter }];
+        for (frequency, mode) in segments.iter() {
+            stack.push(BandStack { current: *frequency, ctun: *frequency, mode: *mode, filter: Filters::F5 });
+        }
+        while stack.len() < BAND_STACK_REGISTERS {
+            stack.push(stack[0]);
+        }
+        stack
+    }
+
+    // keep the active band stacking register in step with current/ctun/mode/filter
+    pub fn store_stack(&mut self) {
+        if self.stack.is_empty() {
+            self.stack = Self::stack_defaults(self);
+        }
+        self.stack_index %= self.stack.len();
+        self.stack[self.stack_index] = BandStack { current: self.current, ctun: self.ctun, mode: self.mode, filter: self.filter };
+    }
+
+    // make the next band stacking register current
+    pub fn next_stack(&mut self) {
+        if self.stack.is_empty() {
+            return;
+        }
+        self.stack_index = (self.stack_index + 1) % self.stack.len();
+        let stack = self.stack[self.stack_index];
+        self.current = stack.current;
+        self.ctun = stack.ctun;
+        self.mode = stack.mode;
+        self.filter = stack.filter;
+    }
+
+    pub fn is_xvtr(&self) -> bool {
+        matches!(self.band, Bands::XVTR1 | Bands::XVTR2 | Bands::XVTR3)
+    }
+
 }
 
 #[derive(Clone)]
 pub struct BandGrid {
     pub grid: Grid,
     buttons: Vec<Button>,
+    bands: Vec<usize>, // band index of each button
     active_index: Rc<RefCell<Option<usize>>>,
     callback: Rc<RefCell<Box<dyn Fn(usize) + 'static>>>
 }
 
+// band buttons per row of the grid
+const BAND_GRID_COLUMNS: usize = 3;
+
 impl BandGrid {
-    pub fn new(builder: &Builder) -> Self {
+    // one button for each band of the band plan, in band plan order
+    pub fn new(builder: &Builder, band_info: &[BandInfo], bands: &[usize]) -> Self {
         let grid: Grid = builder
                 .object("band_grid")
                 .expect("Could not get object 'band_grid' from builder.");
-        let mut buttons = Vec::with_capacity(18);
+        let mut buttons = Vec::with_capacity(bands.len());
         let active_index = Rc::new(RefCell::new(None));
         let callback = Rc::new(RefCell::new(Box::new(|_| {}) as Box<dyn Fn(usize)>));
 
-        let band_info = BandInfo::new();
-        for info in band_info.iter() {
-            let id = format!("{:?}_button", info.band);
-            let button: Button = builder
-                .object(id)
-                .expect("Could not get object band_button from builder.");
-            // update the label
-            button.set_label(&info.label);
+        for (i, band) in bands.iter().enumerate() {
+            let button = Button::with_label(&band_info[*band].label);
             // Set initial button style class
             button.add_css_class("inactive-button");
-            buttons.push(button.clone());
+            grid.attach(&button, (i % BAND_GRID_COLUMNS) as i32, (i / BAND_GRID_COLUMNS) as i32, 1, 1);
+            buttons.push(button);
         }
 
         BandGrid {
             grid,
             buttons,
+            bands: bands.to_vec(),
             active_index,
             callback,
         }
     }
+
+    fn position(&self, index: usize) -> Option<usize> {
+        self.bands.iter().position(|b| *b == index)
+    }
             
     pub fn set_callback<F>(&mut self, callback: F, initial_button: usize)
     where
@@ -175,15 +325,18 @@ impl BandGrid {
         *active_idx = Some(initial_button);
         for (i, button) in self.buttons.iter().enumerate() {
             let callback_clone = self.callback.clone();
-            let button_index = i;
+            let band_index = self.bands[i];
+            let bands_clone = self.bands.clone();
             let active_index_clone = self.active_index.clone();
             let buttons_clone = self.buttons.clone();
             button.connect_clicked(move |clicked_button| {
                 let mut active_idx = active_index_clone.borrow_mut();
                 if let Some(prev_idx) = *active_idx {
-                    let prev_button = &buttons_clone[prev_idx];
-                    prev_button.remove_css_class("active-button");
-                    prev_button.add_css_class("inactive-button");
+                    if let Some(prev_position) = bands_clone.iter().position(|b| *b == prev_idx) {
+                        let prev_button = &buttons_clone[prev_position];
+                        prev_button.remove_css_class("active-button");
+                        prev_button.add_css_class("inactive-button");
+                    }
                 }
 
                 // Set the style of the newly active button
@@ -191,10 +344,11 @@ impl BandGrid {
                 clicked_button.add_css_class("active-button");
 
                 // Update the active index
-                *active_idx = Some(button_index);
-                (callback_clone.borrow())(button_index);
+                *active_idx = Some(band_index);
+                drop(active_idx);
+                (callback_clone.borrow())(band_index);
             });
-            if i == initial_button {
+            if band_index == initial_button {
                 button.remove_css_class("inactive-button");
                 button.add_css_class("active-button");
             }
@@ -206,18 +360,23 @@ impl BandGrid {
     }
 
     pub fn set_active_index(&mut self, index: usize) {
-eprintln!("Bands::set_active_index {}", index);
-        let old_index: usize = self.active_index.borrow().expect("Bands: set_active_index error using active_index");
-        self.buttons[old_index].remove_css_class("active-button");
-        self.buttons[old_index].add_css_class("inactive-button");
+        if let Some(old_index) = *self.active_index.borrow() {
+            if let Some(position) = self.position(old_index) {
+                self.buttons[position].remove_css_class("active-button");
+                self.buttons[position].add_css_class("inactive-button");
+            }
+        }
         let mut active_idx = self.active_index.borrow_mut();
         *active_idx = Some(index);
-        self.buttons[index].remove_css_class("inactive-button");
-        self.buttons[index].add_css_class("active-button");
+        // bands outside the band plan have no button
+        if let Some(position) = self.position(index) {
+            self.buttons[position].remove_css_class("inactive-button");
+            self.buttons[position].add_css_class("active-button");
+        }
     }
 
-    pub fn get_button(&self, index: usize) -> &Button {
-        &self.buttons[index]
+    pub fn get_button(&self, index: usize) -> Option<&Button> {
+        self.position(index).map(|position| &self.buttons[position])
     }
 
     pub fn get_widget(&self) -> &Grid {
@@ -225,16 +384,92 @@ eprintln!("Bands::set_active_index {}", index);
     }
 
     pub fn update_band_label(&mut self, band: Bands, label: &str) {
-        self.buttons[band.to_usize()].set_label(label);
+        if let Some(position) = self.position(band.to_usize()) {
+            self.buttons[position].set_label(label);
+        }
     }
 
     pub fn get_current_label(&self) -> String {
-        let active_idx: usize = self.active_index.borrow().expect("Bands: set_active_index error using active_index");
-        if let Some(text) = &self.buttons[active_idx].label() {
-            text.to_string()
-        } else {
-            "".to_string()
+        let active_idx = *self.active_index.borrow();
+        match active_idx.and_then(|index| self.position(index)) {
+            Some(position) => match self.buttons[position].label() {
+                Some(text) => text.to_string(),
+                None => "".to_string(),
+            },
+            None => "".to_string(),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band(band: Bands) -> BandInfo {
+        BandInfo::new()[band.to_usize()].clone()
+    }
+
+    #[test]
+    fn new_bands_have_full_stacks() {
+        for info in BandInfo::new() {
+            assert_eq!(info.stack.len(), BAND_STACK_REGISTERS);
+            assert_eq!(info.stack_index, 0);
+            assert_eq!(info.stack[0].current, info.current);
+            assert_eq!(info.stack[0].mode, info.mode);
         }
     }
 
+    #[test]
+    fn next_stack_cycles_the_registers() {
+        let mut info = band(Bands::Band20);
+        info.next_stack();
+        assert_eq!(info.stack_index, 1);
+        assert_eq!(info.current, 14020000.0);
+        assert_eq!(info.ctun, 14020000.0);
+        assert_eq!(info.mode, Modes::CWU);
+        info.next_stack();
+        assert_eq!(info.stack_index, 2);
+        assert_eq!(info.current, 14074000.0);
+        assert_eq!(info.mode, Modes::DIGU);
+        info.next_stack();
+        assert_eq!(info.stack_index, 0);
+        assert_eq!(info.current, 14175000.0);
+        assert_eq!(info.mode, Modes::USB);
+    }
+
+    #[test]
+    fn store_stack_keeps_the_active_register() {
+        let mut info = band(Bands::Band20);
+        info.next_stack();
+        info.current = 14030000.0;
+        info.ctun = 14031000.0;
+        info.mode = Modes::CWL;
+        info.filter = Filters::F3;
+        info.store_stack();
+        assert_eq!(info.stack[2].current, 14074000.0);
+        info.next_stack();
+        info.next_stack();
+        info.next_stack();
+        assert_eq!(info.stack_index, 1);
+        assert_eq!(info.current, 14030000.0);
+        assert_eq!(info.ctun, 14031000.0);
+        assert_eq!(info.mode, Modes::CWL);
+        assert_eq!(info.filter, Filters::F3);
+    }
+
+    #[test]
+    fn empty_stack_from_an_older_configuration() {
+        let mut info = band(Bands::Band40);
+        info.stack.clear();
+        info.stack_index = 4;
+        info.next_stack();
+        assert_eq!(info.current, 7150000.0);
+        info.current = 7010000.0;
+        info.store_stack();
+        assert_eq!(info.stack.len(), BAND_STACK_REGISTERS);
+        assert_eq!(info.stack_index, 1);
+        assert_eq!(info.stack[1].current, 7010000.0);
+        assert_eq!(info.stack[2].current, 7074000.0);
+    }
 }
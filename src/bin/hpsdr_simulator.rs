@@ -0,0 +1,75 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*
+    Runs a simulated HPSDR radio on the local machine.
+
+    hpsdr_simulator [--protocol 1|2] [--address ip] [--board n]
+*/
+
+use std::env;
+use std::sync::{Arc, atomic::AtomicBool};
+
+use rustyHPSDR::simulator::{Protocol1Simulator, Protocol2Simulator};
+
+fn main() {
+    let mut protocol: u8 = 1;
+    let mut address = "0.0.0.0".to_string();
+    let mut board: Option<u8> = None;
+
+    let args: Vec<String> = env::args().collect();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--protocol" if i + 1 < args.len() => {
+                i += 1;
+                protocol = args[i].parse::<u8>().unwrap_or(1);
+                },
+            "--address" if i + 1 < args.len() => {
+                i += 1;
+                address = args[i].clone();
+                },
+            "--board" if i + 1 < args.len() => {
+                i += 1;
+                board = args[i].parse::<u8>().ok();
+                },
+            _ => {
+                eprintln!("Usage: {} [--protocol 1|2] [--address ip] [--board n]", args[0]);
+                return;
+                },
+        }
+        i += 1;
+    }
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let result = if protocol == 2 {
+        let mut simulator = Protocol2Simulator::new(address);
+        if let Some(b) = board {
+            simulator.set_board(b);
+        }
+        simulator.run(stop_flag)
+    } else {
+        let mut simulator = Protocol1Simulator::new(format!("{}:1024", address));
+        if let Some(b) = board {
+            simulator.set_board(b);
+        }
+        simulator.run(stop_flag)
+    };
+    if let Err(e) = result {
+        eprintln!("Simulator failed: {}", e);
+    }
+}
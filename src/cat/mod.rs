@@ -15,20 +15,41 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::ffi::CStr;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufReader, BufWriter, Read, Write};
-use std::net::TcpListener;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::fs::{symlink, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
 use std::sync::mpsc::{self};
+use std::thread;
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
+use crate::bands::Bands;
+use crate::modes::Modes;
 use crate::radio::RadioMutex;
 
 const RIG_ID: &str = "019"; // Kenwood TS-2000
 const DEBUG_CAT: bool = false;
+const AI_POLL_MS: u64 = 50;
+const PTY_POLL_MS: u64 = 500;
+const ACCEPT_POLL_MS: u64 = 100;
 
 pub enum CatMessage {
     UpdateMox(bool),
     UpdateFrequencyA(f64),
     UpdateFrequencyB(f64),
+    UpdateMode(usize, usize),
+    UpdateFilter(usize, f64, f64),
+    UpdateAFGain(usize, f32),
+    UpdateNR(usize, bool),
+    UpdateNB(usize, bool),
+    UpdateANF(usize, bool),
+    UpdateSplit(bool),
+    UpdateDrive(f32),
+    UpdateBand(usize, usize),
+    RecallMemory(usize, usize),
 }
 
 impl Default for CatMessage {
@@ -37,9 +58,63 @@ impl Default for CatMessage {
     }
 }
 
+// counts a client until its thread ends, even if it panics
+struct CatClient {
+    peer: SocketAddr,
+    clients: Arc<AtomicUsize>,
+}
+
+impl Drop for CatClient {
+    fn drop(&mut self) {
+        let active = self.clients.fetch_sub(1, Ordering::SeqCst) - 1;
+        eprintln!("CAT: client {} disconnected ({} active)", self.peer, active);
+    }
+}
+
+// Kenwood MD/IF mode numbers, 1=LSB 2=USB 3=CW 4=FM 5=AM 6=FSK 7=CW-R 9=FSK-R
+fn mode_to_kenwood(mode: usize) -> u8 {
+    match Modes::from_usize(mode) {
+        Some(Modes::LSB) => 1,
+        Some(Modes::USB) => 2,
+        Some(Modes::CWU) => 3,
+        Some(Modes::FMN) => 4,
+        Some(Modes::AM) | Some(Modes::SAM) | Some(Modes::DSB) | Some(Modes::DRM) => 5,
+        Some(Modes::DIGL) => 6,
+        Some(Modes::CWL) => 7,
+        Some(Modes::DIGU) => 9,
+        _ => 2,
+    }
+}
+
+fn kenwood_to_mode(mode: u8) -> Option<usize> {
+    let mode = match mode {
+        1 => Modes::LSB,
+        2 => Modes::USB,
+        3 => Modes::CWU,
+        4 => Modes::FMN,
+        5 => Modes::AM,
+        6 => Modes::DIGL,
+        7 => Modes::CWL,
+        9 => Modes::DIGU,
+        _ => return None,
+    };
+    Some(mode.to_usize())
+}
+
+// radio state watched for AI2 auto information
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CatState {
+    frequency_a: f64,
+    frequency_b: f64,
+    mode: usize,
+    transmitting: bool,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CAT {
     pub address: String,
+#[serde(skip_serializing, skip_deserializing)]
+    ai: u8,
 }
 
 impl CAT {
@@ -47,8 +122,10 @@ impl CAT {
     pub fn new(network_address: String) -> Self {
 if DEBUG_CAT {eprintln!("CAT::new");}
         let address = network_address;
+        let ai = 0;
         CAT {
             address,
+            ai,
         }
     }
 
@@ -58,26 +135,84 @@ if DEBUG_CAT {eprintln!("CAT::new");}
 
     pub fn run(&mut self, radio_mutex: &RadioMutex, tx: &mpsc::Sender<CatMessage>, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
 if DEBUG_CAT {eprintln!("CAT::run");}
-        // lsten for a connection
+        // lsten for connections, non blocking so the stop flag is seen
         let listener = TcpListener::bind(&self.address)?;
+        listener.set_nonblocking(true)?;
+        let clients = Arc::new(AtomicUsize::new(0));
 
-
+if DEBUG_CAT {eprintln!("CAT::listening");}
         while !stop_flag.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    let r = radio_mutex.radio.lock().unwrap();
+                    let max_clients = r.cat_max_clients;
+                    drop(r);
+                    if clients.load(Ordering::SeqCst) >= max_clients {
+                        eprintln!("CAT: rejected client {} (limit of {} clients)", peer, max_clients);
+                        continue;
+                    }
+                    let active = clients.fetch_add(1, Ordering::SeqCst) + 1;
+                    eprintln!("CAT: client {} connected ({} active)", peer, active);
 
-if DEBUG_CAT {eprintln!("CAT::listening");}
-        let stream = listener.incoming().next().expect("Failed to accept connection")?;
+                    // each client has its own AI setting
+                    let mut client = self.clone();
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let tx_clone = tx.clone();
+                    let stop_flag_clone = stop_flag.clone();
+                    let cat_client = CatClient{peer, clients: clients.clone()};
+                    thread::spawn(move || {
+                        let _cat_client = cat_client;
+                        if let Err(e) = client.serve_client(stream, &radio_mutex_clone, &tx_clone, stop_flag_clone) {
+                            eprintln!("CAT: client {} error {}", peer, e);
+                        }
+                    });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+                }
+                Err(e) => {
+                    eprintln!("CAT: accept error {}", e);
+                    thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+                }
+            }
+        }
+if DEBUG_CAT {eprintln!("CAT::run: exiting");}
+        Ok(())
+    }
+
+    fn serve_client(&mut self, stream: TcpStream, radio_mutex: &RadioMutex, tx: &mpsc::Sender<CatMessage>, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
         let reader_stream = stream.try_clone()?;
-        let mut writer_stream = stream.try_clone()?;
+        let writer_stream = stream.try_clone()?;
         let mut reader = BufReader::new(reader_stream);
         let mut writer = BufWriter::new(writer_stream);
         let mut received_data = [0; 1024];
 
+        // wake up regularly so AI2 changes can be pushed to the client
+        stream.set_read_timeout(Some(Duration::from_millis(AI_POLL_MS)))?;
+        self.ai = 0;
+        let mut last_state = self.get_state(radio_mutex);
+
 if DEBUG_CAT {eprintln!("CAT::running");}
         while !stop_flag.load(Ordering::Relaxed) {
+            if self.ai == 2 {
+                let state = self.get_state(radio_mutex);
+                if state != last_state {
+                    let frames = self.auto_information(&last_state, &state, radio_mutex, tx.clone());
+if DEBUG_CAT {eprintln!("CAT::run: auto information {:?}", frames);}
+                    for frame in frames {
+                        writer.write_all(frame.as_bytes())?;
+                    }
+                    writer.flush()?;
+                    last_state = state;
+                }
+            } else {
+                // so turning on AI2 only reports later changes
+                last_state = self.get_state(radio_mutex);
+            }
             match reader.read(&mut received_data) {
                 Ok(0) => {
                     // client closed
-                    eprintln!("cat: client closed connection");
                     break;
                     }
                 Ok(bytes_read) => {
@@ -90,18 +225,183 @@ if DEBUG_CAT {eprintln!("CAT::run: reply {:?}", reply);}
                         }
                     }
                     }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    // no command from the client
+                    }
                 Err(e) => {
-                    eprintln!("cat error {}", e);
-                    break;
+                    return Err(e);
                     }
             }
         }
+        Ok(())
+    }
+
+    // serve CAT on a pseudo-terminal linked from cat_pty_path while cat_pty_enabled is set
+    pub fn run_pty(&mut self, radio_mutex: &RadioMutex, tx: &mpsc::Sender<CatMessage>, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
+if DEBUG_CAT {eprintln!("CAT::run_pty");}
+        while !stop_flag.load(Ordering::Relaxed) {
+            let r = radio_mutex.radio.lock().unwrap();
+            let enabled = r.cat_pty_enabled;
+            let path = r.cat_pty_path.clone();
+            drop(r);
+            if !enabled || path.is_empty() {
+                thread::sleep(Duration::from_millis(PTY_POLL_MS));
+                continue;
+            }
+
+            let (mut master, slave_name, _slave) = match Self::open_pty() {
+                Ok(pty) => pty,
+                Err(e) => {
+                    eprintln!("CAT: failed to create serial port: {}", e);
+                    thread::sleep(Duration::from_millis(PTY_POLL_MS));
+                    continue;
+                }
+            };
+            if let Err(e) = Self::create_link(&slave_name, &path) {
+                eprintln!("CAT: failed to link {} to {}: {}", path, slave_name, e);
+                thread::sleep(Duration::from_millis(PTY_POLL_MS));
+                continue;
+            }
+            eprintln!("CAT: serial port {} -> {}", path, slave_name);
+
+            self.ai = 0;
+            let mut last_state = self.get_state(radio_mutex);
+            let mut received_data = [0; 1024];
+            let mut input = String::new();
+            while !stop_flag.load(Ordering::Relaxed) {
+                let r = radio_mutex.radio.lock().unwrap();
+                let changed = !r.cat_pty_enabled || r.cat_pty_path != path;
+                drop(r);
+                if changed {
+                    break;
+                }
+
+                if self.ai == 2 {
+                    let state = self.get_state(radio_mutex);
+                    if state != last_state {
+                        for frame in self.auto_information(&last_state, &state, radio_mutex, tx.clone()) {
+                            let _ = master.write_all(frame.as_bytes());
+                        }
+                        last_state = state;
+                    }
+                } else {
+                    last_state = self.get_state(radio_mutex);
+                }
+
+                match master.read(&mut received_data) {
+                    Ok(0) => {
+                        thread::sleep(Duration::from_millis(AI_POLL_MS));
+                    }
+                    Ok(bytes_read) => {
+                        // serial data can arrive a character at a time so only parse complete commands
+                        input.push_str(&String::from_utf8_lossy(&received_data[..bytes_read]));
+                        if let Some(end) = input.rfind(';') {
+                            let commands: String = input.drain(..=end).collect();
+                            let reply = self.parse_commands(&commands, radio_mutex, tx.clone());
+if DEBUG_CAT {eprintln!("CAT::run_pty: reply {:?}", reply);}
+                            for i in 0..reply.len() {
+                                if reply[i].len() > 0 {
+                                    let _ = master.write_all(reply[i].as_bytes());
+                                }
+                            }
+                        }
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(AI_POLL_MS));
+                    }
+                    Err(e) => {
+                        eprintln!("CAT: serial port error {}", e);
+                        break;
+                    }
+                }
+            }
+            let _ = fs::remove_file(&path);
+            eprintln!("CAT: serial port {} closed", path);
         }
-if DEBUG_CAT {eprintln!("CAT::run: exiting");}
+if DEBUG_CAT {eprintln!("CAT::run_pty: exiting");}
         Ok(())
     }
 
-    fn parse_commands(&self, input: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> Vec<String> {
+    // returns the non blocking master, the slave device name and the slave held open
+    fn open_pty() -> io::Result<(File, String, File)> {
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master_file = unsafe { File::from_raw_fd(master) };
+        let mut name = [0 as libc::c_char; 128];
+        unsafe {
+            if libc::grantpt(master) != 0 || libc::unlockpt(master) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ptsname_r(master, name.as_mut_ptr(), name.len()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let slave_name = unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned();
+
+        // keep the slave open so the master does not see a hangup between clients
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&slave_name)?;
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(slave.as_raw_fd(), &mut termios) == 0 {
+                libc::cfmakeraw(&mut termios);
+                libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios);
+            }
+            let flags = libc::fcntl(master, libc::F_GETFL);
+            libc::fcntl(master, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+        Ok((master_file, slave_name, slave))
+    }
+
+    // point the stable path at the pty, only ever replacing an old symlink
+    fn create_link(slave_name: &str, path: &str) -> io::Result<()> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_symlink() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "path exists and is not a symlink"));
+            }
+            fs::remove_file(path)?;
+        }
+        symlink(slave_name, path)
+    }
+
+    fn get_state(&self, radio_mutex: &RadioMutex) -> CatState {
+        let r = radio_mutex.radio.lock().unwrap();
+        let frequency_a = if r.receiver[0].ctun { r.receiver[0].ctun_frequency } else { r.receiver[0].frequency };
+        let frequency_b = if r.receiver[1].ctun { r.receiver[1].ctun_frequency } else { r.receiver[1].frequency };
+        let mode = r.receiver[0].mode;
+        let transmitting = r.is_transmitting();
+        CatState {
+            frequency_a,
+            frequency_b,
+            mode,
+            transmitting,
+        }
+    }
+
+    // unsolicited frames for AI2 describing what changed
+    fn auto_information(&self, last: &CatState, state: &CatState, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> Vec<String> {
+        let mut frames = Vec::new();
+        if state.frequency_a != last.frequency_a {
+            frames.push(self.FA_cmd("", radio_mutex, tx.clone()));
+        }
+        if state.frequency_b != last.frequency_b {
+            frames.push(self.FB_cmd("", radio_mutex, tx.clone()));
+        }
+        if state.mode != last.mode {
+            frames.push(self.MD_cmd("", radio_mutex, tx.clone()));
+        }
+        if state.frequency_a != last.frequency_a || state.mode != last.mode || state.transmitting != last.transmitting {
+            frames.push(self.IF_cmd("", radio_mutex, tx.clone()));
+        }
+        frames
+    }
+
+    fn parse_commands(&mut self, input: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> Vec<String> {
         let cmd = input.trim_end_matches(';').to_uppercase(); // cmd does not include the ;
         let commands: Vec<&str> = input
             .split(';')
@@ -112,6 +412,10 @@ if DEBUG_CAT {eprintln!("CAT::run: exiting");}
         let mut reply = Vec::new();
 
         for (i, cmd) in commands.iter().enumerate() {
+            if cmd.len() < 2 {
+                reply.push("?;".to_string());
+                continue;
+            }
             let command_code = &cmd[..2];
             let suffix = &cmd[2..];
 if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suffix);}
@@ -122,6 +426,8 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
                 "FA" => self.FA_cmd(suffix, radio_mutex, tx.clone()),
                 "FB" => self.FB_cmd(suffix, radio_mutex, tx.clone()),
                 "KS" => self.KS_cmd(suffix, radio_mutex, tx.clone()),
+                "KY" => self.KY_cmd(suffix, radio_mutex, tx.clone()),
+                "MC" => self.MC_cmd(suffix, radio_mutex, tx.clone()),
                 "MD" => self.MD_cmd(suffix, radio_mutex, tx.clone()),
                 "RX" => self.RX_cmd(suffix, radio_mutex, tx.clone()),
                 "VX" => self.VX_cmd(suffix, radio_mutex, tx.clone()),
@@ -134,14 +440,256 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
     }
 
     fn parse_zz_command(&self, command: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if command.len() < 4 {
+            return "?;".to_string();
+        }
         let cmd = &command[..4];
         let suffix = &command[4..];
-        "?;".to_string()
+        match cmd {
+            "ZZFA" => self.ZZFA_cmd(suffix, radio_mutex, tx),
+            "ZZFB" => self.ZZFB_cmd(suffix, radio_mutex, tx),
+            "ZZMD" => self.ZZMD_cmd(suffix, radio_mutex, tx),
+            "ZZAG" => self.ZZAG_cmd(suffix, radio_mutex, tx),
+            "ZZFL" => self.ZZFL_cmd(suffix, radio_mutex, tx),
+            "ZZFH" => self.ZZFH_cmd(suffix, radio_mutex, tx),
+            "ZZNR" => self.ZZNR_cmd(suffix, radio_mutex, tx),
+            "ZZNB" => self.ZZNB_cmd(suffix, radio_mutex, tx),
+            "ZZNT" => self.ZZNT_cmd(suffix, radio_mutex, tx),
+            "ZZSP" => self.ZZSP_cmd(suffix, radio_mutex, tx),
+            "ZZSM" => self.ZZSM_cmd(suffix, radio_mutex, tx),
+            "ZZPC" => self.ZZPC_cmd(suffix, radio_mutex, tx),
+            "ZZBS" => self.ZZBS_cmd(suffix, radio_mutex, tx),
+            _ => "?;".to_string(),
+        }
+    }
+
+    // Thetis band codes used by ZZBS
+    fn band_to_zz(band: Bands) -> &'static str {
+        match band {
+            Bands::Band160 => "160",
+            Bands::Band80 => "080",
+            Bands::Band60 => "060",
+            Bands::Band40 => "040",
+            Bands::Band30 => "030",
+            Bands::Band20 => "020",
+            Bands::Band17 => "017",
+            Bands::Band15 => "015",
+            Bands::Band12 => "012",
+            Bands::Band10 => "010",
+            Bands::Band6 => "006",
+            Bands::BandWWV => "999",
+            _ => "888", // transverter and other bands are reported as general coverage
+        }
+    }
+
+    fn band_from_zz(code: &str) -> Option<Bands> {
+        match code {
+            "160" => Some(Bands::Band160),
+            "080" => Some(Bands::Band80),
+            "060" => Some(Bands::Band60),
+            "040" => Some(Bands::Band40),
+            "030" => Some(Bands::Band30),
+            "020" => Some(Bands::Band20),
+            "017" => Some(Bands::Band17),
+            "015" => Some(Bands::Band15),
+            "012" => Some(Bands::Band12),
+            "010" => Some(Bands::Band10),
+            "006" => Some(Bands::Band6),
+            "999" => Some(Bands::BandWWV),
+            "888" => Some(Bands::GEN),
+            _ => None,
+        }
+    }
+
+    fn ZZFA_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        let reply = self.FA_cmd(suffix, radio_mutex, tx);
+        if reply.is_empty() { reply } else { format!("ZZ{}", reply) }
+    }
+
+    fn ZZFB_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        let reply = self.FB_cmd(suffix, radio_mutex, tx);
+        if reply.is_empty() { reply } else { format!("ZZ{}", reply) }
+    }
+
+    fn ZZMD_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // Thetis mode numbers match the Modes enum
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZMD{:02};", r.receiver[0].mode)
+        } else {
+            match suffix.parse::<usize>() {
+                Ok(mode) if mode <= Modes::DRM.to_usize() => {
+                    if tx.send(CatMessage::UpdateMode(0, mode)).is_err() {
+                        eprintln!("ZZMD_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                _ => "?;".to_string(),
+            }
+        }
+    }
+
+    fn ZZAG_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // AF gain 000..100
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZAG{:03};", (r.receiver[0].afgain * 100.0).round() as i32)
+        } else {
+            match suffix.parse::<i32>() {
+                Ok(gain) => {
+                    let gain = gain.clamp(0, 100) as f32 / 100.0;
+                    if tx.send(CatMessage::UpdateAFGain(0, gain)).is_err() {
+                        eprintln!("ZZAG_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                Err(_) => "?;".to_string(),
+            }
+        }
+    }
+
+    fn ZZFL_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // filter low edge in Hz, sign and 4 digits
+        let r = radio_mutex.radio.lock().unwrap();
+        if suffix == "" {
+            format!("ZZFL{:+05};", r.receiver[0].filter_low as i32)
+        } else {
+            match suffix.parse::<f64>() {
+                Ok(low) => {
+                    let high = r.receiver[0].filter_high;
+                    if tx.send(CatMessage::UpdateFilter(0, low, high)).is_err() {
+                        eprintln!("ZZFL_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                Err(_) => "?;".to_string(),
+            }
+        }
     }
 
-    fn AI_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
-        // TODO
-        "?;".to_string()
+    fn ZZFH_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // filter high edge in Hz, sign and 4 digits
+        let r = radio_mutex.radio.lock().unwrap();
+        if suffix == "" {
+            format!("ZZFH{:+05};", r.receiver[0].filter_high as i32)
+        } else {
+            match suffix.parse::<f64>() {
+                Ok(high) => {
+                    let low = r.receiver[0].filter_low;
+                    if tx.send(CatMessage::UpdateFilter(0, low, high)).is_err() {
+                        eprintln!("ZZFH_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                Err(_) => "?;".to_string(),
+            }
+        }
+    }
+
+    fn ZZNR_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            let nr = r.receiver[0].nr || r.receiver[0].nr2 || r.receiver[0].nr3 || r.receiver[0].nr4;
+            format!("ZZNR{};", nr as u8)
+        } else {
+            if tx.send(CatMessage::UpdateNR(0, suffix == "1")).is_err() {
+                eprintln!("ZZNR_cmd: Main thread receiver was dropped.");
+            }
+            "".to_string()
+        }
+    }
+
+    fn ZZNB_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZNB{};", (r.receiver[0].nb || r.receiver[0].nb2) as u8)
+        } else {
+            if tx.send(CatMessage::UpdateNB(0, suffix == "1")).is_err() {
+                eprintln!("ZZNB_cmd: Main thread receiver was dropped.");
+            }
+            "".to_string()
+        }
+    }
+
+    fn ZZNT_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZNT{};", r.receiver[0].anf as u8)
+        } else {
+            if tx.send(CatMessage::UpdateANF(0, suffix == "1")).is_err() {
+                eprintln!("ZZNT_cmd: Main thread receiver was dropped.");
+            }
+            "".to_string()
+        }
+    }
+
+    fn ZZSP_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZSP{};", r.split as u8)
+        } else {
+            if tx.send(CatMessage::UpdateSplit(suffix == "1")).is_err() {
+                eprintln!("ZZSP_cmd: Main thread receiver was dropped.");
+            }
+            "".to_string()
+        }
+    }
+
+    fn ZZSM_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // S meter 000..260 in half dB steps from -140 dBm
+        let r = radio_mutex.radio.lock().unwrap();
+        let rx = if suffix == "1" { 1 } else { 0 };
+        let dbm = r.receiver_level(rx);
+        let level = ((dbm + 140.0) * 2.0).clamp(0.0, 260.0) as i32;
+        format!("ZZSM{}{:03};", rx, level)
+    }
+
+    fn ZZPC_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        // drive level 000..100
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZPC{:03};", r.transmitter.drive as i32)
+        } else {
+            match suffix.parse::<i32>() {
+                Ok(drive) => {
+                    if tx.send(CatMessage::UpdateDrive(drive.clamp(0, 100) as f32)).is_err() {
+                        eprintln!("ZZPC_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                Err(_) => "?;".to_string(),
+            }
+        }
+    }
+
+    fn ZZBS_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        if suffix == "" {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("ZZBS{};", Self::band_to_zz(r.receiver[0].band))
+        } else {
+            match Self::band_from_zz(suffix) {
+                Some(band) => {
+                    if tx.send(CatMessage::UpdateBand(0, band.to_usize())).is_err() {
+                        eprintln!("ZZBS_cmd: Main thread receiver was dropped.");
+                    }
+                    "".to_string()
+                }
+                None => "?;".to_string(),
+            }
+        }
+    }
+
+    fn AI_cmd(&mut self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        let mut reply = "".to_string();
+        if suffix == "" {
+            reply = format!("AI{};", self.ai);
+        } else {
+            match suffix.parse::<u8>() {
+                Ok(ai) if ai <= 2 => self.ai = ai,
+                _ => reply = "?;".to_string(),
+            }
+        }
+        reply
     }
 
     fn FA_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
@@ -156,7 +704,10 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
             }
         } else {
             // set the frequency
-            let f = suffix.parse::<f64>().unwrap();
+            let f = match suffix.parse::<f64>() {
+                Ok(f) => f,
+                Err(_) => return "?;".to_string(),
+            };
             if tx.send(CatMessage::UpdateFrequencyA(f)).is_err() {
                 eprintln!("TX_cmd: Main thread receiver was dropped.");
             }
@@ -176,7 +727,10 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
             }
         } else {
             // set the frequency
-            let f = suffix.parse::<f64>().unwrap();
+            let f = match suffix.parse::<f64>() {
+                Ok(f) => f,
+                Err(_) => return "?;".to_string(),
+            };
             if tx.send(CatMessage::UpdateFrequencyB(f)).is_err() {
                 eprintln!("TX_cmd: Main thread receiver was dropped.");
             }
@@ -204,7 +758,7 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
             0, // Memory Bank Number
             0, // Channel Number
             r.is_transmitting() as u8,
-            r.receiver[0].mode,
+            mode_to_kenwood(r.receiver[0].mode),
             0, // VFO A/B
             0, // Scan Status
             r.split as u8,
@@ -224,13 +778,54 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
         reply
     }
 
-    fn MD_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+    // send text as CW, KY; reports 1 when the buffer is full
+    fn KY_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        let mut r = radio_mutex.radio.lock().unwrap();
+        let mut reply = "".to_string();
+        if suffix == "" {
+            reply = format!("KY{};", r.cw_sender.is_full() as u8);
+        } else if r.cw_sender.is_full() || !r.cw_send_text(suffix.strip_prefix(' ').unwrap_or(suffix)) {
+            reply = "?;".to_string();
+        }
+        reply
+    }
+
+    // memory channel in the current bank
+    fn MC_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
         let mut r = radio_mutex.radio.lock().unwrap();
         let mut reply = "".to_string();
         if suffix == "" {
-            reply = format!("MD{};", r.receiver[0].mode);
+            reply = format!("MC{:03};", r.memories.channel);
+        } else if let Ok(channel) = suffix.trim().parse::<usize>() {
+            if r.memories.get(channel).is_some() {
+                r.memories.channel = channel;
+                if tx.send(CatMessage::RecallMemory(0, channel)).is_err() {
+                    eprintln!("MC_cmd: Main thread receiver was dropped.");
+                }
+            } else {
+                reply = "?;".to_string();
+            }
+        } else {
+            reply = "?;".to_string();
+        }
+        reply
+    }
+
+    fn MD_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
+        let r = radio_mutex.radio.lock().unwrap();
+        let mut reply = "".to_string();
+        if suffix == "" {
+            reply = format!("MD{};", mode_to_kenwood(r.receiver[0].mode));
         } else {
             // set the mode
+            match suffix.parse::<u8>().ok().and_then(kenwood_to_mode) {
+                Some(mode) => {
+                    if tx.send(CatMessage::UpdateMode(0, mode)).is_err() {
+                        eprintln!("MD_cmd: Main thread receiver was dropped.");
+                    }
+                }
+                None => reply = "?;".to_string(),
+            }
         }
         reply
     }
@@ -259,6 +854,9 @@ if DEBUG_CAT {eprintln!("CAT::parse_command: {} = {} {}", cmd, command_code, suf
     fn TX_cmd(&self, suffix: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<CatMessage>) -> String {
         let mut r = radio_mutex.radio.lock().unwrap();
         let mut reply = "".to_string();
+        if !r.check_transmit() {
+            return "?;".to_string();
+        }
         r.external_mox = true;
         if tx.send(CatMessage::UpdateMox(true)).is_err() {
             eprintln!("TX_cmd: Main thread receiver was dropped.");
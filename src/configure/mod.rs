@@ -16,17 +16,22 @@
 */
 
 use gtk::prelude::*;
-use gtk::{Adjustment, Builder, Button, CheckButton, DrawingArea, DropDown, Entry, Frame, Grid, Label, ListBox, ListBoxRow, Orientation, PositionType, Scale, StringList, ToggleButton, Window};
+use gtk::{gio, glib};
+use gtk::{Adjustment, Builder, Button, CheckButton, DrawingArea, DropDown, Entry, FileDialog, Frame, Grid, Label, ListBox, ListBoxRow, Orientation, PositionType, Scale, StringList, ToggleButton, Window};
 
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use crate::antenna::Antenna;
+use crate::bandplan::{BandPlan, CUSTOM_BAND_PLAN};
 use crate::bands::Bands;
 use crate::discovery::Boards;
 use crate::radio::{Keyer, RadioModels, RadioMutex};
 use crate::receiver::{AudioOutput};
 use crate::audio::*;
+use crate::scanner::{ScanMode, ScanStop};
+use crate::transmitter::Transmitter;
+use crate::midi::{midi_index_name, MidiAction, MidiFunction, MidiLearned, MidiProfile, MidiType, MIDI};
 use crate::widgets::*;
 
 pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex) -> Window {
@@ -1568,6 +1573,43 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
         }    
     });
 */   
+
+    // CAT serial port
+    let r = radio_mutex.radio.lock().unwrap();
+        let cat_pty_enabled = r.cat_pty_enabled;
+        let cat_pty_path = r.cat_pty_path.clone();
+    drop(r);
+    let cat_pty_check_button: CheckButton = builder
+            .object("cat_pty_check_button")
+            .expect("Could not get object `cat_pty_check_button` from builder.");
+    cat_pty_check_button.set_active(cat_pty_enabled);
+    let radio_mutex_clone = radio_mutex.clone();
+    cat_pty_check_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cat_pty_enabled = button.is_active();
+    });
+    let cat_pty_entry: Entry = builder
+            .object("cat_pty_entry")
+            .expect("Could not get object `cat_pty_entry` from builder.");
+    cat_pty_entry.set_text(&cat_pty_path);
+    let radio_mutex_clone = radio_mutex.clone();
+    cat_pty_entry.connect_activate(move |entry| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cat_pty_path = entry.text().to_string();
+    });
+
+    let r = radio_mutex.radio.lock().unwrap();
+        let cat_max_clients = r.cat_max_clients;
+    drop(r);
+    let cat_max_clients_adjustment: Adjustment = builder
+            .object("cat_max_clients_adjustment")
+            .expect("Could not get object `cat_max_clients_adjustment` from builder.");
+    cat_max_clients_adjustment.set_value(cat_max_clients as f64);
+    let radio_mutex_clone = radio_mutex.clone();
+    cat_max_clients_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cat_max_clients = adjustment.value() as usize;
+    });
     
     // Microphone
     let r = radio_mutex.radio.lock().unwrap();
@@ -2062,21 +2104,138 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     }); 
 
     // Pure Signal
+    let r = radio_mutex.radio.lock().unwrap();
+        let ps_supported = r.transmitter.puresignal_supported();
+        let puresignal = r.transmitter.puresignal;
+        let ps_auto = r.transmitter.ps_auto;
+        let ps_hw_peak = r.transmitter.ps_hw_peak;
+        let ps_hw_peak_default = Transmitter::default_ps_hw_peak(r.protocol, r.board);
+    drop(r);
+
+    let ps_enable: CheckButton = builder
+            .object("ps_enable")
+            .expect("Could not get object `ps_enable` from builder.");
+    ps_enable.set_active(puresignal);
+    ps_enable.set_sensitive(ps_supported);
+    let radio_mutex_clone = radio_mutex.clone();
+    ps_enable.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.transmitter.puresignal = button.is_active();
+        r.transmitter.set_puresignal();
+        r.updated = true;
+    });
+
+    let ps_auto_check_button: CheckButton = builder
+            .object("ps_auto_check_button")
+            .expect("Could not get object `ps_auto_check_button` from builder.");
+    ps_auto_check_button.set_active(ps_auto);
+    ps_auto_check_button.set_sensitive(ps_supported);
+    let radio_mutex_clone = radio_mutex.clone();
+    ps_auto_check_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.transmitter.ps_auto = button.is_active();
+        if r.transmitter.puresignal {
+            r.transmitter.set_puresignal();
+        }
+    });
+
+    let ps_single_cal_button: Button = builder
+            .object("ps_single_cal_button")
+            .expect("Could not get object `ps_single_cal_button` from builder.");
+    ps_single_cal_button.set_sensitive(ps_supported);
+    let radio_mutex_clone = radio_mutex.clone();
+    ps_single_cal_button.connect_clicked(move |_| {
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        r.transmitter.ps_single_cal();
+    });
+
+    let ps_hw_peak_adjustment: Adjustment = builder
+            .object("ps_hw_peak_adjustment")
+            .expect("Could not get object `ps_hw_peak_adjustment` from builder.");
+    ps_hw_peak_adjustment.set_value(ps_hw_peak);
+    let radio_mutex_clone = radio_mutex.clone();
+    ps_hw_peak_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.transmitter.ps_hw_peak = adjustment.value();
+        r.transmitter.set_ps_hw_peak();
+    });
+
+    let ps_hw_peak_default_button: Button = builder
+            .object("ps_hw_peak_default_button")
+            .expect("Could not get object `ps_hw_peak_default_button` from builder.");
+    let ps_hw_peak_adjustment_clone = ps_hw_peak_adjustment.clone();
+    ps_hw_peak_default_button.connect_clicked(move |_| {
+        ps_hw_peak_adjustment_clone.set_value(ps_hw_peak_default);
+    });
+
     let ps_correcting: DrawingArea = builder
             .object("ps_correcting")
             .expect("Could not get object `ps_correcting` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
     ps_correcting.set_draw_func(move | _, cr, _, _| {
-         cr.set_source_rgb(0.0, 0.0, 0.0); // black need to update color based on status
+         let r = radio_mutex_clone.radio.lock().unwrap();
+         if r.transmitter.puresignal && r.transmitter.ps_correcting() {
+             cr.set_source_rgb(0.0, 1.0, 0.0);
+         } else {
+             cr.set_source_rgb(0.0, 0.0, 0.0);
+         }
+         drop(r);
          cr.paint().unwrap();
     });
     let ps_feedback_level: DrawingArea = builder
             .object("ps_feedback_level")
             .expect("Could not get object `ps_feedback_level` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
     ps_feedback_level.set_draw_func(move | _, cr, _, _| {
-         cr.set_source_rgb(0.0, 0.0, 0.0); // black need to update color based on status
+         let r = radio_mutex_clone.radio.lock().unwrap();
+         let status = if r.transmitter.puresignal { r.transmitter.ps_feedback_status() } else { "none" };
+         drop(r);
+         match status {
+             "high" => cr.set_source_rgb(1.0, 0.0, 0.0),
+             "ok" => cr.set_source_rgb(0.0, 1.0, 0.0),
+             "low" => cr.set_source_rgb(1.0, 1.0, 0.0),
+             _ => cr.set_source_rgb(0.0, 0.0, 0.0),
+         }
          cr.paint().unwrap();
     });
 
+    // calibration information labels and the GetPSInfo index shown in each
+    let mut ps_info_labels: Vec<(Label, usize)> = Vec::new();
+    for (id, index) in [("bldr_rx", 0), ("bldr_cm", 1), ("bldr_cc", 2), ("bldr_cs", 3), ("feedbk", 4), ("cor_cnt", 5), ("sln_chk", 6), ("dg_cnt", 13), ("state", 15)] {
+        let label: Label = builder
+                .object(id)
+                .expect("Could not get PureSignal info label from builder.");
+        ps_info_labels.push((label, index));
+    }
+    let get_pk: Label = builder
+            .object("get_pk")
+            .expect("Could not get object `get_pk` from builder.");
+    let set_pk: Label = builder
+            .object("set_pk")
+            .expect("Could not get object `set_pk` from builder.");
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let ps_feedback_level_weak = ps_feedback_level.downgrade();
+    glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
+        let Some(ps_feedback_level) = ps_feedback_level_weak.upgrade() else {
+            // dialog has gone
+            return glib::ControlFlow::Break;
+        };
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        if r.transmitter.puresignal {
+            r.transmitter.update_ps_info();
+            for (label, index) in &ps_info_labels {
+                label.set_label(&format!("{:3}", r.transmitter.ps_info[*index]));
+            }
+            get_pk.set_label(&format!("{:.4}", r.transmitter.ps_max_tx()));
+        }
+        set_pk.set_label(&format!("{:.4}", r.transmitter.ps_hw_peak));
+        drop(r);
+        ps_feedback_level.queue_draw();
+        ps_correcting.queue_draw();
+        glib::ControlFlow::Continue
+    });
+
 
     // Radio
 
@@ -2131,11 +2290,95 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
 
     }
 
+    // Band Plan
+    let mut band_plan_names: Vec<String> = match BandPlan::builtin() {
+        Ok(plans) => plans.iter().map(|p| p.name.clone()).collect(),
+        Err(e) => {
+            eprintln!("Band plan: {}", e);
+            Vec::new()
+        }
+    };
+    band_plan_names.push(CUSTOM_BAND_PLAN.to_string());
+    let r = radio_mutex.radio.lock().unwrap();
+    let band_plan_name = r.band_plan_name.clone();
+    drop(r);
+    let band_plan_dropdown: DropDown = builder
+            .object("band_plan_dropdown")
+            .expect("Could not get object `band_plan_dropdown` from builder.");
+    let band_plan_label: Label = builder
+            .object("band_plan_label")
+            .expect("Could not get object `band_plan_label` from builder.");
+    let string_list_model = StringList::new(&[]);
+    for name in band_plan_names.iter() {
+        string_list_model.append(name);
+    }
+    band_plan_dropdown.set_model(Some(&string_list_model));
+    if let Some(i) = band_plan_names.iter().position(|n| *n == band_plan_name) {
+        band_plan_dropdown.set_selected(i as u32);
+    }
+    if band_plan_name == CUSTOM_BAND_PLAN {
+        band_plan_label.set_label(&BandPlan::custom_path().to_string_lossy());
+    }
+    let radio_mutex_clone = radio_mutex.clone();
+    let band_plan_label_clone = band_plan_label.clone();
+    band_plan_dropdown.connect_selected_notify(move |dropdown| {
+        if let Some(name) = band_plan_names.get(dropdown.selected() as usize) {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            match r.set_band_plan(name) {
+                Ok(_) => {
+                    band_plan_label_clone.set_label(if *name == CUSTOM_BAND_PLAN { "Custom plan loaded" } else { "" });
+                    band_plan_label_clone.set_tooltip_text(None);
+                }
+                Err(e) => {
+                    eprintln!("Band plan: {}", e);
+                    band_plan_label_clone.set_label(&format!("Plan not loaded, using {}", r.band_plan.name));
+                    band_plan_label_clone.set_tooltip_text(Some(&e));
+                }
+            }
+        }
+    });
+
+    let band_plan_save_button: Button = builder
+            .object("band_plan_save_button")
+            .expect("Could not get object `band_plan_save_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    band_plan_save_button.connect_clicked(move |_| {
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        let mut plan = r.band_plan.clone();
+        drop(r);
+        plan.name = CUSTOM_BAND_PLAN.to_string();
+        let path = BandPlan::custom_path();
+        match plan.save(&path) {
+            Ok(_) => band_plan_label.set_label(&path.to_string_lossy()),
+            Err(e) => {
+                eprintln!("Band plan save: {}", e);
+                band_plan_label.set_label("Save failed");
+            }
+        }
+    });
+
+    let r = radio_mutex.radio.lock().unwrap();
+        let tx_lockout = r.tx_lockout;
+    drop(r);
+
+    let tx_lockout_check_button: CheckButton = builder
+            .object("tx_lockout_check_button")
+            .expect("Could not get object `tx_lockout_check_button` from builder.");
+    tx_lockout_check_button.set_active(tx_lockout);
+    let radio_mutex_clone = radio_mutex.clone();
+    tx_lockout_check_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.tx_lockout = button.is_active();
+    });
+
     let r = radio_mutex.radio.lock().unwrap();
         let cw_keyer_mode = r.cw_keyer_mode;
         let cw_keyer_internal = r.cw_keyer_internal;
         let cw_keys_reversed = r.cw_keys_reversed;
         let cw_breakin = r.cw_breakin;
+        let cw_keyer_speed = r.cw_keyer_speed;
+        let cw_keyer_weight = r.cw_keyer_weight;
+        let cw_keyer_spacing = r.cw_keyer_spacing;
     drop(r);
 
     let keyer_mode_dropdown: DropDown = builder
@@ -2186,6 +2429,39 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
         r.updated = true;
     });
 
+    let cw_keyer_speed_adjustment: Adjustment = builder
+            .object("cw_keyer_speed_adjustment")
+            .expect("Could not get object `cw_keyer_speed_adjustment` from builder.");
+    cw_keyer_speed_adjustment.set_value(cw_keyer_speed.into());
+    let radio_mutex_clone = radio_mutex.clone();
+    cw_keyer_speed_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_keyer_speed = adjustment.value() as i32;
+        r.updated = true;
+    });
+
+    let cw_keyer_weight_adjustment: Adjustment = builder
+            .object("cw_keyer_weight_adjustment")
+            .expect("Could not get object `cw_keyer_weight_adjustment` from builder.");
+    cw_keyer_weight_adjustment.set_value(cw_keyer_weight.into());
+    let radio_mutex_clone = radio_mutex.clone();
+    cw_keyer_weight_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_keyer_weight = adjustment.value() as i32;
+        r.updated = true;
+    });
+
+    let cw_keyer_spacing_check_button: CheckButton = builder
+            .object("cw_keyer_spacing_check_button")
+            .expect("Could not get object `cw_keyer_spacing_check_button` from builder.");
+    cw_keyer_spacing_check_button.set_active(cw_keyer_spacing != 0);
+    let radio_mutex_clone = radio_mutex.clone();
+    cw_keyer_spacing_check_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_keyer_spacing = button.is_active() as i32;
+        r.updated = true;
+    });
+
     // Noise - NR
     let r = radio_mutex.radio.lock().unwrap();
         let taps = r.receiver[0].nr_taps;
@@ -2829,8 +3105,9 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr1_id.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr1_id_clone.text();
-        let rx = r.active_receiver;
-        r.receiver[rx].band_info[Bands::XVTR1.to_usize()].label = text.to_string();
+        for receiver in r.receiver.iter_mut() {
+            receiver.band_info[Bands::XVTR1.to_usize()].label = text.to_string();
+        }
         //let mut app_widgets = rc_app_widgets_clone.borrow_mut();
         //app_widgets.band_grid.update_band_label(Bands::XVTR1, &text.to_string());
     });
@@ -2844,10 +3121,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr1_low.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr1_low_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR1.to_usize()].low = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR1.to_usize()].low = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2864,10 +3142,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr1_high.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr1_high_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR1.to_usize()].high = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR1.to_usize()].high = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2884,10 +3163,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr1_lo.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr1_lo_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR1.to_usize()].lo = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR1.to_usize()].lo = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2904,10 +3184,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr1_lo_error.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr1_lo_error_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR1.to_usize()].lo_error = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR1.to_usize()].lo_error = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2934,8 +3215,9 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr2_id.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr2_id_clone.text();
-        let rx = r.active_receiver;
-        r.receiver[rx].band_info[Bands::XVTR2.to_usize()].label = text.to_string();
+        for receiver in r.receiver.iter_mut() {
+            receiver.band_info[Bands::XVTR2.to_usize()].label = text.to_string();
+        }
 //        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
 //        app_widgets.band_grid.update_band_label(Bands::XVTR2, &text.to_string());
     });
@@ -2949,10 +3231,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr2_low.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr2_low_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR2.to_usize()].low = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR2.to_usize()].low = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2969,10 +3252,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr2_high.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr2_high_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR2.to_usize()].high = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR2.to_usize()].high = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -2989,10 +3273,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr2_lo.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr2_lo_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR2.to_usize()].lo = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR2.to_usize()].lo = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3009,10 +3294,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr2_lo_error.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr2_lo_error_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR2.to_usize()].lo_error = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR2.to_usize()].lo_error = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3038,8 +3324,9 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr3_id.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr3_id_clone.text();
-        let rx = r.active_receiver;
-        r.receiver[rx].band_info[Bands::XVTR3.to_usize()].label = text.to_string();
+        for receiver in r.receiver.iter_mut() {
+            receiver.band_info[Bands::XVTR3.to_usize()].label = text.to_string();
+        }
 //        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
 //        app_widgets.band_grid.update_band_label(Bands::XVTR3, &text.to_string());
     });
@@ -3053,10 +3340,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr3_low.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr3_low_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR3.to_usize()].low = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR3.to_usize()].low = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3073,10 +3361,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr3_high.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr3_high_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR3.to_usize()].high = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR3.to_usize()].high = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3093,10 +3382,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr3_lo.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr3_lo_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR3.to_usize()].lo = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR3.to_usize()].lo = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3113,10 +3403,11 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     xvtr3_lo_error.connect_changed(move |_| {
         let mut r = radio_mutex_clone.radio.lock().unwrap();
         let text = xvtr3_lo_error_clone.text();
-        let rx = r.active_receiver;
         match text.parse::<i32>() {
             Ok(number) => {
-                r.receiver[rx].band_info[Bands::XVTR3.to_usize()].lo_error = number as f64;
+                for receiver in r.receiver.iter_mut() {
+                    receiver.band_info[Bands::XVTR3.to_usize()].lo_error = number as f64;
+                }
             },
             Err(e) => {
                 eprintln!("Failed to convert '{}' to i32. Error: {}", text, e);
@@ -3124,6 +3415,383 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
         }
     });
     
+    // transverter gain, drive limit, PTT output and receive only
+    for (band, name) in [(Bands::XVTR1, "xvtr1"), (Bands::XVTR2, "xvtr2"), (Bands::XVTR3, "xvtr3")] {
+        let b = band.to_usize();
+        let r = radio_mutex.radio.lock().unwrap();
+            let rx = r.active_receiver;
+            let rx_gain = r.receiver[rx].band_info[b].xvtr_rx_gain;
+            let drive_limit = r.receiver[rx].band_info[b].xvtr_drive_limit;
+            let ptt_pin = r.receiver[rx].band_info[b].xvtr_ptt_pin;
+            let rx_only = r.receiver[rx].band_info[b].xvtr_rx_only;
+        drop(r);
+
+        let rx_gain_adjustment: Adjustment = builder
+                .object(&format!("{}_rx_gain_adjustment", name))
+                .expect("Could not get object `rx_gain_adjustment` from builder.");
+        rx_gain_adjustment.set_value(rx_gain as f64);
+        let radio_mutex_clone = radio_mutex.clone();
+        rx_gain_adjustment.connect_value_changed(move |adjustment| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            for receiver in r.receiver.iter_mut() {
+                receiver.band_info[b].xvtr_rx_gain = adjustment.value() as f32;
+            }
+        });
+
+        let drive_limit_adjustment: Adjustment = builder
+                .object(&format!("{}_drive_limit_adjustment", name))
+                .expect("Could not get object `drive_limit_adjustment` from builder.");
+        drive_limit_adjustment.set_value(drive_limit as f64);
+        let radio_mutex_clone = radio_mutex.clone();
+        drive_limit_adjustment.connect_value_changed(move |adjustment| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            for receiver in r.receiver.iter_mut() {
+                receiver.band_info[b].xvtr_drive_limit = adjustment.value() as f32;
+            }
+        });
+
+        let ptt_pin_dropdown: DropDown = builder
+                .object(&format!("{}_ptt_pin_dropdown", name))
+                .expect("Could not get object `ptt_pin_dropdown` from builder.");
+        ptt_pin_dropdown.set_selected(ptt_pin as u32);
+        let radio_mutex_clone = radio_mutex.clone();
+        ptt_pin_dropdown.connect_selected_notify(move |dropdown| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            for receiver in r.receiver.iter_mut() {
+                receiver.band_info[b].xvtr_ptt_pin = dropdown.selected() as u8;
+            }
+        });
+
+        let rx_only_check_button: CheckButton = builder
+                .object(&format!("{}_rx_only", name))
+                .expect("Could not get object `rx_only` from builder.");
+        rx_only_check_button.set_active(rx_only);
+        let radio_mutex_clone = radio_mutex.clone();
+        rx_only_check_button.connect_toggled(move |button| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            for receiver in r.receiver.iter_mut() {
+                receiver.band_info[b].xvtr_rx_only = button.is_active();
+            }
+        });
+    }
+
+    // MIDI
+    let r = radio_mutex.radio.lock().unwrap();
+        let midi_device = r.midi_device.clone();
+    drop(r);
+    let midi_devices = MIDI::ports();
+    let midi_mappings_label: Label = builder
+            .object("midi_mappings_label")
+            .expect("Could not get object `midi_mappings_label` from builder.");
+    update_midi_mappings(&midi_mappings_label, radio_mutex);
+    let midi_profile_label: Label = builder
+            .object("midi_profile_label")
+            .expect("Could not get object `midi_profile_label` from builder.");
+    let midi_device_dropdown: DropDown = builder
+            .object("midi_device_dropdown")
+            .expect("Could not get object `midi_device_dropdown` from builder.");
+    let string_list_model = StringList::new(&[]);
+    midi_device_dropdown.set_model(Some(&string_list_model));
+    for i in 0..midi_devices.len() {
+        string_list_model.append(&midi_devices[i]);
+        if midi_devices[i] == midi_device {
+            midi_device_dropdown.set_selected(i as u32);
+        }
+    }
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_mappings_label_clone = midi_mappings_label.clone();
+    let midi_profile_label_clone = midi_profile_label.clone();
+    midi_device_dropdown.connect_selected_notify(move |dropdown| {
+        let i = dropdown.selected() as usize;
+        if i < midi_devices.len() {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.midi_device = midi_devices[i].clone();
+            // load the profile for this controller
+            if let Some(profile) = MidiProfile::find(&r.midi_device) {
+                r.midi_functions = profile.to_functions();
+                midi_profile_label_clone.set_label(&profile.name);
+            } else {
+                midi_profile_label_clone.set_label("");
+            }
+            drop(r);
+            update_midi_mappings(&midi_mappings_label_clone, &radio_mutex_clone);
+        }
+    });
+
+    let midi_profiles = MidiProfile::builtin();
+    let midi_profile_dropdown: DropDown = builder
+            .object("midi_profile_dropdown")
+            .expect("Could not get object `midi_profile_dropdown` from builder.");
+    let string_list_model = StringList::new(&[]);
+    for profile in midi_profiles.iter() {
+        string_list_model.append(&profile.name);
+    }
+    midi_profile_dropdown.set_model(Some(&string_list_model));
+
+    let midi_profile_load_button: Button = builder
+            .object("midi_profile_load_button")
+            .expect("Could not get object `midi_profile_load_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_mappings_label_clone = midi_mappings_label.clone();
+    let midi_profile_label_clone = midi_profile_label.clone();
+    midi_profile_load_button.connect_clicked(move |_| {
+        let i = midi_profile_dropdown.selected() as usize;
+        if i < midi_profiles.len() {
+            apply_midi_profile(&midi_profiles[i], &radio_mutex_clone);
+            midi_profile_label_clone.set_label(&midi_profiles[i].name);
+            update_midi_mappings(&midi_mappings_label_clone, &radio_mutex_clone);
+        }
+    });
+
+    let midi_import_button: Button = builder
+            .object("midi_import_button")
+            .expect("Could not get object `midi_import_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_mappings_label_clone = midi_mappings_label.clone();
+    let midi_profile_label_clone = midi_profile_label.clone();
+    let window_clone = window.clone();
+    midi_import_button.connect_clicked(move |_| {
+        let dialog = FileDialog::builder()
+            .title("Import MIDI Profile")
+            .modal(true)
+            .build();
+        let radio_mutex_clone = radio_mutex_clone.clone();
+        let midi_mappings_label_clone = midi_mappings_label_clone.clone();
+        let midi_profile_label_clone = midi_profile_label_clone.clone();
+        dialog.open(Some(&window_clone), gio::Cancellable::NONE, move |result| {
+            if let Ok(file) = result {
+                if let Some(path) = file.path() {
+                    match MidiProfile::load(&path) {
+                        Ok(profile) => {
+                            apply_midi_profile(&profile, &radio_mutex_clone);
+                            midi_profile_label_clone.set_label(&profile.name);
+                            update_midi_mappings(&midi_mappings_label_clone, &radio_mutex_clone);
+                        }
+                        Err(e) => {
+                            eprintln!("MIDI profile import: {}", e);
+                            midi_profile_label_clone.set_label("Import failed");
+                        }
+                    }
+                }
+            }
+        });
+    });
+
+    let midi_export_button: Button = builder
+            .object("midi_export_button")
+            .expect("Could not get object `midi_export_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    let window_clone = window.clone();
+    midi_export_button.connect_clicked(move |_| {
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        let profile = MidiProfile::from_functions(&r.midi_device, &r.midi_device, &r.midi_functions);
+        drop(r);
+        // .json exports JSON, anything else RON
+        let dialog = FileDialog::builder()
+            .title("Export MIDI Profile")
+            .modal(true)
+            .initial_name("midi_profile.ron")
+            .build();
+        dialog.save(Some(&window_clone), gio::Cancellable::NONE, move |result| {
+            if let Ok(file) = result {
+                if let Some(path) = file.path() {
+                    if let Err(e) = profile.save(&path) {
+                        eprintln!("MIDI profile export: {}", e);
+                    }
+                }
+            }
+        });
+    });
+
+    let midi_types = MidiType::all();
+    let midi_type_dropdown: DropDown = builder
+            .object("midi_type_dropdown")
+            .expect("Could not get object `midi_type_dropdown` from builder.");
+    let string_list_model = StringList::new(&[]);
+    for midi_type in midi_types.iter() {
+        string_list_model.append(midi_type.name());
+    }
+    midi_type_dropdown.set_model(Some(&string_list_model));
+
+    let midi_actions = MidiAction::all();
+    let midi_action_dropdown: DropDown = builder
+            .object("midi_action_dropdown")
+            .expect("Could not get object `midi_action_dropdown` from builder.");
+    let string_list_model = StringList::new(&[]);
+    for midi_action in midi_actions.iter() {
+        string_list_model.append(midi_action.name());
+    }
+    midi_action_dropdown.set_model(Some(&string_list_model));
+
+    let midi_learn_label: Label = builder
+            .object("midi_learn_label")
+            .expect("Could not get object `midi_learn_label` from builder.");
+    // control being edited
+    let midi_learned: Rc<Cell<Option<MidiLearned>>> = Rc::new(Cell::new(None));
+
+    let midi_learn_button: ToggleButton = builder
+            .object("midi_learn_button")
+            .expect("Could not get object `midi_learn_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    midi_learn_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.midi_learn = button.is_active();
+        r.midi_learned = None;
+    });
+
+    // wait for the MIDI thread to see a control while learning
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_learned_clone = midi_learned.clone();
+    let midi_learn_button_weak = midi_learn_button.downgrade();
+    let midi_types_clone = midi_types.clone();
+    let midi_actions_clone = midi_actions.clone();
+    let midi_type_dropdown_clone = midi_type_dropdown.clone();
+    let midi_action_dropdown_clone = midi_action_dropdown.clone();
+    glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
+        let Some(midi_learn_button) = midi_learn_button_weak.upgrade() else {
+            // dialog has gone
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.midi_learn = false;
+            return glib::ControlFlow::Break;
+        };
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        if let Some(learned) = r.midi_learned.take() {
+            let function = r.midi_functions[learned.index].clone();
+            drop(r);
+            midi_learned_clone.set(Some(learned));
+            midi_learn_label.set_label(&midi_index_name(learned.index));
+            let midi_type = if function.midi_type == MidiType::MidiUnknown { learned.midi_type } else { function.midi_type };
+            if let Some(i) = midi_types_clone.iter().position(|t| *t == midi_type) {
+                midi_type_dropdown_clone.set_selected(i as u32);
+            }
+            if let Some(i) = midi_actions_clone.iter().position(|a| *a == function.midi_action) {
+                midi_action_dropdown_clone.set_selected(i as u32);
+            }
+            midi_learn_button.set_active(false);
+        }
+        glib::ControlFlow::Continue
+    });
+
+    let midi_assign_button: Button = builder
+            .object("midi_assign_button")
+            .expect("Could not get object `midi_assign_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_learned_clone = midi_learned.clone();
+    let midi_mappings_label_clone = midi_mappings_label.clone();
+    midi_assign_button.connect_clicked(move |_| {
+        if let Some(learned) = midi_learned_clone.get() {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.midi_functions[learned.index] = MidiFunction {
+                midi_type: midi_types[midi_type_dropdown.selected() as usize],
+                midi_action: midi_actions[midi_action_dropdown.selected() as usize],
+                channel: learned.channel,
+            };
+            drop(r);
+            save_midi_profile(&radio_mutex_clone);
+            update_midi_mappings(&midi_mappings_label_clone, &radio_mutex_clone);
+        }
+    });
+
+    let midi_clear_button: Button = builder
+            .object("midi_clear_button")
+            .expect("Could not get object `midi_clear_button` from builder.");
+    let radio_mutex_clone = radio_mutex.clone();
+    let midi_learned_clone = midi_learned.clone();
+    midi_clear_button.connect_clicked(move |_| {
+        if let Some(learned) = midi_learned_clone.get() {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.midi_functions[learned.index] = MidiFunction::default();
+            drop(r);
+            save_midi_profile(&radio_mutex_clone);
+            update_midi_mappings(&midi_mappings_label, &radio_mutex_clone);
+        }
+    });
+
+    // Scanner
+    let r = radio_mutex.radio.lock().unwrap();
+    let scan_mode = r.scanner.mode;
+    let scan_stop = r.scanner.stop;
+    let scan_step = r.scanner.step;
+    let scan_dwell = r.scanner.dwell;
+    let scan_hang = r.scanner.hang;
+    let scan_threshold = r.scanner.threshold;
+    let scan_fm_level = r.scanner.fm_level;
+    drop(r);
+
+    let scan_mode_dropdown: DropDown = builder
+            .object("scan_mode_dropdown")
+            .expect("Could not get object `scan_mode_dropdown` from builder.");
+    scan_mode_dropdown.set_selected(scan_mode.to_usize() as u32);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_mode_dropdown.connect_selected_notify(move |dropdown| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        if let Some(mode) = ScanMode::from_usize(dropdown.selected() as usize) {
+            r.scanner.mode = mode;
+        }
+    });
+
+    let scan_stop_dropdown: DropDown = builder
+            .object("scan_stop_dropdown")
+            .expect("Could not get object `scan_stop_dropdown` from builder.");
+    scan_stop_dropdown.set_selected(scan_stop.to_usize() as u32);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_stop_dropdown.connect_selected_notify(move |dropdown| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        if let Some(stop) = ScanStop::from_usize(dropdown.selected() as usize) {
+            r.scanner.stop = stop;
+        }
+    });
+
+    let scan_step_adjustment: Adjustment = builder
+            .object("scan_step_adjustment")
+            .expect("Could not get object `scan_step_adjustment` from builder.");
+    scan_step_adjustment.set_value(scan_step);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_step_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.scanner.step = adjustment.value();
+    });
+
+    let scan_dwell_adjustment: Adjustment = builder
+            .object("scan_dwell_adjustment")
+            .expect("Could not get object `scan_dwell_adjustment` from builder.");
+    scan_dwell_adjustment.set_value(scan_dwell as f64);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_dwell_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.scanner.dwell = adjustment.value() as u64;
+    });
+
+    let scan_hang_adjustment: Adjustment = builder
+            .object("scan_hang_adjustment")
+            .expect("Could not get object `scan_hang_adjustment` from builder.");
+    scan_hang_adjustment.set_value(scan_hang as f64);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_hang_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.scanner.hang = adjustment.value() as u64;
+    });
+
+    let scan_threshold_adjustment: Adjustment = builder
+            .object("scan_threshold_adjustment")
+            .expect("Could not get object `scan_threshold_adjustment` from builder.");
+    scan_threshold_adjustment.set_value(scan_threshold);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_threshold_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.scanner.threshold = adjustment.value();
+    });
+
+    let scan_fm_level_adjustment: Adjustment = builder
+            .object("scan_fm_level_adjustment")
+            .expect("Could not get object `scan_fm_level_adjustment` from builder.");
+    scan_fm_level_adjustment.set_value(scan_fm_level);
+    let radio_mutex_clone = radio_mutex.clone();
+    scan_fm_level_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.scanner.fm_level = adjustment.value();
+    });
+
     // OK button
     let ok_button: Button = builder
             .object("ok_button")
@@ -3136,3 +3804,37 @@ pub fn create_configure_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_m
     window
 }
 
+fn update_midi_mappings(label: &Label, radio_mutex: &RadioMutex) {
+    let r = radio_mutex.radio.lock().unwrap();
+    let mut text = String::new();
+    for (i, function) in r.midi_functions.iter().enumerate() {
+        if function.midi_action != MidiAction::MidiNone {
+            text.push_str(&format!("{}\t{}\t{}\n", midi_index_name(i), function.midi_type.name(), function.midi_action.name()));
+        }
+    }
+    if text.is_empty() {
+        text.push_str("No controls mapped");
+    }
+    label.set_label(&text);
+}
+
+// loaded profiles become the user profile for the selected controller
+fn apply_midi_profile(profile: &MidiProfile, radio_mutex: &RadioMutex) {
+    let mut r = radio_mutex.radio.lock().unwrap();
+    r.midi_functions = profile.to_functions();
+    drop(r);
+    save_midi_profile(radio_mutex);
+}
+
+fn save_midi_profile(radio_mutex: &RadioMutex) {
+    let r = radio_mutex.radio.lock().unwrap();
+    if r.midi_device.is_empty() {
+        return;
+    }
+    let profile = MidiProfile::from_functions(&r.midi_device, &r.midi_device, &r.midi_functions);
+    let path = MidiProfile::profile_path(&r.midi_device);
+    drop(r);
+    if let Err(e) = profile.save(&path) {
+        eprintln!("MIDI profile save: {}", e);
+    }
+}
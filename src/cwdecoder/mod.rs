@@ -0,0 +1,352 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+const DEBUG_CW_DECODER: bool = false;
+
+// Goertzel block length, the detector bandwidth is about 1000/CW_BLOCK_MS Hz
+const CW_BLOCK_MS: f32 = 5.0;
+
+// decoded text kept for the display
+pub const CW_DECODER_HISTORY: usize = 4096;
+
+const CW_INITIAL_WPM: f32 = 20.0;
+const CW_MIN_DOT_MS: f32 = 20.0;  // 60 WPM
+const CW_MAX_DOT_MS: f32 = 240.0; // 5 WPM
+
+// tone must be this far above the noise floor to key down
+const CW_MIN_SNR: f32 = 4.0;
+
+// blocks for the noise floor to settle before keying
+const CW_SETTLE_BLOCKS: usize = 40;
+
+const MORSE: [(&str, char); 54] = [
+    (".-", 'A'), ("-...", 'B'), ("-.-.", 'C'), ("-..", 'D'), (".", 'E'), ("..-.", 'F'),
+    ("--.", 'G'), ("....", 'H'), ("..", 'I'), (".---", 'J'), ("-.-", 'K'), (".-..", 'L'),
+    ("--", 'M'), ("-.", 'N'), ("---", 'O'), (".--.", 'P'), ("--.-", 'Q'), (".-.", 'R'),
+    ("...", 'S'), ("-", 'T'), ("..-", 'U'), ("...-", 'V'), (".--", 'W'), ("-..-", 'X'),
+    ("-.--", 'Y'), ("--..", 'Z'),
+    ("-----", '0'), (".----", '1'), ("..---", '2'), ("...--", '3'), ("....-", '4'),
+    (".....", '5'), ("-....", '6'), ("--...", '7'), ("---..", '8'), ("----.", '9'),
+    (".-.-.-", '.'), ("--..--", ','), ("..--..", '?'), ("-..-.", '/'), ("-...-", '='),
+    (".-.-.", '+'), ("-....-", '-'), (".----.", '\''), ("-.-.--", '!'), ("-.--.", '('),
+    ("-.--.-", ')'), (".-...", '&'), ("---...", ':'), ("-.-.-.", ';'), ("..--.-", '_'),
+    (".-..-.", '"'), (".--.-.", '@'), ("...-..-", '$'),
+];
+
+pub fn morse_to_char(symbol: &str) -> Option<char> {
+    MORSE.iter().find(|(code, _)| *code == symbol).map(|(_, c)| *c)
+}
+
+pub fn char_to_morse(c: char) -> Option<&'static str> {
+    let c = c.to_ascii_uppercase();
+    MORSE.iter().find(|(_, ch)| *ch == c).map(|(code, _)| *code)
+}
+
+#[derive(Debug, Clone)]
+pub struct CwDecoder {
+    sample_rate: f32,
+    pitch: f32,
+    block_size: usize,
+    coefficient: f32,
+    q1: f32,
+    q2: f32,
+    n: usize,
+    signal: f32,        // tone peak level
+    noise: f32,         // noise floor
+    blocks: usize,
+    key_down: bool,
+    state_ms: f32,      // time in the current key state
+    mark_before: f32,
+    space_before: f32,
+    dot_ms: f32,        // adapted from the marks
+    char_gap: f32,      // in dots, adapted from the spaces, longer than 3 with Farnsworth timing
+    symbol: String,
+    space_sent: bool,
+    pub wpm: f32,
+    pub text: String,
+    pub count: usize,   // characters decoded since created
+}
+
+impl Default for CwDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CwDecoder {
+
+    pub fn new() -> Self {
+        let dot_ms = 1200.0 / CW_INITIAL_WPM;
+        CwDecoder {
+            sample_rate: 0.0,
+            pitch: 0.0,
+            block_size: 0,
+            coefficient: 0.0,
+            q1: 0.0,
+            q2: 0.0,
+            n: 0,
+            signal: 0.0,
+            noise: 0.0,
+            blocks: 0,
+            key_down: false,
+            state_ms: 0.0,
+            mark_before: 0.0,
+            space_before: 0.0,
+            dot_ms,
+            char_gap: 3.0,
+            symbol: String::new(),
+            space_sent: true,
+            wpm: CW_INITIAL_WPM,
+            text: String::new(),
+            count: 0,
+        }
+    }
+
+    // start again, keeps the decoded text
+    pub fn reset(&mut self) {
+        self.q1 = 0.0;
+        self.q2 = 0.0;
+        self.n = 0;
+        self.signal = 0.0;
+        self.noise = 0.0;
+        self.blocks = 0;
+        self.key_down = false;
+        self.state_ms = 0.0;
+        self.symbol.clear();
+        self.space_sent = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+    }
+
+    // text decoded since count was last read
+    pub fn text_since(&self, count: usize) -> &str {
+        let new = self.count.saturating_sub(count).min(self.text.len());
+        &self.text[self.text.len() - new..]
+    }
+
+    fn tune(&mut self, sample_rate: f32, pitch: f32) {
+        self.sample_rate = sample_rate;
+        self.pitch = pitch;
+        self.block_size = ((sample_rate * CW_BLOCK_MS) / 1000.0) as usize;
+        let k = (0.5 + (self.block_size as f32 * pitch / sample_rate)).floor();
+        self.coefficient = 2.0 * (2.0 * std::f32::consts::PI * k / self.block_size as f32).cos();
+        self.reset();
+    }
+
+    // demodulated audio with the CW tone at pitch Hz
+    pub fn process(&mut self, samples: &[f32], sample_rate: f32, pitch: f32) {
+        if sample_rate != self.sample_rate || pitch != self.pitch {
+            self.tune(sample_rate, pitch);
+        }
+        for sample in samples {
+            let q0 = self.coefficient * self.q1 - self.q2 + sample;
+            self.q2 = self.q1;
+            self.q1 = q0;
+            self.n += 1;
+            if self.n >= self.block_size {
+                let power = self.q1 * self.q1 + self.q2 * self.q2 - self.coefficient * self.q1 * self.q2;
+                let magnitude = power.max(0.0).sqrt() / self.block_size as f32;
+                self.q1 = 0.0;
+                self.q2 = 0.0;
+                self.n = 0;
+                self.block(magnitude);
+            }
+        }
+    }
+
+    fn block(&mut self, magnitude: f32) {
+        let block_ms = self.block_size as f32 * 1000.0 / self.sample_rate;
+
+        // fast attack slow decay for the tone, the noise floor is the average with the key up
+        if magnitude > self.signal {
+            self.signal = magnitude;
+        } else {
+            self.signal += (magnitude - self.signal) * 0.0025;
+        }
+        if !self.key_down || magnitude < self.noise {
+            self.noise += (magnitude - self.noise) * 0.05;
+        }
+
+        if self.blocks < CW_SETTLE_BLOCKS {
+            self.blocks += 1;
+            return;
+        }
+
+        let range = self.signal - self.noise;
+        let key = if self.key_down {
+            magnitude > self.noise + (range * 0.4)
+        } else {
+            magnitude > self.noise + (range * 0.6) && self.signal > self.noise * CW_MIN_SNR
+        };
+
+        if key == self.key_down {
+            self.state_ms += block_ms;
+            if !key {
+                self.gap();
+            }
+            return;
+        }
+
+        let glitch = (self.dot_ms * 0.3).max(CW_BLOCK_MS * 2.0);
+        if self.key_down {
+            if self.state_ms < glitch {
+                // too short for a mark, carry on with the space
+                self.state_ms += self.space_before;
+            } else {
+                self.mark(self.state_ms);
+                self.mark_before = self.state_ms;
+                self.state_ms = block_ms;
+            }
+        } else if self.state_ms < glitch && !self.symbol.is_empty() {
+            // a drop out in the middle of a mark
+            self.symbol.pop();
+            self.state_ms += self.mark_before;
+        } else {
+            self.space(self.state_ms);
+            self.space_before = self.state_ms;
+            self.state_ms = block_ms;
+        }
+        self.key_down = key;
+    }
+
+    fn mark(&mut self, ms: f32) {
+        if ms < self.dot_ms * 2.0 {
+            self.symbol.push('.');
+            // catch up quickly when the sender is much faster
+            let rate = if ms < self.dot_ms * 0.6 { 0.5 } else { 0.25 };
+            self.dot_ms += (ms - self.dot_ms) * rate;
+        } else {
+            self.symbol.push('-');
+            self.dot_ms += ((ms / 3.0) - self.dot_ms) * 0.25;
+        }
+        self.dot_ms = self.dot_ms.clamp(CW_MIN_DOT_MS, CW_MAX_DOT_MS);
+        self.wpm = 1200.0 / self.dot_ms;
+        if self.symbol.len() > 7 {
+            // not morse
+            self.symbol.clear();
+        }
+    }
+
+    // learn the character gap, limiting each step so the occasional word gap has little effect
+    fn space(&mut self, ms: f32) {
+        let dots = ms / self.dot_ms;
+        if dots > 2.0 {
+            self.char_gap += (dots.min(self.char_gap * 1.5) - self.char_gap) * 0.5;
+        }
+        self.char_gap = self.char_gap.max(3.0);
+    }
+
+    // end of character after 2 dots of space, end of word between the character and word gaps
+    fn gap(&mut self) {
+        if !self.symbol.is_empty() && self.state_ms > self.dot_ms * 2.0 {
+            if let Some(c) = morse_to_char(&self.symbol) {
+                self.push(c);
+                self.space_sent = false;
+            }
+            if DEBUG_CW_DECODER {
+                eprintln!("CwDecoder: {} wpm={:.0} char_gap={:.1} dots", self.symbol, self.wpm, self.char_gap);
+            }
+            self.symbol.clear();
+        }
+        if !self.space_sent && self.symbol.is_empty() && self.state_ms > self.dot_ms * self.char_gap * 5.0 / 3.0 {
+            self.push(' ');
+            self.space_sent = true;
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.count += 1;
+        if self.text.len() > CW_DECODER_HISTORY {
+            let excess = self.text.len() - CW_DECODER_HISTORY;
+            self.text.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const PITCH: f32 = 600.0;
+
+    // audio for text keyed at wpm, with silence before for the noise floor and after to end the word
+    fn keyed(text: &str, wpm: f32) -> Vec<f32> {
+        let dot = (SAMPLE_RATE * 1.2 / wpm) as usize;
+        let mut key: Vec<bool> = vec![false; SAMPLE_RATE as usize / 2];
+        for (i, word) in text.split(' ').enumerate() {
+            if i > 0 {
+                key.extend(std::iter::repeat_n(false, dot * 6));
+            }
+            for (j, c) in word.chars().enumerate() {
+                if j > 0 {
+                    key.extend(std::iter::repeat_n(false, dot * 2));
+                }
+                for element in char_to_morse(c).unwrap().chars() {
+                    let length = if element == '.' { dot } else { dot * 3 };
+                    key.extend(std::iter::repeat_n(true, length));
+                    key.extend(std::iter::repeat_n(false, dot));
+                }
+            }
+        }
+        key.extend(std::iter::repeat_n(false, dot * 20));
+        key.iter()
+            .enumerate()
+            .map(|(n, k)| if *k { (2.0 * std::f32::consts::PI * PITCH * n as f32 / SAMPLE_RATE).sin() * 0.5 } else { 0.0 })
+            .collect()
+    }
+
+    #[test]
+    fn morse_table_round_trip() {
+        for (code, c) in MORSE.iter() {
+            assert_eq!(char_to_morse(*c), Some(*code));
+            assert_eq!(morse_to_char(code), Some(*c));
+        }
+        assert_eq!(char_to_morse('a'), Some(".-"));
+        assert_eq!(morse_to_char("........"), None);
+    }
+
+    #[test]
+    fn decodes_keyed_text() {
+        let mut decoder = CwDecoder::new();
+        decoder.process(&keyed("PARIS CQ", 20.0), SAMPLE_RATE, PITCH);
+        assert_eq!(decoder.text.trim(), "PARIS CQ");
+        assert!((decoder.wpm - 20.0).abs() < 2.0, "wpm {}", decoder.wpm);
+    }
+
+    #[test]
+    fn adapts_to_a_faster_sender() {
+        let mut decoder = CwDecoder::new();
+        decoder.process(&keyed("TEST TEST TEST", 30.0), SAMPLE_RATE, PITCH);
+        assert!(decoder.text.trim().ends_with("TEST"), "text {:?}", decoder.text);
+        assert!((decoder.wpm - 30.0).abs() < 3.0, "wpm {}", decoder.wpm);
+    }
+
+    #[test]
+    fn text_since_returns_new_characters() {
+        let mut decoder = CwDecoder::new();
+        decoder.process(&keyed("EE", 20.0), SAMPLE_RATE, PITCH);
+        let count = decoder.count;
+        decoder.process(&keyed("TT", 20.0), SAMPLE_RATE, PITCH);
+        assert_eq!(decoder.text_since(count).trim(), "TT");
+        decoder.clear();
+        assert_eq!(decoder.text_since(count), "");
+    }
+}
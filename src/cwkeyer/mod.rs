@@ -0,0 +1,469 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::f32::consts::PI;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio::Audio;
+use crate::modes::Modes;
+use crate::radio::{Keyer, RadioMutex};
+
+const DEBUG_CW_KEYER: bool = false;
+
+// resolution of the key timing, shared with the CW sender
+pub const CW_TICK_MS: u64 = 1;
+
+// how often an idle keyer reads the radio settings
+const CW_KEYER_IDLE_MS: u64 = 100;
+
+// keyer weight range, 50 gives equal mark and space
+pub const CW_MIN_WEIGHT: i32 = 33;
+pub const CW_MAX_WEIGHT: i32 = 66;
+
+// set by wake, an idle keyer thread waits on it
+static KEYER_WAKE: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+const SIDETONE_RATE: f32 = 48000.0;
+// rise and fall of the sidetone to avoid clicks
+const SIDETONE_RAMP_MS: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Element {
+    Dot,
+    Dash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+enum KeyerState {
+    #[default]
+    Idle,
+    Mark(Element),
+    Gap(Element),
+    CharGap,
+}
+
+// length of a dot in ms at the given speed
+pub fn dot_ms(wpm: i32) -> f32 {
+    1200.0 / wpm.max(1) as f32
+}
+
+// ms added to each mark and taken from the following space for the weight
+pub fn weight_extra(dot_ms: f32, weight: i32) -> f32 {
+    dot_ms * (weight.clamp(CW_MIN_WEIGHT, CW_MAX_WEIGHT) - 50) as f32 / 50.0
+}
+
+// called when a paddle changes or text is queued so the keyer reacts within a tick
+pub fn wake() {
+    let (woken, condvar) = &KEYER_WAKE;
+    *woken.lock().unwrap() = true;
+    condvar.notify_one();
+}
+
+// wait up to timeout for wake, true if it was called
+fn wait_for_wake(timeout: Duration) -> bool {
+    let (woken, condvar) = &KEYER_WAKE;
+    let guard = woken.lock().unwrap();
+    let (mut guard, _) = condvar.wait_timeout_while(guard, timeout, |woken| !*woken).unwrap();
+    std::mem::take(&mut *guard)
+}
+
+// keyer settings copied from the radio for each tick
+pub struct CwKeyerSettings {
+    pub mode: Keyer,
+    pub speed: i32,
+    pub weight: i32,
+    pub spacing: bool,
+    pub breakin: bool,
+    pub mox: bool,
+    pub ptt_delay: i32,
+    pub hang_time: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CwKeyer {
+    state: KeyerState,
+    deadline: Option<Instant>,
+    hang: Option<Instant>,
+    dot_memory: bool,
+    dash_memory: bool,
+    squeezed: bool, // both paddles held during the element
+    pub ptt: bool, // raised by the keyer when break-in is on
+    pub key_down: bool,
+}
+
+impl CwKeyer {
+
+    pub fn new() -> Self {
+        CwKeyer::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ptt || self.key_down || self.state != KeyerState::Idle
+    }
+
+    pub fn abort(&mut self) {
+        *self = CwKeyer::default();
+    }
+
+    fn start(&mut self, element: Element, base: Instant, dot: f32, extra: f32) {
+        let mark = if element == Element::Dot { dot } else { 3.0 * dot };
+        self.key_down = true;
+        self.state = KeyerState::Mark(element);
+        self.deadline = Some(base + Duration::from_secs_f32((mark + extra) / 1000.0));
+if DEBUG_CW_KEYER {eprintln!("CwKeyer: {:?}", element);}
+    }
+
+    // step the keyer with the paddle state, returns (ptt changed, key changed)
+    pub fn tick(&mut self, now: Instant, dot: bool, dash: bool, settings: &CwKeyerSettings) -> (bool, bool) {
+        let ptt = self.ptt;
+        let key_down = self.key_down;
+        let pressed = dot || dash;
+
+        if !settings.breakin && !settings.mox {
+            // without break-in the paddles only key while MOX is on
+            self.abort();
+            return (self.ptt != ptt, self.key_down != key_down);
+        }
+
+        if pressed {
+            self.hang = None;
+            if settings.breakin && !self.ptt {
+                self.ptt = true;
+                self.deadline = Some(now + Duration::from_millis(settings.ptt_delay.max(0) as u64));
+                return (true, false);
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if now < deadline {
+                self.latch(dot, dash, settings.mode);
+                return (false, false);
+            }
+        }
+
+        let dot_ms = dot_ms(settings.speed);
+        let extra = weight_extra(dot_ms, settings.weight);
+        let base = self.deadline.unwrap_or(now);
+
+        if settings.mode == Keyer::Straight {
+            self.key_down = pressed;
+            self.state = KeyerState::Idle;
+            self.deadline = None;
+        } else {
+            match self.state {
+                KeyerState::Idle => {
+                    self.deadline = None;
+                    if dot {
+                        self.start(Element::Dot, now, dot_ms, extra);
+                    } else if dash {
+                        self.start(Element::Dash, now, dot_ms, extra);
+                    }
+                }
+                KeyerState::Mark(element) => {
+                    self.key_down = false;
+                    self.state = KeyerState::Gap(element);
+                    self.deadline = Some(base + Duration::from_secs_f32((dot_ms - extra) / 1000.0));
+                }
+                KeyerState::Gap(element) => {
+                    if settings.mode == Keyer::ModeA && self.squeezed && !pressed {
+                        // mode A stops as soon as a squeeze is released
+                        self.dot_memory = false;
+                        self.dash_memory = false;
+                    }
+                    let next = match element {
+                        Element::Dot if dash || self.dash_memory => Some(Element::Dash),
+                        Element::Dot if dot => Some(Element::Dot),
+                        Element::Dash if dot || self.dot_memory => Some(Element::Dot),
+                        Element::Dash if dash => Some(Element::Dash),
+                        _ => None,
+                    };
+                    self.dot_memory = false;
+                    self.dash_memory = false;
+                    self.squeezed = false;
+                    match next {
+                        Some(next) => self.start(next, base, dot_ms, extra),
+                        None if settings.spacing => {
+                            // strict character space of 3 dots
+                            self.state = KeyerState::CharGap;
+                            self.deadline = Some(base + Duration::from_secs_f32(2.0 * dot_ms / 1000.0));
+                        }
+                        None => {
+                            self.state = KeyerState::Idle;
+                            self.deadline = None;
+                        }
+                    }
+                }
+                KeyerState::CharGap => {
+                    self.state = KeyerState::Idle;
+                    self.deadline = None;
+                }
+            }
+        }
+
+        if self.ptt && !pressed && !self.key_down && self.state == KeyerState::Idle {
+            match self.hang {
+                None => self.hang = Some(now + Duration::from_millis(settings.hang_time.max(0) as u64)),
+                Some(hang) if now >= hang => {
+                    self.hang = None;
+                    self.ptt = false;
+                }
+                _ => {}
+            }
+        }
+
+        (self.ptt != ptt, self.key_down != key_down)
+    }
+
+    // remember the other paddle pressed during an element
+    fn latch(&mut self, dot: bool, dash: bool, mode: Keyer) {
+        if mode == Keyer::Straight {
+            return;
+        }
+        if dot && dash {
+            self.squeezed = true;
+        }
+        match self.state {
+            KeyerState::Mark(Element::Dot) | KeyerState::Gap(Element::Dot) if dash => self.dash_memory = true,
+            KeyerState::Mark(Element::Dash) | KeyerState::Gap(Element::Dash) if dot => self.dot_memory = true,
+            _ => {}
+        }
+    }
+}
+
+// runs the software keyer and writes the sidetone to the local audio output,
+// the radio is only locked every tick while keying and the output is only
+// open while keying and for the hang time after it
+pub fn run(radio_mutex: &RadioMutex) {
+    let mut sidetone = Audio::new();
+    let mut sidetone_open = false;
+    let mut sidetone_failed = false;
+    let mut sidetone_start = Instant::now();
+    let mut frames_written: u64 = 0;
+    let mut phase: f32 = 0.0;
+    let mut level: f32 = 0.0;
+    let step = 1.0 / (SIDETONE_RATE * SIDETONE_RAMP_MS / 1000.0);
+    let idle = Duration::from_millis(CW_KEYER_IDLE_MS);
+    let ramp = Duration::from_secs_f32(SIDETONE_RAMP_MS / 1000.0);
+    let mut keying = false;
+    let mut last_keying: Option<Instant> = None;
+    let mut hang = Duration::ZERO;
+    let mut last_read: Option<Instant> = None;
+    let mut wanted = false;
+    let mut key_down = false;
+    let mut frequency: f32 = 0.0;
+    let mut volume: f32 = 0.0;
+    let mut device = String::new();
+
+    loop {
+        let woken = if keying || sidetone_open {
+            thread::sleep(Duration::from_millis(CW_TICK_MS));
+            wait_for_wake(Duration::ZERO)
+        } else {
+            wait_for_wake(idle)
+        };
+        let now = Instant::now();
+        let stale = last_read.is_none_or(|t| now.duration_since(t) >= idle);
+        if keying || woken || stale {
+            last_read = Some(now);
+            let mut r = radio_mutex.radio.lock().unwrap();
+            let cw = r.transmitter.mode == Modes::CWL.to_usize() || r.transmitter.mode == Modes::CWU.to_usize();
+            let software = cw && !r.cw_keyer_internal;
+            if software {
+                r.cw_keyer_tick(now);
+            } else if r.cw_keyer.is_active() {
+                r.cw_keyer_abort();
+            }
+            let pressed = r.dot || r.dash || r.midi_dot || r.midi_dash;
+            // the sender's key down also sounds the sidetone
+            keying = software && (pressed || r.cw_keyer.is_active() || r.cw_sender.is_active());
+            wanted = software && r.receiver[0].local_output && r.cw_keyer_sidetone_volume > 0;
+            key_down = r.cw_key_down();
+            frequency = r.cw_keyer_sidetone_frequency as f32;
+            volume = r.cw_keyer_sidetone_volume as f32 / 127.0;
+            device = r.receiver[0].output_device.clone();
+            hang = Duration::from_millis(r.cw_keyer_hang_time.max(0) as u64);
+            drop(r);
+        }
+        if keying {
+            last_keying = Some(now);
+        }
+        // let the last element fall before closing
+        let sounding = last_keying.is_some_and(|t| now.duration_since(t) <= hang + ramp);
+
+        if !wanted || !sounding {
+            if sidetone_open {
+                let _ = sidetone.close_output();
+                sidetone_open = false;
+            }
+            sidetone_failed = false;
+            continue;
+        }
+        if !sidetone_open && !sidetone_failed {
+            match sidetone.open_output(&device) {
+                Ok(()) => {
+                    sidetone_open = true;
+                    sidetone_start = now;
+                    frames_written = 0;
+                }
+                Err(e) => {
+                    eprintln!("CW sidetone: cannot open {}: {}", device, e);
+                    sidetone_failed = true;
+                }
+            }
+        }
+        if !sidetone_open {
+            continue;
+        }
+
+        // keep pace with the audio clock, stereo frames
+        let due = (now.duration_since(sidetone_start).as_secs_f64() * SIDETONE_RATE as f64) as u64;
+        let frames = due.saturating_sub(frames_written) as usize;
+        frames_written = due;
+        let mut buffer = vec![0.0f32; frames * 2];
+        for i in 0..frames {
+            if key_down {
+                level = (level + step).min(1.0);
+            } else {
+                level = (level - step).max(0.0);
+            }
+            let sample = if level > 0.0 { phase.sin() * level * volume } else { 0.0 };
+            phase += 2.0 * PI * frequency / SIDETONE_RATE;
+            if phase > 2.0 * PI {
+                phase -= 2.0 * PI;
+            }
+            buffer[i * 2] = sample;
+            buffer[i * 2 + 1] = sample;
+        }
+        let _ = sidetone.write_output(&buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 20 wpm, 60 ms dots
+    const SPEED: i32 = 20;
+
+    fn settings(mode: Keyer) -> CwKeyerSettings {
+        CwKeyerSettings {
+            mode,
+            speed: SPEED,
+            weight: 50,
+            spacing: false,
+            breakin: false,
+            mox: true,
+            ptt_delay: 0,
+            hang_time: 0,
+        }
+    }
+
+    // tick the keyer every ms with paddles(ms) giving (dot, dash), returns the (start, length) of each mark
+    fn key(settings: &CwKeyerSettings, ms: u64, paddles: impl Fn(u64) -> (bool, bool)) -> Vec<(u64, u64)> {
+        let mut keyer = CwKeyer::new();
+        let start = Instant::now();
+        let mut marks = Vec::new();
+        let mut down: Option<u64> = None;
+        for t in 0..ms {
+            let (dot, dash) = paddles(t);
+            keyer.tick(start + Duration::from_millis(t), dot, dash, settings);
+            match (keyer.key_down, down) {
+                (true, None) => down = Some(t),
+                (false, Some(d)) => {
+                    marks.push((d, t - d));
+                    down = None;
+                }
+                _ => {}
+            }
+        }
+        assert!(down.is_none(), "key still down after {} ms", ms);
+        assert!(!keyer.is_active());
+        marks
+    }
+
+    // element timing is kept to within a tick
+    fn assert_marks(marks: &[(u64, u64)], expected: &[(u64, u64)]) {
+        assert_eq!(marks.len(), expected.len(), "{:?}", marks);
+        for (mark, expected) in marks.iter().zip(expected.iter()) {
+            assert!(mark.0.abs_diff(expected.0) <= 1 && mark.1.abs_diff(expected.1) <= 1, "{:?} != {:?}", marks, expected);
+        }
+    }
+
+    #[test]
+    fn held_paddles_send_dots_and_dashes() {
+        let marks = key(&settings(Keyer::ModeB), 500, |t| (t < 300, false));
+        assert_marks(&marks, &[(0, 60), (120, 60), (240, 60)]);
+        let marks = key(&settings(Keyer::ModeB), 500, |t| (false, t < 200));
+        assert_marks(&marks, &[(0, 180)]);
+    }
+
+    #[test]
+    fn dot_and_dash_memory() {
+        // a dot tapped during a dash follows it
+        let marks = key(&settings(Keyer::ModeB), 500, |t| ((100..110).contains(&t), t < 50));
+        assert_marks(&marks, &[(0, 180), (240, 60)]);
+        // a dash tapped during the space after a dot follows it
+        let marks = key(&settings(Keyer::ModeA), 500, |t| (t < 10, (80..90).contains(&t)));
+        assert_marks(&marks, &[(0, 60), (120, 180)]);
+    }
+
+    #[test]
+    fn squeeze_release_mode_a_and_b() {
+        // squeeze through the dot and into the dash then let go of both paddles
+        let squeeze = |t: u64| (t < 130, t < 130);
+        let marks = key(&settings(Keyer::ModeA), 600, squeeze);
+        assert_marks(&marks, &[(0, 60), (120, 180)]);
+        // mode B completes the dot that was due after the dash
+        let marks = key(&settings(Keyer::ModeB), 600, squeeze);
+        assert_marks(&marks, &[(0, 60), (120, 180), (360, 60)]);
+    }
+
+    #[test]
+    fn weight_lengthens_marks_not_the_element_period() {
+        let mut heavy = settings(Keyer::ModeB);
+        heavy.weight = 66;
+        let marks = key(&heavy, 400, |t| (t < 150, false));
+        assert_marks(&marks, &[(0, 79), (120, 79)]);
+        // outside the range the weight is clamped
+        heavy.weight = 90;
+        let marks = key(&heavy, 400, |t| (t < 150, false));
+        assert_marks(&marks, &[(0, 79), (120, 79)]);
+        let mut light = settings(Keyer::ModeB);
+        light.weight = 33;
+        let marks = key(&light, 400, |t| (t < 150, false));
+        assert_marks(&marks, &[(0, 40), (120, 40)]);
+    }
+
+    #[test]
+    fn spacing_enforces_the_character_space() {
+        let paddles = |t: u64| (t < 10 || (130..140).contains(&t), false);
+        let marks = key(&settings(Keyer::ModeB), 500, paddles);
+        assert_marks(&marks, &[(0, 60), (130, 60)]);
+        let mut spaced = settings(Keyer::ModeB);
+        spaced.spacing = true;
+        let marks = key(&spaced, 500, |t| (t < 10 || (130..250).contains(&t), false));
+        assert_marks(&marks, &[(0, 60), (240, 60)]);
+    }
+
+    #[test]
+    fn straight_key_follows_the_paddle() {
+        let marks = key(&settings(Keyer::Straight), 300, |t| ((10..25).contains(&t) || (100..250).contains(&t), false));
+        assert_eq!(marks, vec![(10, 15), (100, 150)]);
+    }
+}
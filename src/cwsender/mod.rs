@@ -0,0 +1,438 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use gtk::prelude::*;
+use gtk::{Builder, Button, Entry, Grid, Label, SpinButton, Window};
+use glib::ControlFlow::{Break, Continue};
+use glib::timeout_add_local;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cwdecoder::char_to_morse;
+use crate::cwkeyer::{self, dot_ms, weight_extra, CW_TICK_MS};
+use crate::radio::RadioMutex;
+use crate::widgets::AppWidgets;
+
+const DEBUG_CW_SENDER: bool = false;
+
+// characters waiting to be sent before CAT KY reports the buffer full
+const CW_SENDER_QUEUE_MAX: usize = 256;
+
+pub const CW_MACROS: usize = 8;
+
+// set when text is queued, the sender thread waits on it while idle
+static SENDER_WAKE: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+#[derive(Clone, Copy, Debug)]
+struct CwElement {
+    key_down: bool,
+    ms: f32,
+    chars: usize, // pending characters completed by this element
+    space: bool,  // already includes a word space
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CwSender {
+    elements: VecDeque<CwElement>,
+    deadline: Option<Instant>,
+    hang: bool,
+    pub pending: String, // text queued but not yet sent
+    pub ptt: bool,       // raised by the sender when break-in is on
+    pub key_down: bool,
+}
+
+impl CwSender {
+
+    pub fn new() -> Self {
+        CwSender::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ptt || self.key_down || !self.elements.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= CW_SENDER_QUEUE_MAX
+    }
+
+    pub fn abort(&mut self) {
+        self.elements.clear();
+        self.pending.clear();
+        self.deadline = None;
+        self.hang = false;
+        self.ptt = false;
+        self.key_down = false;
+    }
+
+    // convert text to key down/up elements at the given speed and weight
+    pub fn queue(&mut self, text: &str, wpm: i32, weight: i32) {
+        let dot = dot_ms(wpm);
+        let extra = weight_extra(dot, weight);
+        for c in text.chars() {
+            let c = c.to_ascii_uppercase();
+            if c == ' ' {
+                // a character already ends with 3 dots of space, a word needs 7
+                match self.elements.back_mut() {
+                    Some(last) if !last.key_down && !last.space => {
+                        last.ms += 4.0 * dot;
+                        last.chars += 1;
+                        last.space = true;
+                    }
+                    _ => {
+                        self.elements.push_back(CwElement { key_down: false, ms: 7.0 * dot, chars: 1, space: true });
+                    }
+                }
+                self.pending.push(c);
+                continue;
+            }
+            let Some(code) = char_to_morse(c) else {
+                if DEBUG_CW_SENDER {eprintln!("CwSender::queue: no morse for {:?}", c);}
+                continue;
+            };
+            let symbols = code.len();
+            for (i, symbol) in code.chars().enumerate() {
+                let mark = if symbol == '-' { 3.0 * dot } else { dot };
+                self.elements.push_back(CwElement { key_down: true, ms: mark + extra, chars: 0, space: false });
+                if i + 1 < symbols {
+                    self.elements.push_back(CwElement { key_down: false, ms: dot - extra, chars: 0, space: false });
+                } else {
+                    self.elements.push_back(CwElement { key_down: false, ms: 3.0 * dot - extra, chars: 1, space: false });
+                }
+            }
+            self.pending.push(c);
+        }
+        if !self.elements.is_empty() {
+            wake();
+        }
+    }
+
+    // step the key, returns (ptt changed, key changed)
+    pub fn tick(&mut self, now: Instant, mox: bool, breakin: bool, ptt_delay: i32, hang_time: i32) -> (bool, bool) {
+        let ptt = self.ptt;
+        let key_down = self.key_down;
+        if let Some(deadline) = self.deadline {
+            if now < deadline {
+                return (false, false);
+            }
+        }
+        if let Some(element) = self.elements.pop_front() {
+            if !breakin && !mox {
+                // without break-in the text waits for MOX
+                self.elements.push_front(element);
+                self.deadline = None;
+                self.key_down = false;
+            } else if breakin && !self.ptt {
+                self.elements.push_front(element);
+                self.ptt = true;
+                self.deadline = Some(now + Duration::from_millis(ptt_delay.max(0) as u64));
+            } else {
+                let start = self.deadline.unwrap_or(now);
+                self.hang = false;
+                self.key_down = element.key_down;
+                self.deadline = Some(start + Duration::from_secs_f32(element.ms / 1000.0));
+                let chars = element.chars.min(self.pending.len());
+                self.pending.drain(..chars);
+            }
+        } else {
+            self.key_down = false;
+            if self.ptt && !self.hang {
+                self.hang = true;
+                self.deadline = Some(now + Duration::from_millis(hang_time.max(0) as u64));
+            } else {
+                self.hang = false;
+                self.ptt = false;
+                self.deadline = None;
+            }
+        }
+        (self.ptt != ptt, self.key_down != key_down)
+    }
+}
+
+// replace {MYCALL}, {CALL}, {RST} and {NR} in a macro
+pub fn expand_macro(text: &str, my_call: &str, call: &str, rst: &str, serial: u32) -> String {
+    text.replace("{MYCALL}", my_call)
+        .replace("{CALL}", call)
+        .replace("{RST}", rst)
+        .replace("{NR}", &format!("{:03}", serial))
+}
+
+// start the sender thread and the keyer thread for the sidetone
+fn wake() {
+    let (woken, condvar) = &SENDER_WAKE;
+    *woken.lock().unwrap() = true;
+    condvar.notify_one();
+    cwkeyer::wake();
+}
+
+fn wait_for_text() {
+    let (woken, condvar) = &SENDER_WAKE;
+    let guard = woken.lock().unwrap();
+    let mut guard = condvar.wait_while(guard, |woken| !*woken).unwrap();
+    *guard = false;
+}
+
+// times the queued key events, runs for the life of the radio and sleeps while there is no text
+pub fn run(radio_mutex: &RadioMutex) {
+    loop {
+        wait_for_text();
+        loop {
+            thread::sleep(Duration::from_millis(CW_TICK_MS));
+            let mut r = radio_mutex.radio.lock().unwrap();
+            if !r.cw_sender.is_active() {
+                break;
+            }
+            r.cw_sender_tick();
+        }
+    }
+}
+
+pub fn create_cw_sender_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex) -> Window {
+
+    let ui_xml = include_str!("../ui/cwsender.xml");
+    let builder = Builder::from_string(ui_xml);
+
+    let window: Window = builder
+            .object("cw_sender_window")
+            .expect("Could not get object `cw_sender_window` from builder.");
+
+    let app_widgets = rc_app_widgets.borrow();
+    window.set_transient_for(Some(&app_widgets.main_window)); // keeps it on top
+    drop(app_widgets);
+
+    let my_call_entry: Entry = builder
+            .object("cw_my_call_entry")
+            .expect("Could not get object `cw_my_call_entry` from builder.");
+    let call_entry: Entry = builder
+            .object("cw_call_entry")
+            .expect("Could not get object `cw_call_entry` from builder.");
+    let rst_entry: Entry = builder
+            .object("cw_rst_entry")
+            .expect("Could not get object `cw_rst_entry` from builder.");
+    let serial_spinbutton: SpinButton = builder
+            .object("cw_serial_spinbutton")
+            .expect("Could not get object `cw_serial_spinbutton` from builder.");
+    let macros_grid: Grid = builder
+            .object("cw_macros_grid")
+            .expect("Could not get object `cw_macros_grid` from builder.");
+    let text_entry: Entry = builder
+            .object("cw_text_entry")
+            .expect("Could not get object `cw_text_entry` from builder.");
+    let stop_button: Button = builder
+            .object("cw_stop_button")
+            .expect("Could not get object `cw_stop_button` from builder.");
+    let pending_label: Label = builder
+            .object("cw_pending_label")
+            .expect("Could not get object `cw_pending_label` from builder.");
+
+    let r = radio_mutex.radio.lock().unwrap();
+        my_call_entry.set_text(&r.cw_my_call);
+        call_entry.set_text(&r.cw_call);
+        rst_entry.set_text(&r.cw_rst);
+        serial_spinbutton.set_value(r.cw_serial as f64);
+        let macros = r.cw_macros.clone();
+    drop(r);
+
+    let radio_mutex_clone = radio_mutex.clone();
+    my_call_entry.connect_changed(move |entry| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_my_call = entry.text().to_uppercase();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    call_entry.connect_changed(move |entry| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_call = entry.text().to_uppercase();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    rst_entry.connect_changed(move |entry| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_rst = entry.text().to_string();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    serial_spinbutton.connect_value_changed(move |spinbutton| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_serial = spinbutton.value() as u32;
+    });
+
+    for (i, text) in macros.iter().enumerate() {
+        let button = Button::with_label(&format!("M{}", i + 1));
+        let entry = Entry::new();
+        entry.set_text(text);
+        entry.set_hexpand(true);
+        macros_grid.attach(&button, 0, i as i32, 1, 1);
+        macros_grid.attach(&entry, 1, i as i32, 1, 1);
+
+        let radio_mutex_clone = radio_mutex.clone();
+        entry.connect_changed(move |entry| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.cw_macros[i] = entry.text().to_string();
+        });
+
+        let radio_mutex_clone = radio_mutex.clone();
+        let serial_spinbutton_clone = serial_spinbutton.clone();
+        button.connect_clicked(move |_| {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.cw_send_macro(i);
+            let serial = r.cw_serial;
+            drop(r);
+            serial_spinbutton_clone.set_value(serial as f64);
+        });
+    }
+
+    let radio_mutex_clone = radio_mutex.clone();
+    text_entry.connect_activate(move |entry| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        if r.cw_send_text(&format!("{} ", entry.text())) {
+            entry.set_text("");
+        }
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    stop_button.connect_clicked(move |_| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.cw_abort();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let window_clone = window.clone();
+    timeout_add_local(Duration::from_millis(100), move || {
+        if !window_clone.is_visible() {
+            return Break;
+        }
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        pending_label.set_label(&r.cw_sender.pending);
+        Continue
+    });
+
+    window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(sender: &CwSender) -> Vec<(bool, f32)> {
+        sender.elements.iter().map(|e| (e.key_down, e.ms)).collect()
+    }
+
+    #[test]
+    fn queue_times_the_elements() {
+        let mut sender = CwSender::new();
+        sender.queue("a", 20, 50);
+        assert_eq!(timing(&sender), vec![(true, 60.0), (false, 60.0), (true, 180.0), (false, 180.0)]);
+        assert_eq!(sender.pending, "A");
+        assert!(sender.is_active());
+    }
+
+    #[test]
+    fn queue_applies_the_weight() {
+        let mut sender = CwSender::new();
+        sender.queue("E", 20, 60);
+        assert_eq!(timing(&sender), vec![(true, 72.0), (false, 168.0)]);
+        // limited to the keyer range
+        let mut sender = CwSender::new();
+        sender.queue("E", 20, 100);
+        assert_eq!(timing(&sender), vec![(true, 79.2), (false, 160.8)]);
+    }
+
+    #[test]
+    fn queue_word_spaces() {
+        let mut sender = CwSender::new();
+        sender.queue("E E", 20, 50);
+        assert_eq!(timing(&sender), vec![(true, 60.0), (false, 420.0), (true, 60.0), (false, 180.0)]);
+        assert_eq!(sender.elements[1].chars, 2);
+        assert_eq!(sender.pending, "E E");
+
+        // a second space or a leading space is a full word space
+        let mut sender = CwSender::new();
+        sender.queue(" E  ", 20, 50);
+        assert_eq!(timing(&sender), vec![(false, 420.0), (true, 60.0), (false, 420.0), (false, 420.0)]);
+    }
+
+    #[test]
+    fn queue_skips_unknown_characters() {
+        let mut sender = CwSender::new();
+        sender.queue("#", 20, 50);
+        assert!(sender.elements.is_empty());
+        assert!(sender.pending.is_empty());
+        assert!(!sender.is_active());
+    }
+
+    #[test]
+    fn queue_reports_full() {
+        let mut sender = CwSender::new();
+        sender.queue(&"E".repeat(CW_SENDER_QUEUE_MAX - 1), 20, 50);
+        assert!(!sender.is_full());
+        sender.queue("E", 20, 50);
+        assert!(sender.is_full());
+    }
+
+    #[test]
+    fn tick_breakin_raises_ptt_then_keys_and_hangs() {
+        let mut sender = CwSender::new();
+        sender.queue("E", 20, 50);
+        let t0 = Instant::now();
+        let ms = |n: u64| t0 + Duration::from_millis(n);
+        assert_eq!(sender.tick(t0, false, true, 10, 100), (true, false));
+        assert_eq!(sender.tick(ms(5), false, true, 10, 100), (false, false));
+        assert_eq!(sender.tick(ms(10), false, true, 10, 100), (false, true));
+        assert!(sender.key_down);
+        assert_eq!(sender.pending, "E");
+        assert_eq!(sender.tick(ms(71), false, true, 10, 100), (false, true));
+        assert!(sender.pending.is_empty());
+        assert_eq!(sender.tick(ms(251), false, true, 10, 100), (false, false));
+        assert!(sender.ptt);
+        assert_eq!(sender.tick(ms(300), false, true, 10, 100), (false, false));
+        assert_eq!(sender.tick(ms(352), false, true, 10, 100), (true, false));
+        assert!(!sender.is_active());
+    }
+
+    #[test]
+    fn tick_without_breakin_waits_for_mox() {
+        let mut sender = CwSender::new();
+        sender.queue("E", 20, 50);
+        let t0 = Instant::now();
+        assert_eq!(sender.tick(t0, false, false, 10, 100), (false, false));
+        assert_eq!(sender.elements.len(), 2);
+        assert_eq!(sender.tick(t0, true, false, 10, 100), (false, true));
+        assert!(!sender.ptt);
+    }
+
+    #[test]
+    fn abort_clears_everything() {
+        let mut sender = CwSender::new();
+        sender.queue("TEST", 20, 50);
+        sender.tick(Instant::now(), false, true, 0, 0);
+        sender.abort();
+        assert!(!sender.is_active());
+        assert!(sender.pending.is_empty());
+    }
+
+    #[test]
+    fn expand_macro_fields() {
+        assert_eq!(expand_macro("{CALL} DE {MYCALL} {RST} {NR} {CALL}", "G0ORX", "N6LYT", "599", 7),
+            "N6LYT DE G0ORX 599 007 N6LYT");
+        assert_eq!(expand_macro("CQ {NR}", "", "", "", 1234), "CQ 1234");
+    }
+}
@@ -0,0 +1,302 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use gtk::prelude::*;
+use gtk::{Adjustment, Builder, CheckButton, DrawingArea, GestureDrag, Label, Window};
+
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+use crate::radio::RadioMutex;
+use crate::widgets::AppWidgets;
+use crate::wdsp::*;
+
+// xdivEXT combiner id, there is only one diversity receiver
+const DIVERSITY_ID: i32 = 0;
+
+// RX1 (ADC0) and RX2 (ADC1)
+const DIVERSITY_INPUTS: usize = 2;
+
+// gain range of the second ADC relative to the first
+pub const DIVERSITY_GAIN_MIN: f64 = -25.0;
+pub const DIVERSITY_GAIN_MAX: f64 = 25.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diversity {
+    pub enabled: bool,
+    pub gain: f64,  // dB applied to ADC1
+    pub phase: f64, // degrees applied to ADC1
+#[serde(skip_serializing, skip_deserializing)]
+    input: Vec<Vec<f64>>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub output: Vec<f64>,
+#[serde(skip_serializing, skip_deserializing)]
+    samples: usize,
+#[serde(skip_serializing, skip_deserializing)]
+    pub buffer_size: usize, // 0 until the combiner has been created
+}
+
+impl Default for Diversity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Diversity {
+
+    pub fn new() -> Self {
+        let enabled = false;
+        let gain = 0.0;
+        let phase = 0.0;
+        let input = Vec::new();
+        let output = Vec::new();
+        let samples = 0;
+        let buffer_size = 0;
+
+        Diversity {
+            enabled,
+            gain,
+            phase,
+            input,
+            output,
+            samples,
+            buffer_size,
+        }
+    }
+
+    // (re)create the combiner, also called when the receiver buffer size changes
+    pub fn init(&mut self, buffer_size: usize) {
+        if self.buffer_size != 0 {
+            unsafe {
+                destroy_divEXT(DIVERSITY_ID);
+            }
+        }
+        self.buffer_size = buffer_size;
+        self.input = vec![vec![0.0f64; buffer_size * 2]; DIVERSITY_INPUTS];
+        self.output = vec![0.0f64; buffer_size * 2];
+        self.samples = 0;
+        unsafe {
+            create_divEXT(DIVERSITY_ID, self.enabled as i32, DIVERSITY_INPUTS as i32, buffer_size as i32);
+            SetEXTDIVOutput(DIVERSITY_ID, DIVERSITY_INPUTS as i32); // output the combined inputs
+        }
+        self.set_rotate();
+    }
+
+    pub fn set_enabled(&mut self, state: bool) {
+        self.enabled = state;
+        self.samples = 0;
+        if self.buffer_size == 0 {
+            return; // init runs the combiner with enabled
+        }
+        unsafe {
+            SetEXTDIVRun(DIVERSITY_ID, state as i32);
+        }
+    }
+
+    // ADC0 is the reference, ADC1 is scaled and rotated before they are summed
+    pub fn set_rotate(&self) {
+        if self.buffer_size == 0 {
+            return; // set by init
+        }
+        let gain = 10.0_f64.powf(self.gain / 20.0);
+        let phase = self.phase * PI / 180.0;
+        let mut i_rotate = [1.0, gain * phase.cos()];
+        let mut q_rotate = [0.0, gain * phase.sin()];
+        unsafe {
+            SetEXTDIVRotate(DIVERSITY_ID, DIVERSITY_INPUTS as i32, i_rotate.as_mut_ptr(), q_rotate.as_mut_ptr());
+        }
+    }
+
+    // returns true when a buffer has been combined into output
+    pub fn add_iq_samples(&mut self, i0: f64, q0: f64, i1: f64, q1: f64) -> bool {
+        if self.buffer_size == 0 {
+            return false;
+        }
+        let x = self.samples * 2;
+        self.input[0][x] = i0;
+        self.input[0][x+1] = q0;
+        self.input[1][x] = i1;
+        self.input[1][x+1] = q1;
+        self.samples += 1;
+        if self.samples < self.buffer_size {
+            return false;
+        }
+        self.samples = 0;
+        let mut input_ptrs: Vec<*mut f64> = self.input.iter_mut().map(|buffer| buffer.as_mut_ptr()).collect();
+        unsafe {
+            xdivEXT(DIVERSITY_ID, self.buffer_size as i32, input_ptrs.as_mut_ptr(), self.output.as_mut_ptr());
+        }
+        true
+    }
+}
+
+// gain and phase of ADC1 as a point on the polar plot, the centre is the minimum gain
+fn polar_to_point(gain: f64, phase: f64, width: f64, height: f64) -> (f64, f64) {
+    let radius = width.min(height) / 2.0 - 4.0;
+    let r = radius * (gain - DIVERSITY_GAIN_MIN) / (DIVERSITY_GAIN_MAX - DIVERSITY_GAIN_MIN);
+    let a = phase * PI / 180.0;
+    (width / 2.0 + r * a.cos(), height / 2.0 - r * a.sin())
+}
+
+fn point_to_polar(x: f64, y: f64, width: f64, height: f64) -> (f64, f64) {
+    let radius = width.min(height) / 2.0 - 4.0;
+    let dx = x - width / 2.0;
+    let dy = height / 2.0 - y;
+    let r = (dx * dx + dy * dy).sqrt().min(radius);
+    let gain = DIVERSITY_GAIN_MIN + (r / radius) * (DIVERSITY_GAIN_MAX - DIVERSITY_GAIN_MIN);
+    let phase = dy.atan2(dx) * 180.0 / PI;
+    (gain, phase)
+}
+
+pub fn create_diversity_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex) -> Window {
+
+    let ui_xml = include_str!("../ui/diversity.xml");
+    let builder = Builder::from_string(ui_xml);
+
+    let window: Window = builder
+            .object("diversity_window")
+            .expect("Could not get object `diversity_window` from builder.");
+
+    let app_widgets = rc_app_widgets.borrow();
+    window.set_transient_for(Some(&app_widgets.main_window)); // keeps it on top
+    drop(app_widgets);
+
+    let enable_check_button: CheckButton = builder
+            .object("diversity_enable_check_button")
+            .expect("Could not get object `diversity_enable_check_button` from builder.");
+    let gain_adjustment: Adjustment = builder
+            .object("diversity_gain_adjustment")
+            .expect("Could not get object `diversity_gain_adjustment` from builder.");
+    let phase_adjustment: Adjustment = builder
+            .object("diversity_phase_adjustment")
+            .expect("Could not get object `diversity_phase_adjustment` from builder.");
+    let polar_plot: DrawingArea = builder
+            .object("diversity_polar_plot")
+            .expect("Could not get object `diversity_polar_plot` from builder.");
+    let status_label: Label = builder
+            .object("diversity_status_label")
+            .expect("Could not get object `diversity_status_label` from builder.");
+
+    let r = radio_mutex.radio.lock().unwrap();
+        let available = r.adc.len() > 1;
+        let enabled = r.diversity.enabled;
+        let gain = r.diversity.gain;
+        let phase = r.diversity.phase;
+    drop(r);
+
+    enable_check_button.set_active(enabled);
+    enable_check_button.set_sensitive(available);
+    gain_adjustment.set_value(gain);
+    phase_adjustment.set_value(phase);
+    if !available {
+        status_label.set_label("Diversity needs a radio with two ADCs");
+    }
+
+    let radio_mutex_clone = radio_mutex.clone();
+    enable_check_button.connect_toggled(move |button| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.diversity.set_enabled(button.is_active());
+        r.updated = true;
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let polar_plot_clone = polar_plot.clone();
+    gain_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.diversity.gain = adjustment.value();
+        r.diversity.set_rotate();
+        drop(r);
+        polar_plot_clone.queue_draw();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let polar_plot_clone = polar_plot.clone();
+    phase_adjustment.connect_value_changed(move |adjustment| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        r.diversity.phase = adjustment.value();
+        r.diversity.set_rotate();
+        drop(r);
+        polar_plot_clone.queue_draw();
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    polar_plot.set_draw_func(move |_, cr, width, height| {
+        let width = width as f64;
+        let height = height as f64;
+        let radius = width.min(height) / 2.0 - 4.0;
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        cr.paint().unwrap();
+
+        // 0dB ring, cross hairs and the outer ring
+        cr.set_line_width(1.0);
+        cr.set_source_rgb(0.5, 0.5, 0.5);
+        let (x0, _) = polar_to_point(0.0, 0.0, width, height);
+        cr.arc(width / 2.0, height / 2.0, x0 - width / 2.0, 0.0, 2.0 * PI);
+        cr.stroke().unwrap();
+        cr.move_to(width / 2.0 - radius, height / 2.0);
+        cr.line_to(width / 2.0 + radius, height / 2.0);
+        cr.move_to(width / 2.0, height / 2.0 - radius);
+        cr.line_to(width / 2.0, height / 2.0 + radius);
+        cr.stroke().unwrap();
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.arc(width / 2.0, height / 2.0, radius, 0.0, 2.0 * PI);
+        cr.stroke().unwrap();
+
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        let (x, y) = polar_to_point(r.diversity.gain, r.diversity.phase, width, height);
+        let enabled = r.diversity.enabled;
+        drop(r);
+        if enabled {
+            cr.set_source_rgb(0.0, 1.0, 0.0);
+        } else {
+            cr.set_source_rgb(1.0, 0.65, 0.0);
+        }
+        cr.move_to(width / 2.0, height / 2.0);
+        cr.line_to(x, y);
+        cr.stroke().unwrap();
+        cr.arc(x, y, 4.0, 0.0, 2.0 * PI);
+        cr.fill().unwrap();
+    });
+
+    // drag the point around the plot to null the noise
+    let gesture_drag = GestureDrag::new();
+    let polar_plot_clone = polar_plot.clone();
+    let gain_adjustment_clone = gain_adjustment.clone();
+    let phase_adjustment_clone = phase_adjustment.clone();
+    let set_point = move |x: f64, y: f64| {
+        let width = polar_plot_clone.width() as f64;
+        let height = polar_plot_clone.height() as f64;
+        let (gain, phase) = point_to_polar(x, y, width, height);
+        gain_adjustment_clone.set_value(gain);
+        phase_adjustment_clone.set_value(phase);
+    };
+    let set_point_clone = set_point.clone();
+    gesture_drag.connect_drag_begin(move |_, x, y| {
+        set_point_clone(x, y);
+    });
+    gesture_drag.connect_drag_update(move |gesture, offset_x, offset_y| {
+        if let Some((x, y)) = gesture.start_point() {
+            set_point(x + offset_x, y + offset_y);
+        }
+    });
+    polar_plot.add_controller(gesture_drag);
+
+    window
+}
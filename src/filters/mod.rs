@@ -397,6 +397,11 @@ impl FilterGrid {
     }
 
     pub fn get_filter_values(&self,mode: usize, filter: usize) -> (f64, f64) {
+        Self::filter_values(mode, filter)
+    }
+
+    // filter edges for a mode without needing the FilterGrid widget (used by CAT/rigctl)
+    pub fn filter_values(mode: usize, filter: usize) -> (f64, f64) {
         let m = match Modes::from_usize(mode) {
            Some(Modes::LSB) => Self::filterLSB,
            Some(Modes::USB) => Self::filterUSB,
@@ -0,0 +1,407 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*
+    Runs the radio without the GTK user interface.
+
+    The radio is discovered (or contacted directly with --device), the
+    saved configuration is loaded, the protocol thread is started and all
+    control is done through the CAT, RIGCTL, TCI and MIDI servers.
+
+    A server runs when it is enabled in radio.json or its address is given
+    on the command line. Addresses not on the command line come from radio.json.
+
+    rustyHPSDR --headless [--device ip] [--cat ip:port] [--rigctl ip:port] [--tci ip:port]
+*/
+
+use std::cell::RefCell;
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::net::IpAddr;
+use std::os::raw::c_char;
+use std::os::unix::ffi::OsStringExt;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+
+use crate::cat::{CatMessage, CAT};
+use crate::cwkeyer;
+use crate::cwsender;
+use crate::discovery::{discover, manual_discovery, device_name, Device};
+use crate::midi::{MidiMessage, MIDI};
+use crate::protocol1::Protocol1;
+use crate::protocol2::Protocol2;
+use crate::radio::{Radio, RadioMutex};
+use crate::rigctl::{RIGCTLMessage, RIGCTL};
+use crate::tci::{TCIMessage, TCIDataMessage, TCI};
+use crate::wdsp::*;
+
+const SPECTRUM_WIDTH: i32 = 1024;
+
+pub struct Headless {
+    pub device_address: Option<IpAddr>,
+    pub cat_address: Option<String>,
+    pub rigctl_address: Option<String>,
+    pub tci_address: Option<String>,
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Headless {
+
+    pub fn new() -> Self {
+        let device_address = None;
+        let cat_address = None;
+        let rigctl_address = None;
+        let tci_address = None;
+        Headless {
+            device_address,
+            cat_address,
+            rigctl_address,
+            tci_address,
+        }
+    }
+
+    // returns true if the command line asks for headless mode
+    pub fn requested(args: &[String]) -> bool {
+        args.iter().any(|a| a == "--headless")
+    }
+
+    pub fn parse_args(&mut self, args: &[String]) -> bool {
+        let mut i = 1;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--headless" => {
+                    },
+                "--device" if i + 1 < args.len() => {
+                    i += 1;
+                    match args[i].parse::<IpAddr>() {
+                        Ok(ip) => self.device_address = Some(ip),
+                        Err(e) => {
+                            eprintln!("Invalid device address {}: {}", args[i], e);
+                            return false;
+                        },
+                    }
+                    },
+                "--cat" if i + 1 < args.len() => {
+                    i += 1;
+                    self.cat_address = Some(args[i].clone());
+                    },
+                "--rigctl" if i + 1 < args.len() => {
+                    i += 1;
+                    self.rigctl_address = Some(args[i].clone());
+                    },
+                "--tci" if i + 1 < args.len() => {
+                    i += 1;
+                    self.tci_address = Some(args[i].clone());
+                    },
+                _ => {
+                    eprintln!("Usage: {} --headless [--device ip] [--cat ip:port] [--rigctl ip:port] [--tci ip:port]", args[0]);
+                    return false;
+                    },
+            }
+            i += 1;
+        }
+        true
+    }
+
+    // an address on the command line enables the server, otherwise use radio.json
+    fn server_address(enabled: bool, cli_address: &Option<String>, config_address: &str) -> Option<String> {
+        match cli_address {
+            Some(address) => Some(address.clone()),
+            None if enabled => Some(config_address.to_string()),
+            None => None,
+        }
+    }
+
+    fn find_device(&self) -> Option<Device> {
+        let devices = Rc::new(RefCell::new(Vec::new()));
+        match self.device_address {
+            Some(ip) => {
+                if !manual_discovery(Rc::clone(&devices), ip) {
+                    eprintln!("Headless: no radio found at {}", ip);
+                    return None;
+                }
+                },
+            None => {
+                discover(Rc::clone(&devices));
+                },
+        }
+        let devices = devices.borrow();
+        for device in devices.iter() {
+            eprintln!("Headless: found {} at {:?} Protocol {} status {}", device_name(device.board), device.address, device.protocol, device.status);
+        }
+        // use the first radio that is not in use
+        devices.iter().find(|d| d.status == 2).copied()
+    }
+
+    fn create_wisdom(&self) {
+        let config_dir = dirs::config_dir().unwrap_or_else(env::temp_dir);
+        let my_dir = config_dir.join("rustyHPSDR").join("");
+        if !my_dir.is_dir() && let Err(e) = fs::create_dir_all(&my_dir) {
+            eprintln!("Failed to create directory {:?}: {}", my_dir, e);
+        }
+        match CString::new(my_dir.into_os_string().into_vec()) {
+            Ok(c_string) => {
+                let c_path_ptr: *const c_char = c_string.as_ptr();
+                unsafe {
+                    WDSPwisdom(c_path_ptr);
+                }
+                eprintln!("WDSPwisdom completed");
+            }
+            Err(e) => {
+                eprintln!("Error converting path to C string (contains null bytes?): {}", e);
+            }
+        }
+    }
+
+    pub async fn run(&self) {
+        self.create_wisdom();
+
+        let device = match self.find_device() {
+            Some(d) => d,
+            None => {
+                eprintln!("Headless: no radio available");
+                return;
+            }
+        };
+        eprintln!("Headless: using {} at {:?} Protocol {}", device_name(device.board), device.address, device.protocol);
+
+        let radio_mutex = RadioMutex::new(Arc::new(Mutex::new(Radio::load(device, SPECTRUM_WIDTH))));
+
+        // initialize the receivers and transmitter
+        {
+            let mut r = radio_mutex.radio.lock().unwrap();
+            let rx = if r.receiver[0].active { 0 } else { 1 };
+            for i in 0..2 {
+                r.receiver[i].spectrum_width = SPECTRUM_WIDTH;
+                r.receiver[i].init();
+                let channel = r.receiver[i].channel;
+                r.receiver[i].init_analyzer(channel, SPECTRUM_WIDTH);
+            }
+            r.audio[0].init();
+            r.audio[1].init();
+            r.receiver[rx].set_mode();
+            r.transmitter.init();
+            r.transmitter.init_analyzer(SPECTRUM_WIDTH);
+            let buffer_size = r.receiver[0].buffer_size;
+            r.diversity.init(buffer_size);
+
+            r.notch = 0;
+            for i in 0..r.notches.len() {
+                let notch = r.notches[i];
+                r.add_notch(notch);
+            }
+            unsafe {
+                RXANBPSetTuneFrequency(0, r.receiver[0].frequency as f64);
+                RXANBPSetTuneFrequency(1, r.receiver[1].frequency as f64);
+                RXANBPSetNotchesRun(0, 1);
+                RXANBPSetNotchesRun(1, 1);
+            }
+        }
+
+        match device.protocol {
+            1 => {
+                let mut p1 = Protocol1::new(device);
+                let radio_mutex_clone = radio_mutex.clone();
+                thread::spawn(move || {
+                    p1.run(&radio_mutex_clone);
+                });
+            },
+            2 => {
+                let mut p2 = Protocol2::new(device);
+                let radio_mutex_clone = radio_mutex.clone();
+                thread::spawn(move || {
+                    p2.run(&radio_mutex_clone);
+                });
+            },
+            _ => {
+                eprintln!("Invalid protocol");
+                return;
+            },
+        }
+
+        let radio_mutex_clone = radio_mutex.clone();
+        thread::spawn(move || {
+            cwsender::run(&radio_mutex_clone);
+        });
+
+        let radio_mutex_clone = radio_mutex.clone();
+        thread::spawn(move || {
+            cwkeyer::run(&radio_mutex_clone);
+        });
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+
+        // the servers to run, None when disabled
+        let (cat_address, rigctl_address, tci_address, midi_device) = {
+            let r = radio_mutex.radio.lock().unwrap();
+            let cat_address = Self::server_address(r.cat_enabled, &self.cat_address, &r.cat_address);
+            let rigctl_address = Self::server_address(r.rigctl_enabled, &self.rigctl_address, &r.rigctl_address);
+            let tci_address = Self::server_address(r.tci_enabled, &self.tci_address, &r.tci_address);
+            let midi_device = if r.midi_enabled { Some(r.midi_device.clone()) } else { None };
+            (cat_address, rigctl_address, tci_address, midi_device)
+        };
+
+        // CAT server and serial port (the serial port only when enabled in radio.json)
+        let (cat_tx, cat_rx): (std::sync::mpsc::Sender<CatMessage>, std::sync::mpsc::Receiver<CatMessage>) = std::sync::mpsc::channel();
+        if let Some(cat_address) = cat_address {
+            eprintln!("Headless: CAT server on {}", cat_address);
+            let mut cat = CAT::new(cat_address);
+            let mut cat_pty = cat.clone();
+            let cat_pty_tx = cat_tx.clone();
+            let radio_mutex_clone = radio_mutex.clone();
+            let stop_flag_clone = stop_flag.clone();
+            thread::spawn(move || {
+                if let Err(e) = cat.run(&radio_mutex_clone, &cat_tx, stop_flag_clone) {
+                    eprintln!("CAT server failed: {}", e);
+                }
+            });
+
+            let radio_mutex_clone = radio_mutex.clone();
+            let stop_flag_clone = stop_flag.clone();
+            thread::spawn(move || {
+                if let Err(e) = cat_pty.run_pty(&radio_mutex_clone, &cat_pty_tx, stop_flag_clone) {
+                    eprintln!("CAT serial port failed: {}", e);
+                }
+            });
+        }
+
+        // RIGCTL server
+        let (rigctl_tx, rigctl_rx): (std::sync::mpsc::Sender<RIGCTLMessage>, std::sync::mpsc::Receiver<RIGCTLMessage>) = std::sync::mpsc::channel();
+        if let Some(rigctl_address) = rigctl_address {
+            eprintln!("Headless: RIGCTL server on {}", rigctl_address);
+            let mut rigctl = RIGCTL::new(rigctl_address);
+            let radio_mutex_clone = radio_mutex.clone();
+            let stop_flag_clone = stop_flag.clone();
+            thread::spawn(move || {
+                if let Err(e) = rigctl.run(&radio_mutex_clone, &rigctl_tx, stop_flag_clone) {
+                    eprintln!("RIGCTL server failed: {}", e);
+                }
+            });
+        }
+
+        // TCI server
+        let (tx_from_tci, mut rx_at_main): (mpsc::Sender<TCIMessage>, mpsc::Receiver<TCIMessage>) = mpsc::channel(100);
+        let (tx_from_main, rx_at_tci): (mpsc::Sender<TCIDataMessage>, mpsc::Receiver<TCIDataMessage>) = mpsc::channel(100);
+        if let Some(tci_address) = tci_address {
+            eprintln!("Headless: TCI server on {}", tci_address);
+            let rx_at_tci = Arc::new(tokio::sync::Mutex::new(rx_at_tci));
+            let tci = TCI::new(tci_address);
+            let radio_mutex_clone = radio_mutex.clone();
+            let stop_flag_clone = stop_flag.clone();
+            tokio::spawn(async move {
+                if let Err(e) = tci.run(radio_mutex_clone, &tx_from_tci, rx_at_tci, stop_flag_clone).await {
+                    eprintln!("TCI server failed: {}", e);
+                }
+            });
+        }
+
+        // MIDI controller
+        let (midi_tx, midi_rx): (std::sync::mpsc::Sender<MidiMessage>, std::sync::mpsc::Receiver<MidiMessage>) = std::sync::mpsc::channel();
+        if let Some(midi_device) = midi_device {
+            eprintln!("Headless: MIDI device {}", midi_device);
+            let midi = MIDI::new(midi_device);
+            let radio_mutex_clone = radio_mutex.clone();
+            let stop_flag_clone = stop_flag.clone();
+            thread::spawn(move || {
+                if let Err(e) = midi.run(&radio_mutex_clone, &midi_tx, stop_flag_clone) {
+                    eprintln!("MIDI: {}", e);
+                }
+            });
+        }
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        let mut keepalive_time = Instant::now();
+        loop {
+            tokio::select! {
+                _ = &mut ctrl_c => {
+                    eprintln!("Headless: shutting down");
+                    break;
+                }
+                _ = tokio::time::sleep(Duration::from_millis(10)) => {
+                }
+            }
+
+            // protocol 2 needs to send a keep alive message
+            if device.protocol == 2 && keepalive_time.elapsed() >= Duration::from_millis(250) {
+                keepalive_time = Instant::now();
+                let mut r = radio_mutex.radio.lock().unwrap();
+                r.keepalive = true;
+                if !r.received {
+                    eprintln!("no data received from radio!");
+                } else {
+                    r.received = false;
+                }
+            }
+
+            while let Ok(msg) = cat_rx.try_recv() {
+                let mut r = radio_mutex.radio.lock().unwrap();
+                r.handle_cat_message(&msg);
+            }
+
+            while let Ok(msg) = rigctl_rx.try_recv() {
+                match msg {
+                    RIGCTLMessage::ClientConnected() => eprintln!("RIGCTL client connected"),
+                    RIGCTLMessage::ClientDisconnected() => eprintln!("RIGCTL client disconnected"),
+                    _ => {},
+                }
+                let mut r = radio_mutex.radio.lock().unwrap();
+                r.handle_rigctl_message(&msg);
+            }
+
+            while let Ok(msg) = midi_rx.try_recv() {
+                let mut r = radio_mutex.radio.lock().unwrap();
+                r.handle_midi_message(&msg);
+            }
+
+            loop {
+                match rx_at_main.try_recv() {
+                    Ok(msg) => {
+                        match msg {
+                            TCIMessage::ClientConnected() => eprintln!("TCI client connected"),
+                            TCIMessage::ClientDisconnected() => eprintln!("TCI client disconnected"),
+                            _ => {},
+                        }
+                        let mut r = radio_mutex.radio.lock().unwrap();
+                        r.handle_tci_message(&msg, &tx_from_main);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+        }
+
+        stop_flag.store(true, Ordering::SeqCst);
+        let mut r = radio_mutex.radio.lock().unwrap();
+        r.mox = false;
+        r.tune = false;
+        r.set_state();
+        r.save(device);
+    }
+
+}
@@ -42,4 +42,13 @@ pub mod tci;
 pub mod widgets;
 pub mod antenna;
 pub mod vfo;
+pub mod simulator;
+pub mod headless;
+pub mod memories;
+pub mod scanner;
+pub mod bandplan;
+pub mod cwdecoder;
+pub mod cwkeyer;
+pub mod cwsender;
+pub mod diversity;
 
@@ -52,7 +52,7 @@ use rustyHPSDR::agc::*;
 use rustyHPSDR::bands::*;
 use rustyHPSDR::cat::{CatMessage, CAT};
 use rustyHPSDR::rigctl::{RIGCTLMessage, RIGCTL};
-//use rustyHPSDR::midi::{MidiMessage, MIDI};
+use rustyHPSDR::midi::{MidiMessage, MIDI};
 use rustyHPSDR::tci::{TCIMessage, TCIDataMessage, TCI};
 use rustyHPSDR::modes::*;
 use rustyHPSDR::filters::*;
@@ -62,6 +62,12 @@ use rustyHPSDR::discovery::Boards;
 use rustyHPSDR::radio::Radio;
 use rustyHPSDR::radio::RadioMutex;
 use rustyHPSDR::configure::*;
+use rustyHPSDR::cwdecoder::CW_DECODER_HISTORY;
+use rustyHPSDR::cwkeyer;
+use rustyHPSDR::cwsender::{self, create_cw_sender_dialog};
+use rustyHPSDR::diversity::create_diversity_dialog;
+use rustyHPSDR::memories::{create_memories_dialog, update_memory_widgets};
+use rustyHPSDR::scanner::SCAN_TICK_MS;
 use rustyHPSDR::protocol1::Protocol1;
 use rustyHPSDR::protocol2::Protocol2;
 use rustyHPSDR::spectrum::*;
@@ -72,9 +78,20 @@ use rustyHPSDR::wdsp::*;
 use rustyHPSDR::notches::*;
 use rustyHPSDR::widgets::*;
 use rustyHPSDR::vfo::*;
+use rustyHPSDR::headless::Headless;
 
 #[tokio::main]
 async fn main() {
+    // run without the user interface if requested
+    let args: Vec<String> = env::args().collect();
+    if Headless::requested(&args) {
+        let mut headless = Headless::new();
+        if headless.parse_args(&args) {
+            headless.run().await;
+        }
+        return;
+    }
+
     let id = format!("org.g0orx.rustyHPSDR.pid{}", process::id());
     let application = Application::builder()
         .application_id(id)
@@ -345,6 +362,12 @@ fn build_ui(app: &Application) {
                         style_context.add_class("basic-button");
                         let style_context = app_widgets.a_swap_b_button.style_context();
                         style_context.add_class("basic-button");
+                        let style_context = app_widgets.memories_button.style_context();
+                        style_context.add_class("basic-button");
+                        let style_context = app_widgets.cw_button.style_context();
+                        style_context.add_class("basic-button");
+                        let style_context = app_widgets.diversity_button.style_context();
+                        style_context.add_class("basic-button");
 
                         let style_context = app_widgets.ctun_button.style_context();
                         style_context.add_class("toggle");
@@ -358,14 +381,17 @@ fn build_ui(app: &Application) {
                         style_context.add_class("toggle");
                         app_widgets.rigctl_button.set_active(r.rigctl_enabled);
 
-                        //let style_context = app_widgets.midi_button.style_context();
-                        //style_context.add_class("toggle");
-                        //app_widgets.midi_button.set_active(r.midi_enabled);
+                        let style_context = app_widgets.midi_button.style_context();
+                        style_context.add_class("toggle");
+                        app_widgets.midi_button.set_active(r.midi_enabled);
 
                         let style_context = app_widgets.tci_button.style_context();
                         style_context.add_class("toggle");
                         app_widgets.tci_button.set_active(r.tci_enabled);
 
+                        let style_context = app_widgets.scan_button.style_context();
+                        style_context.add_class("toggle");
+
                         let style_context = app_widgets.split_button.style_context();
                         style_context.add_class("toggle");
                         app_widgets.split_button.set_active(r.split);
@@ -479,6 +505,11 @@ fn build_ui(app: &Application) {
                         app_widgets.snb_button.set_active(r.receiver[rx].snb);
                         r.receiver[rx].set_snb();
 
+                        let style_context = app_widgets.cw_decoder_button.style_context();
+                        style_context.add_class("toggle");
+                        app_widgets.cw_decoder_button.set_active(r.receiver[rx].cw_decoder);
+                        cw_decoder_visibility(&r, &app_widgets);
+
                         let style_context = app_widgets.mox_button.style_context();
                         style_context.add_class("toggle");
 
@@ -502,6 +533,83 @@ fn build_ui(app: &Application) {
                         });
                     });                         
 
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
+                    app_widgets.memories_button.connect_clicked(move |_| {
+                        let app_widgets = rc_app_widgets_clone_clone.borrow();
+                        let memories_dialog = create_memories_dialog(&rc_app_widgets_clone_clone.clone(), &radio_mutex_clone);
+                        app_widgets.memories_button.set_sensitive(false);
+                        memories_dialog.present();
+                        let rc_app_widgets = rc_app_widgets_clone_clone.clone();
+                        memories_dialog.connect_close_request(move |_| {
+                            let app_widgets = rc_app_widgets.borrow();
+                            app_widgets.memories_button.set_sensitive(true);
+                            Propagation::Proceed
+                        });
+                    });
+
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
+                    app_widgets.cw_button.connect_clicked(move |_| {
+                        let app_widgets = rc_app_widgets_clone_clone.borrow();
+                        let cw_sender_dialog = create_cw_sender_dialog(&rc_app_widgets_clone_clone.clone(), &radio_mutex_clone);
+                        app_widgets.cw_button.set_sensitive(false);
+                        cw_sender_dialog.present();
+                        let rc_app_widgets = rc_app_widgets_clone_clone.clone();
+                        cw_sender_dialog.connect_close_request(move |_| {
+                            let app_widgets = rc_app_widgets.borrow();
+                            app_widgets.cw_button.set_sensitive(true);
+                            Propagation::Proceed
+                        });
+                    });
+
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
+                    app_widgets.diversity_button.connect_clicked(move |_| {
+                        let app_widgets = rc_app_widgets_clone_clone.borrow();
+                        let diversity_dialog = create_diversity_dialog(&rc_app_widgets_clone_clone.clone(), &radio_mutex_clone);
+                        app_widgets.diversity_button.set_sensitive(false);
+                        diversity_dialog.present();
+                        let rc_app_widgets = rc_app_widgets_clone_clone.clone();
+                        diversity_dialog.connect_close_request(move |_| {
+                            let app_widgets = rc_app_widgets.borrow();
+                            app_widgets.diversity_button.set_sensitive(true);
+                            Propagation::Proceed
+                        });
+                    });
+
+                    let radio_mutex_clone = radio_mutex.clone();
+                    app_widgets.scan_button.connect_clicked(move |button| {
+                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                        if button.is_active() {
+                            let rx = if r.receiver[0].active { 0 } else { 1 };
+                            r.start_scan(rx);
+                        } else {
+                            r.stop_scan();
+                        }
+                    });
+
+                    // step the scanner
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
+                    glib::timeout_add_local(Duration::from_millis(SCAN_TICK_MS), move || {
+                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                        let retuned = r.scan();
+                        let running = r.scanner.is_running();
+                        drop(r);
+                        if retuned {
+                            // band and memory scans both move the VFO
+                            update_memory_widgets(&rc_app_widgets_clone_clone, &radio_mutex_clone);
+                            update_ui(&radio_mutex_clone, &rc_app_widgets_clone_clone);
+                        }
+                        let app_widgets = rc_app_widgets_clone_clone.borrow();
+                        if !running && app_widgets.scan_button.is_active() {
+                            // stopped by transmit or an empty memory bank
+                            app_widgets.scan_button.set_active(false);
+                        }
+                        glib::ControlFlow::Continue
+                    });
+
                     let scroll_controller_a = EventControllerScroll::new(
                         EventControllerScrollFlags::VERTICAL
                     );
@@ -663,6 +771,7 @@ fn build_ui(app: &Application) {
                             let mut r = radio_mutex_clone.radio.lock().unwrap();
                             let app_widgets = rc_app_widgets_clone_clone.borrow();
                             r.rx2_enabled = button.is_active();
+                            cw_decoder_visibility(&r, &app_widgets);
                             if r.rx2_enabled {
                                 app_widgets.spectrum_2_display.set_visible(true);
                                 app_widgets.waterfall_2_display.set_visible(true);
@@ -1260,19 +1369,30 @@ fn build_ui(app: &Application) {
                         r.receiver[rx].set_snb();
                     });
 
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
+                    app_widgets.cw_decoder_button.clone().connect_clicked(move |button| {
+                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                        let app_widgets = rc_app_widgets_clone_clone.borrow();
+                        let rx = if r.receiver[0].active { 0 } else { 1 };
+                        r.receiver[rx].cw_decoder = button.is_active();
+                        if r.receiver[rx].cw_decoder {
+                            r.receiver[rx].cw_decoder_state.reset();
+                        }
+                        cw_decoder_visibility(&r, &app_widgets);
+                    });
+
                     let radio_mutex_clone = radio_mutex.clone();
                     let rc_app_widgets_clone_clone = rc_app_widgets_clone.clone();
                     app_widgets.mox_button.clone().connect_clicked(move |button| {
                         let mut r = radio_mutex_clone.radio.lock().unwrap();
                         let app_widgets = rc_app_widgets_clone_clone.borrow();
-                        r.mox = button.is_active();
-                        if r.mox && app_widgets.tun_button.is_active() {
-                           app_widgets.tun_button.set_active(false);
-                           r.tune = false;
-                           r.transmitter.set_tuning(r.tune, r.cw_keyer_sidetone_frequency);
+                        if !r.set_mox(button.is_active()) {
+                            button.set_active(false);
+                            app_widgets.tx_lockout.set_label(&r.tx_lockout_reason);
+                            return;
                         }
-                        r.updated = true;
-                        r.set_state();
+                        app_widgets.tun_button.set_active(r.tune);
                         if r.mox {
                             if r.split {
                                 app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
@@ -1295,14 +1415,12 @@ fn build_ui(app: &Application) {
                     app_widgets.tun_button.clone().connect_clicked(move |button| {
                         let mut r = radio_mutex_clone.radio.lock().unwrap();
                         let app_widgets = rc_app_widgets_clone_clone.borrow();
-                        r.tune = button.is_active();
-                        if r.tune && app_widgets.mox_button.is_active() {
-                           app_widgets.mox_button.set_active(false);
-                           r.mox = false;
+                        if !r.set_tune(button.is_active()) {
+                            button.set_active(false);
+                            app_widgets.tx_lockout.set_label(&r.tx_lockout_reason);
+                            return;
                         }
-                        r.transmitter.set_tuning(r.tune, r.cw_keyer_sidetone_frequency);
-                        r.updated = true;
-                        r.set_state();
+                        app_widgets.mox_button.set_active(r.mox);
                         if r.tune {
                             if r.split {
                                 app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
@@ -1430,6 +1548,8 @@ fn build_ui(app: &Application) {
                         r.audio[1].init();
                         r.receiver[rx].set_mode();
                         r.transmitter.init();
+                        let buffer_size = r.receiver[0].buffer_size;
+                        r.diversity.init(buffer_size);
 
 
                         if !r.rx2_enabled {
@@ -1475,6 +1595,8 @@ fn build_ui(app: &Application) {
 
                         app_widgets.snb_button.set_active(r.receiver[rx].snb);
 
+                        app_widgets.cw_decoder_button.set_active(r.receiver[rx].cw_decoder);
+
                         //initialize the notch vector
                         r.notch = 0;
                         for i in 0..r.notches.len() {
@@ -1552,6 +1674,16 @@ fn build_ui(app: &Application) {
                         _ => eprintln!("Invalid protocol"),
                     }
 
+                    let radio_mutex_clone = radio_mutex.clone();
+                    thread::spawn(move || {
+                        cwsender::run(&radio_mutex_clone);
+                    });
+
+                    let radio_mutex_clone = radio_mutex.clone();
+                    thread::spawn(move || {
+                        cwkeyer::run(&radio_mutex_clone);
+                    });
+
                     let radio_mutex_clone = radio_mutex.clone();
                     app_widgets.main_window.connect_close_request(move |_| {
                         let r = radio_mutex_clone.radio.lock().unwrap();
@@ -1631,6 +1763,16 @@ fn build_ui(app: &Application) {
                     let rc_meter_tx_clone2 = rc_meter_tx_clone.clone();
                     let meter_tx_timeout_id = timeout_add_local(Duration::from_millis(update_interval as u64), move || {
                         meter_tx_update(&radio_mutex_clone, &rc_app_widgets_clone2, &rc_meter_tx_clone2);
+                        tx_lockout_update(&radio_mutex_clone, &rc_app_widgets_clone2);
+                        ps_status_update(&radio_mutex_clone, &rc_app_widgets_clone2);
+                        Continue
+                    });
+
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let rc_app_widgets_clone2 = rc_app_widgets_clone.clone();
+                    let mut cw_decoder_count = [0usize; 2];
+                    let cw_decoder_timeout_id = timeout_add_local(Duration::from_millis(update_interval as u64), move || {
+                        cw_decoder_update(&radio_mutex_clone, &rc_app_widgets_clone2, &mut cw_decoder_count);
                         Continue
                     });
 
@@ -1675,6 +1817,13 @@ fn build_ui(app: &Application) {
                         thread::spawn(move || {
                             cat_clone_clone.run(&radio_mutex_clone, &tx_clone, stop_flag_clone);
                         });
+                        let radio_mutex_clone = radio_mutex.clone();
+                        let mut cat_clone_clone = cat_clone.clone();
+                        let tx_clone = tx.clone();
+                        let stop_flag_clone = stop_flag.clone();
+                        thread::spawn(move || {
+                            cat_clone_clone.run_pty(&radio_mutex_clone, &tx_clone, stop_flag_clone);
+                        });
                     }
 
                     // handle CAT button
@@ -1694,6 +1843,13 @@ fn build_ui(app: &Application) {
                             thread::spawn(move || {
                                 cat_clone_clone.run(&radio_mutex_clone_clone, &tx_clone_clone, stop_flag_clone);
                             });
+                            let radio_mutex_clone_clone = radio_mutex_clone.clone();
+                            let mut cat_clone_clone = cat_clone.clone();
+                            let tx_clone_clone = tx_clone.clone();
+                            let stop_flag_clone = stop_flag.clone();
+                            thread::spawn(move || {
+                                cat_clone_clone.run_pty(&radio_mutex_clone_clone, &tx_clone_clone, stop_flag_clone);
+                            });
                         } else {
                             let mut r = radio_mutex_clone.radio.lock().unwrap();
                             r.cat_enabled = false;
@@ -1708,63 +1864,53 @@ fn build_ui(app: &Application) {
                     glib::timeout_add_local(Duration::from_millis(100), clone!(@strong radio_mutex_clone, @strong rc_app_widgets_clone=> move || {
                         match rx.try_recv() {
                             Ok(msg) => {
-                                // Message received, update the UI
+                                // Message received, update the radio then the UI
+                                let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                let accepted = r.handle_cat_message(&msg);
+                                drop(r);
                                 match msg {
-                                    CatMessage::UpdateMox(state) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let app_widgets = rc_app_widgets_clone2.borrow();
-                                        r.mox = state;
-                                        r.updated = true;
-                                        r.set_state();
-                                        if r.mox {
-                                            if r.split {
-                                                app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
-                                                app_widgets.vfo_b_frequency.add_css_class("vfo-tx-label");
-                                            } else {
-                                                app_widgets.vfo_a_frequency.remove_css_class("vfo-a-label");
-                                                app_widgets.vfo_a_frequency.add_css_class("vfo-tx-label");
-                                            }
-                                        } else if r.split {
-                                            app_widgets.vfo_b_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_b_frequency.add_css_class("vfo-b-label");
-                                        } else {
-                                            app_widgets.vfo_a_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_a_frequency.add_css_class("vfo-a-label");
-                                        }
+                                    CatMessage::UpdateMox(_) => {
+                                        update_remote_mox_ui(&radio_mutex_clone, &rc_app_widgets_clone2, accepted);
                                     },
-                                    CatMessage::UpdateFrequencyA(f) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
-                                        if let Some(band_info) = r.receiver[0].find_band_from_frequency(f) {
-                                            // turn off CTUN
-                                            r.receiver[0].ctun = false;
+                                    CatMessage::UpdateFrequencyA(_) => {
+                                        if accepted {
+                                            let r = radio_mutex_clone.radio.lock().unwrap();
+                                            let app_widgets = rc_app_widgets_clone2.borrow();
                                             app_widgets.ctun_button.set_active(r.receiver[0].ctun);
-                                            if r.receiver[0].band != band_info.band {
-                                                r.receiver[0].band = band_info.band;
-                                                //app_widgets.band_grid.set_active_index(band_info.band.to_usize());
-                                            }
-                                            r.receiver[0].set_frequency(f);
                                             let formatted_value = format_u32_with_separators(r.receiver[0].frequency as u32);
                                             app_widgets.vfo_a_frequency.set_label(&formatted_value);
-                                        } else {
-                                            // ignore it as not a valid address for bands
                                         }
                                     },
-                                    CatMessage::UpdateFrequencyB(f) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let app_widgets = rc_app_widgets_clone.borrow();
-                                        if let Some(band_info) = r.receiver[0].find_band_from_frequency(f) {
-                                            // turn off CTUN
-                                            r.receiver[1].ctun = false;
+                                    CatMessage::UpdateFrequencyB(_) => {
+                                        if accepted {
+                                            let r = radio_mutex_clone.radio.lock().unwrap();
+                                            let app_widgets = rc_app_widgets_clone2.borrow();
                                             app_widgets.ctun_button.set_active(r.receiver[1].ctun);
-                                            r.receiver[1].band = band_info.band;
-                                            r.receiver[1].frequency = f;
                                             let formatted_value = format_u32_with_separators(r.receiver[1].frequency as u32);
                                             app_widgets.vfo_b_frequency.set_label(&formatted_value);
-                                        } else {
-                                            // ignore it as not a valid address for bands
                                         }
                                     },
+                                    CatMessage::UpdateFilter(_, _, _) => {},
+                                    CatMessage::UpdateSplit(state) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.split_button.set_active(state);
+                                    },
+                                    CatMessage::UpdateDrive(drive) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        // the drive adjustment callback updates the radio
+                                        app_widgets.drive_adjustment.set_value(drive.into());
+                                    },
+                                    CatMessage::UpdateBand(rx, _) => {
+                                        update_vfo_label(&radio_mutex_clone, &rc_app_widgets_clone2, rx);
+                                        update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
+                                    },
+                                    CatMessage::RecallMemory(_, _) => {
+                                        if accepted {
+                                            update_memory_widgets(&rc_app_widgets_clone2, &radio_mutex_clone);
+                                            update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
+                                        }
+                                    },
+                                    _ => update_ui(&radio_mutex_clone, &rc_app_widgets_clone2),
                                 }
                                 // Continue the polling timeout (return Continue(true))
                                 glib::ControlFlow::Continue
@@ -1830,7 +1976,10 @@ fn build_ui(app: &Application) {
                     glib::timeout_add_local(Duration::from_millis(100), clone!(@strong radio_mutex_clone, @strong rc_app_widgets_clone=> move || {
                         match rx.try_recv() {
                             Ok(msg) => {
-                                // Message received, update the UI
+                                // Message received, update the radio then the UI
+                                let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                let accepted = r.handle_rigctl_message(&msg);
+                                drop(r);
                                 match msg {
                                     RIGCTLMessage::ClientConnected() => {
                                         let app_widgets = rc_app_widgets_clone2.borrow();
@@ -1840,46 +1989,43 @@ fn build_ui(app: &Application) {
                                         let app_widgets = rc_app_widgets_clone2.borrow();
                                         app_widgets.rigctl_button.remove_css_class("connected");
                                     },
-                                    RIGCTLMessage::UpdateMox(state) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let app_widgets = rc_app_widgets_clone2.borrow();
-                                        r.mox = state;
-                                        r.updated = true;
-                                        r.set_state();
-                                        if r.mox {
-                                            if r.split {
-                                                app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
-                                                app_widgets.vfo_b_frequency.add_css_class("vfo-tx-label");
-                                            } else {
-                                                app_widgets.vfo_a_frequency.remove_css_class("vfo-a-label");
-                                                app_widgets.vfo_a_frequency.add_css_class("vfo-tx-label");
-                                            }
-                                        } else if r.split {
-                                            app_widgets.vfo_b_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_b_frequency.add_css_class("vfo-b-label");
-                                        } else {
-                                            app_widgets.vfo_a_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_a_frequency.add_css_class("vfo-a-label");
-                                        }
+                                    RIGCTLMessage::UpdateMox(_) => {
+                                        update_remote_mox_ui(&radio_mutex_clone, &rc_app_widgets_clone2, accepted);
                                     },
-                                    RIGCTLMessage::UpdateFrequencyA(f) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
-                                        if let Some(band_info) = r.receiver[0].find_band_from_frequency(f) {
-                                            // turn off CTUN
-                                            r.receiver[0].ctun = false;
+                                    RIGCTLMessage::UpdateFrequencyA(_) => {
+                                        if accepted {
+                                            let r = radio_mutex_clone.radio.lock().unwrap();
+                                            let app_widgets = rc_app_widgets_clone2.borrow();
                                             app_widgets.ctun_button.set_active(r.receiver[0].ctun);
-                                            if r.receiver[0].band != band_info.band {
-                                                r.receiver[0].band = band_info.band;
-                                                //app_widgets.band_grid.set_active_index(band_info.band.to_usize());
-                                            }
-                                            r.receiver[0].set_frequency(f);
                                             let formatted_value = format_u32_with_separators(r.receiver[0].frequency as u32);
                                             app_widgets.vfo_a_frequency.set_label(&formatted_value);
-                                        } else {
-                                            // ignore it as not a valid address for bands
                                         }
                                     },
+                                    RIGCTLMessage::UpdateFrequencyB(_) => {
+                                        if accepted {
+                                            let r = radio_mutex_clone.radio.lock().unwrap();
+                                            let app_widgets = rc_app_widgets_clone2.borrow();
+                                            let formatted_value = format_u32_with_separators(r.receiver[1].frequency as u32);
+                                            app_widgets.vfo_b_frequency.set_label(&formatted_value);
+                                        }
+                                    },
+                                    RIGCTLMessage::UpdateSplit(state) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.split_button.set_active(state);
+                                    },
+                                    RIGCTLMessage::UpdateDrive(drive) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        // the drive adjustment callback updates the radio
+                                        app_widgets.drive_adjustment.set_value(drive.into());
+                                    },
+                                    RIGCTLMessage::RecallMemory(_, _) => {
+                                        if accepted {
+                                            update_memory_widgets(&rc_app_widgets_clone2, &radio_mutex_clone);
+                                            update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
+                                        }
+                                    },
+                                    RIGCTLMessage::StoreMemory(_, _) => {},
+                                    _ => update_ui(&radio_mutex_clone, &rc_app_widgets_clone2),
                                 }
                                 // Continue the polling timeout (return Continue(true))
                                 glib::ControlFlow::Continue
@@ -1898,41 +2044,43 @@ fn build_ui(app: &Application) {
                     }));
 
 
-/*
                     let r = radio_mutex.radio.lock().unwrap();
                     let midi_enabled = r.midi_enabled;
+                    let midi_device = r.midi_device.clone();
                     drop(r);
-                    let midi = MIDI::new("Studio 2A:Studio 2A MIDI 1 28:0".to_string());
                     let (tx, rx): (std::sync::mpsc::Sender<MidiMessage>, std::sync::mpsc::Receiver<MidiMessage>) = std::sync::mpsc::channel();
                     let stop_midi_flag = Arc::new(AtomicBool::new(false));
                     let tx_clone = tx.clone();
                     let stop_flag = Arc::clone(&stop_midi_flag);
-                    let midi_clone = midi.clone();
                     if midi_enabled {
                         let radio_mutex_clone = radio_mutex.clone();
-                        let midi_clone_clone = midi_clone.clone();
+                        let midi = MIDI::new(midi_device);
                         let stop_flag_clone = stop_flag.clone();
                         thread::spawn(move || {
-                            midi_clone_clone.run(&radio_mutex_clone, &tx_clone, stop_flag_clone);
+                            if let Err(e) = midi.run(&radio_mutex_clone, &tx_clone, stop_flag_clone) {
+                                eprintln!("MIDI: {}", e);
+                            }
                         });
                     }
 
                     // handle MIDI button
                     let radio_mutex_clone = radio_mutex.clone();
                     let tx_clone = tx.clone();
-                    let midi_clone = midi.clone();
                     app_widgets.midi_button.connect_clicked(move |button| {
                         if button.is_active() {
                             let mut r = radio_mutex_clone.radio.lock().unwrap();
                             r.midi_enabled = true;
+                            // the device may have been changed in the configure dialog
+                            let midi = MIDI::new(r.midi_device.clone());
                             drop(r);
                             stop_flag.store(false, Ordering::SeqCst);
                             let radio_mutex_clone_clone = radio_mutex_clone.clone();
-                            let midi_clone_clone = midi_clone.clone();
                             let tx_clone_clone = tx_clone.clone();
                             let stop_flag_clone = stop_flag.clone();
                             thread::spawn(move || {
-                                midi_clone_clone.run(&radio_mutex_clone_clone, &tx_clone_clone, stop_flag_clone);
+                                if let Err(e) = midi.run(&radio_mutex_clone_clone, &tx_clone_clone, stop_flag_clone) {
+                                    eprintln!("MIDI: {}", e);
+                                }
                             });
                         } else {
                             let mut r = radio_mutex_clone.radio.lock().unwrap();
@@ -1948,17 +2096,67 @@ fn build_ui(app: &Application) {
                     glib::timeout_add_local(Duration::from_millis(100), clone!(@strong radio_mutex_clone, @strong rc_app_widgets_clone=> move || {
                         match rx.try_recv() {
                             Ok(msg) => {
-                                // Message received, update the UI
+                                // Message received, the transmit, AF gain, NR and NB controls
+                                // go through their widgets, the rest update the radio directly
                                 match msg {
+                                    MidiMessage::UpdateMox(state) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        if app_widgets.mox_button.is_active() != state {
+                                            app_widgets.mox_button.emit_clicked();
+                                        }
+                                    },
                                     MidiMessage::StepFrequencyA(increment) => {
-                                        eprintln!("MidiMessage::StepFrequencyA {}", increment);
                                         spectrum_waterfall_scroll(&radio_mutex_clone, &rc_app_widgets_clone2, 0, -increment as f64);
                                     },
-                                    MidiMessage::StepFrequencyA(increment) => {
-                                        eprintln!("MidiMessage::StepFrequencyB {}", increment);
+                                    MidiMessage::StepFrequencyB(increment) => {
+                                        spectrum_waterfall_scroll(&radio_mutex_clone, &rc_app_widgets_clone2, 1, -increment as f64);
+                                    },
+                                    MidiMessage::UpdateAFGain(gain) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        // the afgain adjustment callback updates the radio
+                                        app_widgets.afgain_adjustment.set_value((gain * 100.0).into());
+                                    },
+                                    MidiMessage::StepAFGain(increment) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        let value = app_widgets.afgain_adjustment.value() + increment as f64;
+                                        app_widgets.afgain_adjustment.set_value(value.clamp(0.0, 100.0));
+                                    },
+                                    MidiMessage::ToggleMox => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.mox_button.emit_clicked();
+                                    },
+                                    MidiMessage::ToggleTune => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.tun_button.emit_clicked();
+                                    },
+                                    MidiMessage::ToggleNR => {
+                                        // cycles NR/NR2/NR3/NR4/off like the button
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.nr_button.emit_clicked();
+                                    },
+                                    MidiMessage::ToggleNB => {
+                                        // cycles NB/NB2/off like the button
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.nb_button.emit_clicked();
+                                    },
+                                    MidiMessage::UpdateFilterWidth(_) | MidiMessage::StepFilterWidth(_) => {
+                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                        r.handle_midi_message(&msg);
+                                    },
+                                    MidiMessage::BandUp | MidiMessage::BandDown => {
+                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                        let rx = if r.receiver[0].active { 0 } else { 1 };
+                                        r.handle_midi_message(&msg);
+                                        drop(r);
+                                        update_vfo_label(&radio_mutex_clone, &rc_app_widgets_clone2, rx);
+                                        update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
+                                    },
+                                    MidiMessage::NextMode => {
+                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                        r.handle_midi_message(&msg);
+                                        drop(r);
+                                        update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
                                     },
-                                    _ => {
-                                    }
                                 }
                                 // Continue the polling timeout (return Continue(true))
                                 glib::ControlFlow::Continue
@@ -1975,7 +2173,7 @@ fn build_ui(app: &Application) {
                             }
                         }
                     }));
-*/
+
                     let r = radio_mutex.radio.lock().unwrap();
                     let tci_enabled = r.tci_enabled;
                     drop(r);
@@ -2038,91 +2236,47 @@ eprintln!("Disable TCI Server");
                     //glib::timeout_add_local(Duration::from_millis(100), move || {
                         match rx_at_main.try_recv() {
                             Ok(msg) => {
-                                // Message received, update the UI
+                                // Message received, update the radio then the UI
+                                let mut r = radio_mutex_clone.radio.lock().unwrap();
+                                let accepted = r.handle_tci_message(&msg, &tx_from_main_for_timeout);
+                                drop(r);
                                 match msg {
                                     TCIMessage::ClientConnected() => {
                                         let app_widgets = rc_app_widgets_clone2.borrow();
                                         app_widgets.tci_button.add_css_class("connected");
                                     },
                                     TCIMessage::ClientDisconnected() => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        r.receiver[0].tci_send_iq_samples = false;
-                                        drop(r);
                                         let app_widgets = rc_app_widgets_clone2.borrow();
                                         app_widgets.tci_button.remove_css_class("connected");
                                     },
-                                    TCIMessage::IQStart(rx) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let tx = tx_from_main_for_timeout.clone();
-                                        r.receiver[rx].enable_tci_iq(tx);
-                                    },
-                                    TCIMessage::UpdateMox(state) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let app_widgets = rc_app_widgets_clone2.borrow();
-                                        r.mox = state;
-                                        r.updated = true;
-                                        r.set_state();
-                                        if r.mox {
-                                            if r.split {
-                                                app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
-                                                app_widgets.vfo_b_frequency.add_css_class("vfo-tx-label");
-                                            } else {
-                                                app_widgets.vfo_a_frequency.remove_css_class("vfo-a-label");
-                                                app_widgets.vfo_a_frequency.add_css_class("vfo-tx-label");
-                                            }
-                                        } else if r.split {
-                                            app_widgets.vfo_b_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_b_frequency.add_css_class("vfo-b-label");
-                                        } else {
-                                            app_widgets.vfo_a_frequency.remove_css_class("vfo-tx-label");
-                                            app_widgets.vfo_a_frequency.add_css_class("vfo-a-label");
-                                        }
-                                    },
-                                    TCIMessage::UpdateDDS(f) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        r.receiver[0].ctun = true;
-                                        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
-                                        if let Some(band_info) = r.receiver[0].find_band_from_frequency(f) {
-                                            app_widgets.ctun_button.set_active(r.receiver[0].ctun);
-                                            if r.receiver[0].band != band_info.band {
-                                                r.receiver[0].band = band_info.band;
-                                            }
-                                            r.receiver[0].ctun = true;
-                                            r.receiver[0].frequency = f;
-                                            r.receiver[0].ctun_frequency = f;
-                                            let formatted_value = format_u32_with_separators(r.receiver[0].frequency as u32);
-                                            app_widgets.vfo_a_frequency.set_label(&formatted_value);
-                                        } else {
-                                            // ignore it as not a valid address for bands
-                                        }
+                                    TCIMessage::UpdateMox(_) => {
+                                        update_remote_mox_ui(&radio_mutex_clone, &rc_app_widgets_clone2, accepted);
                                     },
-                                    TCIMessage::UpdateFrequencyA(f) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
-                                        if let Some(band_info) = r.receiver[0].find_band_from_frequency(f) {
-                                            app_widgets.ctun_button.set_active(r.receiver[0].ctun);
-                                            if r.receiver[0].band != band_info.band {
-                                                r.receiver[0].band = band_info.band;
-                                            }
-                                            r.receiver[0].ctun = true;
-                                            r.receiver[0].set_frequency(f);
-                                            let formatted_value = format_u32_with_separators(r.receiver[0].ctun_frequency as u32);
-                                            app_widgets.vfo_a_frequency.set_label(&formatted_value);
-                                        } else {
-                                            // ignore it as not a valid address for bands
+                                    TCIMessage::UpdateDDS(_, _) | TCIMessage::UpdateVFO(_, _) => {
+                                        if accepted {
+                                            update_ui(&radio_mutex_clone, &rc_app_widgets_clone2);
                                         }
                                     },
-
-                                    TCIMessage::UpdateAFGain(g) => {
-                                        let mut r = radio_mutex_clone.radio.lock().unwrap();
-                                        let mut app_widgets = rc_app_widgets_clone.borrow_mut();
-                                        let afgain = (g.clamp(-60.0, 0.0) + 60.0) / 60.0;
-                                        r.receiver[0].afgain = afgain;
-                                        r.receiver[0].set_afgain();
+                                    TCIMessage::UpdateAFGain(_) => {
+                                        let r = radio_mutex_clone.radio.lock().unwrap();
+                                        let afgain = r.receiver[0].afgain;
+                                        drop(r);
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
                                         app_widgets.afgain_adjustment.block_signal(&afgain_adjustment_id);
                                         app_widgets.afgain_adjustment.set_value((afgain * 100.0).into());
                                         app_widgets.afgain_adjustment.unblock_signal(&afgain_adjustment_id);
                                     },
+                                    TCIMessage::UpdateDrive(drive) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        // the drive adjustment callback updates the radio
+                                        app_widgets.drive_adjustment.set_value(drive.into());
+                                    },
+                                    TCIMessage::UpdateSplit(state) => {
+                                        let app_widgets = rc_app_widgets_clone2.borrow();
+                                        app_widgets.split_button.set_active(state);
+                                    },
+                                    TCIMessage::IQStart(_) | TCIMessage::UpdateSquelch(_, _) => {},
+                                    _ => update_ui(&radio_mutex_clone, &rc_app_widgets_clone2),
                                 }
                                 // Continue the polling timeout (return Continue(true))
                                 glib::ControlFlow::Continue
@@ -2223,9 +2377,7 @@ fn meter_1_update(radio_mutex: &RadioMutex,  rc_app_widgets: &Rc<RefCell<AppWidg
             r.transmitter.alc = GetTXAMeter(r.transmitter.channel,txaMeterType_TXA_ALC_AV as i32);
         }
     } else {
-        unsafe {
-            r.s_meter_dbm = GetRXAMeter(r.receiver[0].channel,rxaMeterType_RXA_S_AV as i32);
-        }
+        r.s_meter_dbm = r.receiver_level(0);
         meter.update_rx(r.s_meter_dbm, false);
         app_widgets.meter_1_display.queue_draw();
     }
@@ -2236,9 +2388,7 @@ fn meter_2_update(radio_mutex: &RadioMutex,  rc_app_widgets: &Rc<RefCell<AppWidg
     let mut meter = rc_meter.borrow_mut();
     let mut r = radio_mutex.radio.lock().unwrap();
     if !r.is_transmitting() {
-        unsafe {
-            r.s_meter_dbm = GetRXAMeter(r.receiver[1].channel,rxaMeterType_RXA_S_AV as i32);
-        }
+        r.s_meter_dbm = r.receiver_level(1);
         meter.update_rx(r.s_meter_dbm, false);
         app_widgets.meter_2_display.queue_draw();
     }
@@ -2248,32 +2398,12 @@ fn meter_tx_update(radio_mutex: &RadioMutex,  rc_app_widgets: &Rc<RefCell<AppWid
     let app_widgets = rc_app_widgets.borrow();
     let r = radio_mutex.radio.lock().unwrap();
     let is_transmitting = r.is_transmitting();
-    let forward = r.transmitter.alex_forward_power;
-    let reverse = r.transmitter.alex_reverse_power;
-    let c1 = r.transmitter.c1;
-    let c2 = r.transmitter.c2;
+    // calculate the power and SWR
+    let (fwd, swr) = r.transmitter.power_and_swr();
     let alc = r .transmitter.alc;
     let input_level = r.transmitter.input_level;
     drop(r);
 
-    // calculate the SWR
-    let fwd_power = forward as f32;
-    let rev_power = reverse as f32;
-
-    let v_fwd = (fwd_power / 4095.0) * c1;
-    let fwd = (v_fwd * v_fwd) / c2;
-
-    let v_rev = (rev_power / 4095.0) * c1;
-    let rev = (v_rev * v_rev) / c2;
-
-    let mut swr = (1.0 + (rev / fwd).sqrt())  / (1.0 - (rev / fwd).sqrt());
-    if swr < 0.0 {
-        swr = 1.0;
-    }
-    if swr.is_nan() {
-        swr = 1.0;
-    }
-
     if is_transmitting {
         let formatted_power = format!("Power: {:.1} W", fwd);
         app_widgets.tx_power.set_label(&formatted_power);
@@ -2287,6 +2417,107 @@ fn meter_tx_update(radio_mutex: &RadioMutex,  rc_app_widgets: &Rc<RefCell<AppWid
     }
 }
 
+// show the TX lockout reason and drop MOX/TUNE if tuned outside the allowed TX ranges
+fn tx_lockout_update(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>) {
+    let app_widgets = rc_app_widgets.borrow();
+    let mut r = radio_mutex.radio.lock().unwrap();
+    let reason = r.transmit_lockout().unwrap_or_default();
+    if (r.mox || r.tune || r.external_mox) && !r.check_transmit() {
+        r.mox = false;
+        r.tune = false;
+        r.external_mox = false;
+        r.transmitter.set_tuning(false, r.cw_keyer_sidetone_frequency);
+        r.updated = true;
+        r.set_state();
+        app_widgets.mox_button.set_active(false);
+        app_widgets.tun_button.set_active(false);
+        if r.split {
+            app_widgets.vfo_b_frequency.remove_css_class("vfo-tx-label");
+            app_widgets.vfo_b_frequency.add_css_class("vfo-b-label");
+        } else {
+            app_widgets.vfo_a_frequency.remove_css_class("vfo-tx-label");
+            app_widgets.vfo_a_frequency.add_css_class("vfo-a-label");
+        }
+    }
+    app_widgets.tx_lockout.set_label(&reason);
+}
+
+// show the PureSignal calibration state and feedback level while PureSignal is enabled
+fn ps_status_update(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>) {
+    let app_widgets = rc_app_widgets.borrow();
+    let mut r = radio_mutex.radio.lock().unwrap();
+    let enabled = r.transmitter.puresignal && r.transmitter.puresignal_supported();
+    app_widgets.ps_status.set_visible(enabled);
+    if !enabled {
+        return;
+    }
+    r.transmitter.update_ps_info();
+    let state = r.transmitter.ps_state();
+    let level = r.transmitter.ps_feedback_level();
+    let status = r.transmitter.ps_feedback_status();
+    let correcting = r.transmitter.ps_correcting();
+    drop(r);
+
+    let formatted_status = if correcting {
+                               format!("PS: {} FB {} Correcting", state, level)
+                           } else {
+                               format!("PS: {} FB {}", state, level)
+                           };
+    app_widgets.ps_status.set_label(&formatted_status);
+    for s in ["low", "ok", "high"] {
+        app_widgets.ps_status.remove_css_class(&format!("ps-feedback-{}", s));
+    }
+    if status != "none" {
+        app_widgets.ps_status.add_css_class(&format!("ps-feedback-{}", status));
+    }
+}
+
+// show the CW decoder panel of each receiver that has the decoder on
+fn cw_decoder_visibility(r: &Radio, app_widgets: &AppWidgets) {
+    app_widgets.cw_decoder_1_frame.set_visible(r.receiver[0].cw_decoder);
+    app_widgets.cw_decoder_2_frame.set_visible(r.rx2_enabled && r.receiver[1].cw_decoder);
+}
+
+// append newly decoded CW text to the receiver panels
+fn cw_decoder_update(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>, counts: &mut [usize; 2]) {
+    let app_widgets = rc_app_widgets.borrow();
+    for rx in 0..2 {
+        let r = radio_mutex.radio.lock().unwrap();
+        if !r.receiver[rx].cw_decoder {
+            continue;
+        }
+        let decoder = &r.receiver[rx].cw_decoder_state;
+        if decoder.count < counts[rx] {
+            // decoder was recreated
+            counts[rx] = 0;
+        }
+        let text = decoder.text_since(counts[rx]).to_string();
+        counts[rx] = decoder.count;
+        let wpm = decoder.wpm;
+        drop(r);
+
+        let (frame, view) = if rx == 0 {
+            (&app_widgets.cw_decoder_1_frame, &app_widgets.cw_decoder_1_view)
+        } else {
+            (&app_widgets.cw_decoder_2_frame, &app_widgets.cw_decoder_2_view)
+        };
+        frame.set_label(Some(&format!("CW {:.0} WPM", wpm)));
+        if text.is_empty() {
+            continue;
+        }
+        let buffer = view.buffer();
+        buffer.insert(&mut buffer.end_iter(), &text);
+        if buffer.char_count() > CW_DECODER_HISTORY as i32 {
+            let mut start = buffer.start_iter();
+            let mut end = buffer.iter_at_offset(buffer.char_count() - CW_DECODER_HISTORY as i32);
+            buffer.delete(&mut start, &mut end);
+        }
+        let mark = buffer.create_mark(None, &buffer.end_iter(), false);
+        view.scroll_mark_onscreen(&mark);
+        buffer.delete_mark(&mark);
+    }
+}
+
 fn spectrum_waterfall_clicked(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>, rx: usize, x: f64, width: i32, button: u32) -> bool {
     let mut r = radio_mutex.radio.lock().unwrap();
     if rx == 0 {
@@ -2335,15 +2566,7 @@ fn spectrum_waterfall_scroll(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCe
     let mut r = radio_mutex.radio.lock().unwrap();
     let app_widgets = rc_app_widgets.borrow();
 
-    let frequency_low = r.receiver[rx].frequency - (r.receiver[rx].sample_rate/2) as f64;
-    let frequency_high = r.receiver[rx].frequency + (r.receiver[rx].sample_rate/2) as f64;
-    let mut f1 = if r.receiver[rx].ctun {
-                     r.receiver[rx].ctun_frequency
-                  } else {
-                     r.receiver[rx].frequency
-                  };
-    f1 -= r.receiver[rx].step * dy as f64;
-    r.receiver[rx].set_frequency(f1);
+    let f1 = r.step_vfo_frequency(rx, -dy);
     let formatted_value = format_u32_with_separators(f1 as u32);
 
     if rx == 0 {
@@ -2353,6 +2576,50 @@ fn spectrum_waterfall_scroll(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCe
     }
 }
 
+fn update_vfo_label(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>, rx: usize) {
+    let r = radio_mutex.radio.lock().unwrap();
+    let app_widgets = rc_app_widgets.borrow();
+
+    let formatted_value = format_u32_with_separators(
+                              if r.receiver[rx].ctun {
+                                  r.receiver[rx].ctun_frequency as u32
+                              } else {
+                                  r.receiver[rx].frequency as u32
+                              });
+
+    if rx == 0 {
+        app_widgets.vfo_a_frequency.set_label(&formatted_value);
+    } else {
+        app_widgets.vfo_b_frequency.set_label(&formatted_value);
+    }
+}
+
+// show a MOX change from CAT, RIGCTL or TCI, or why it was refused
+fn update_remote_mox_ui(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>, accepted: bool) {
+    let r = radio_mutex.radio.lock().unwrap();
+    let app_widgets = rc_app_widgets.borrow();
+
+    if !accepted {
+        app_widgets.tx_lockout.set_label(&r.tx_lockout_reason);
+        return;
+    }
+    if r.mox {
+        if r.split {
+            app_widgets.vfo_b_frequency.remove_css_class("vfo-b-label");
+            app_widgets.vfo_b_frequency.add_css_class("vfo-tx-label");
+        } else {
+            app_widgets.vfo_a_frequency.remove_css_class("vfo-a-label");
+            app_widgets.vfo_a_frequency.add_css_class("vfo-tx-label");
+        }
+    } else if r.split {
+        app_widgets.vfo_b_frequency.remove_css_class("vfo-tx-label");
+        app_widgets.vfo_b_frequency.add_css_class("vfo-b-label");
+    } else {
+        app_widgets.vfo_a_frequency.remove_css_class("vfo-tx-label");
+        app_widgets.vfo_a_frequency.add_css_class("vfo-a-label");
+    }
+}
+
 fn update_ui(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>) {
     let r = radio_mutex.radio.lock().unwrap();
     let rx = if r.receiver[0].active { 0 } else { 1 };
@@ -2368,6 +2635,7 @@ fn update_ui(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>)
     let nb2 = r.receiver[rx].nb2;
     let anf = r.receiver[rx].anf;
     let snb = r.receiver[rx].snb;
+    let cw_decoder = r.receiver[rx].cw_decoder;
     let afgain = r.receiver[rx].afgain;
     let agc = r.receiver[rx].agc;
     let agcgain = r.receiver[rx].agcgain;
@@ -2386,10 +2654,16 @@ fn update_ui(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>)
     }
     let am_squelch_threshold = r.receiver[rx].am_squelch_threshold;
     let fm_squelch_threshold = r.receiver[rx].fm_squelch_threshold;
+    let vfo_a = if r.receiver[0].ctun { r.receiver[0].ctun_frequency } else { r.receiver[0].frequency };
+    let vfo_b = if r.receiver[1].ctun { r.receiver[1].ctun_frequency } else { r.receiver[1].frequency };
     drop(r);
 
     let mut app_widgets = rc_app_widgets.borrow_mut();
 
+    // update VFOs, memory recall and scanning retune the receivers
+    app_widgets.vfo_a_frequency.set_label(&format_u32_with_separators(vfo_a as u32));
+    app_widgets.vfo_b_frequency.set_label(&format_u32_with_separators(vfo_b as u32));
+
     // update step index
     app_widgets.step_dropdown.set_selected(step_index as u32);
 
@@ -2438,6 +2712,9 @@ fn update_ui(radio_mutex: &RadioMutex, rc_app_widgets: &Rc<RefCell<AppWidgets>>)
     // update SNB
     app_widgets.snb_button.set_active(snb);
 
+    // update CW decoder
+    app_widgets.cw_decoder_button.set_active(cw_decoder);
+
     // update AFGain
     app_widgets.afgain_adjustment.set_value((afgain * 100.0).into());
 
@@ -0,0 +1,720 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use gtk::prelude::*;
+use gtk::gio;
+use gtk::{Builder, Button, DropDown, Entry, FileDialog, Label, ListBox, ListBoxRow, Orientation, StringList, Window};
+
+use std::cell::{Cell, RefCell};
+use std::fs::{create_dir_all, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::antenna::Antenna;
+use crate::filters::{FilterGrid, Filters};
+use crate::modes::Modes;
+use crate::radio::RadioMutex;
+use crate::util::format_u32_with_separators;
+use crate::widgets::AppWidgets;
+
+// CHIRP generic CSV columns
+const CHIRP_HEADER: &str = "Location,Name,Frequency,Duplex,Offset,Tone,rToneFreq,cToneFreq,DtcsCode,DtcsPolarity,RxDtcsCode,CrossMode,Mode,TStep,Skip,Power,Comment,URCALL,RPT1CALL,RPT2CALL,DVCODE";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Memory {
+    pub name: String,
+    pub frequency: f64, // tuned frequency
+    pub mode: usize,
+    pub filter: usize,
+    pub filter_low: f64,
+    pub filter_high: f64,
+    pub ctun_offset: f64, // tuned frequency - center frequency, 0 if CTUN is off
+    pub antenna: Antenna,
+    pub split: bool,
+    pub tx_frequency: f64,
+    pub ctcss: Option<f64>,
+}
+
+impl Memory {
+
+    pub fn new(name: String, frequency: f64, mode: usize) -> Self {
+        let filter = if mode == Modes::FMN.to_usize() || mode == Modes::AM.to_usize() || mode == Modes::SAM.to_usize() {
+            Filters::F3.to_usize()
+        } else {
+            Filters::F5.to_usize()
+        };
+        let (filter_low, filter_high) = FilterGrid::filter_values(mode, filter);
+        let ctun_offset = 0.0;
+        let antenna = Antenna::NONE; // leave the band antenna unchanged
+        let split = false;
+        let tx_frequency = frequency;
+        let ctcss = None;
+
+        Memory {
+            name,
+            frequency,
+            mode,
+            filter,
+            filter_low,
+            filter_high,
+            ctun_offset,
+            antenna,
+            split,
+            tx_frequency,
+            ctcss,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryBank {
+    pub name: String,
+    pub channels: Vec<Memory>,
+}
+
+impl MemoryBank {
+
+    pub fn new(name: String) -> Self {
+        MemoryBank {
+            name,
+            channels: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Memories {
+    pub banks: Vec<MemoryBank>,
+    pub bank: usize,
+    pub channel: usize,
+}
+
+impl Default for Memories {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memories {
+
+    pub fn new() -> Self {
+        let banks = vec![MemoryBank::new("Bank 1".to_string())];
+        let bank = 0;
+        let channel = 0;
+
+        Memories {
+            banks,
+            bank,
+            channel,
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        if path.exists() {
+            match File::open(path) {
+                Ok(mut file) => {
+                    let mut s = String::new();
+                    if file.read_to_string(&mut s).is_ok() {
+                        match serde_json::from_str::<Memories>(&s) {
+                            Ok(mut memories) => {
+                                if memories.banks.is_empty() {
+                                    memories.banks.push(MemoryBank::new("Bank 1".to_string()));
+                                }
+                                if memories.bank >= memories.banks.len() {
+                                    memories.bank = 0;
+                                }
+                                return memories;
+                            }
+                            Err(e) => {
+                                eprintln!("Error reading memories {:?}: {}", path, e);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error opening memories {:?}: {}", path, e);
+                }
+            }
+        }
+        Self::new()
+    }
+
+    pub fn save(&self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                if let Err(e) = create_dir_all(parent) {
+                    eprintln!("Error creating {:?}: {}", parent, e);
+                    return;
+                }
+            }
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(s) => {
+                match File::create(path) {
+                    Ok(mut file) => {
+                        if let Err(e) = file.write_all(s.as_bytes()) {
+                            eprintln!("Error writing memories {:?}: {}", path, e);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error creating memories {:?}: {}", path, e);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Error serializing memories: {}", e);
+            }
+        }
+    }
+
+    pub fn channels(&self) -> &Vec<Memory> {
+        &self.banks[self.bank].channels
+    }
+
+    pub fn get(&self, channel: usize) -> Option<&Memory> {
+        self.banks[self.bank].channels.get(channel)
+    }
+
+    // replace a channel, or add it after the last one
+    pub fn store(&mut self, channel: usize, memory: Memory) -> usize {
+        let channels = &mut self.banks[self.bank].channels;
+        if channel < channels.len() {
+            channels[channel] = memory;
+            channel
+        } else {
+            channels.push(memory);
+            channels.len() - 1
+        }
+    }
+
+    pub fn delete(&mut self, channel: usize) {
+        let channels = &mut self.banks[self.bank].channels;
+        if channel < channels.len() {
+            channels.remove(channel);
+        }
+    }
+
+    pub fn add_bank(&mut self, name: String) {
+        self.banks.push(MemoryBank::new(name));
+        self.bank = self.banks.len() - 1;
+        self.channel = 0;
+    }
+
+    // export the current bank as CHIRP generic CSV
+    pub fn to_chirp_csv(&self) -> String {
+        let mut csv = String::from(CHIRP_HEADER);
+        csv.push_str("\r\n");
+        for (i, memory) in self.channels().iter().enumerate() {
+            let (duplex, offset) = if memory.split {
+                ("split", memory.tx_frequency)
+            } else {
+                ("", 0.0)
+            };
+            let (tone, tone_frequency) = match memory.ctcss {
+                Some(f) => ("Tone", f),
+                None => ("", 88.5),
+            };
+            let name = memory.name.replace(',', " ");
+            csv.push_str(&format!("{},{},{:.6},{},{:.6},{},{:.1},{:.1},023,NN,023,Tone->Tone,{},5.00,,,,,,,\r\n",
+                i, name, memory.frequency / 1000000.0, duplex, offset / 1000000.0, tone, tone_frequency, tone_frequency, mode_to_chirp(memory.mode)));
+        }
+        csv
+    }
+
+    // channels from a CHIRP generic CSV file, in location order
+    pub fn from_chirp_csv(csv: &str) -> Result<Vec<Memory>, String> {
+        let mut lines = csv.lines().filter(|l| !l.trim().is_empty());
+        let header = lines.next().ok_or("empty file")?;
+        let columns: Vec<String> = split_csv(header);
+        let column = |name: &str| columns.iter().position(|c| c == name);
+        let frequency_column = column("Frequency").ok_or("no Frequency column")?;
+        let location_column = column("Location");
+        let name_column = column("Name");
+        let duplex_column = column("Duplex");
+        let offset_column = column("Offset");
+        let tone_column = column("Tone");
+        let tone_frequency_column = column("rToneFreq");
+        let mode_column = column("Mode");
+
+        let mut channels: Vec<(usize, Memory)> = Vec::new();
+        for (n, line) in lines.enumerate() {
+            let fields = split_csv(line);
+            let field = |c: Option<usize>| c.and_then(|c| fields.get(c)).map(|f| f.trim()).unwrap_or("");
+            let frequency = match field(Some(frequency_column)).parse::<f64>() {
+                Ok(f) => f * 1000000.0,
+                Err(_) => return Err(format!("line {}: bad frequency", n + 2)),
+            };
+            let location = field(location_column).parse::<usize>().unwrap_or(n);
+            let mode = mode_from_chirp(field(mode_column));
+            let mut name = field(name_column).to_string();
+            if name.is_empty() {
+                name = format!("{:.6}", frequency / 1000000.0);
+            }
+            let mut memory = Memory::new(name, frequency, mode);
+            let offset = field(offset_column).parse::<f64>().unwrap_or(0.0) * 1000000.0;
+            match field(duplex_column) {
+                "+" => {
+                    memory.split = true;
+                    memory.tx_frequency = frequency + offset;
+                }
+                "-" => {
+                    memory.split = true;
+                    memory.tx_frequency = frequency - offset;
+                }
+                "split" => {
+                    memory.split = true;
+                    memory.tx_frequency = offset;
+                }
+                _ => {}
+            }
+            if matches!(field(tone_column), "Tone" | "TSQL") {
+                memory.ctcss = field(tone_frequency_column).parse::<f64>().ok();
+            }
+            channels.push((location, memory));
+        }
+        channels.sort_by_key(|(location, _)| *location);
+        Ok(channels.into_iter().map(|(_, memory)| memory).collect())
+    }
+}
+
+fn mode_to_chirp(mode: usize) -> &'static str {
+    match Modes::from_usize(mode) {
+        Some(Modes::LSB) => "LSB",
+        Some(Modes::USB) => "USB",
+        Some(Modes::CWU) => "CW",
+        Some(Modes::CWL) => "CWR",
+        Some(Modes::FMN) => "FM",
+        Some(Modes::DIGU) | Some(Modes::DIGL) => "DIG",
+        _ => "AM",
+    }
+}
+
+fn mode_from_chirp(mode: &str) -> usize {
+    let mode = match mode {
+        "LSB" => Modes::LSB,
+        "USB" => Modes::USB,
+        "CW" | "NCW" => Modes::CWU,
+        "CWR" | "NCWR" => Modes::CWL,
+        "FM" | "NFM" | "WFM" => Modes::FMN,
+        "AM" | "NAM" => Modes::AM,
+        "DIG" | "PKT" | "RTTY" | "FSK" => Modes::DIGU,
+        "RTTYR" | "FSKR" => Modes::DIGL,
+        _ => Modes::USB,
+    };
+    mode.to_usize()
+}
+
+// split a CSV line allowing for quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+pub fn create_memories_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex) -> Window {
+
+    let ui_xml = include_str!("../ui/memories.xml");
+    let builder = Builder::from_string(ui_xml);
+
+    let window: Window = builder
+            .object("memories_window")
+            .expect("Could not get object `memories_window` from builder.");
+
+    let app_widgets = rc_app_widgets.borrow();
+    window.set_transient_for(Some(&app_widgets.main_window)); // keeps it on top
+    drop(app_widgets);
+
+    let bank_dropdown: DropDown = builder
+            .object("memories_bank_dropdown")
+            .expect("Could not get object `memories_bank_dropdown` from builder.");
+    let bank_entry: Entry = builder
+            .object("memories_bank_entry")
+            .expect("Could not get object `memories_bank_entry` from builder.");
+    let new_bank_button: Button = builder
+            .object("memories_new_bank_button")
+            .expect("Could not get object `memories_new_bank_button` from builder.");
+    let memories_list: ListBox = builder
+            .object("memories_list")
+            .expect("Could not get object `memories_list` from builder.");
+    let name_entry: Entry = builder
+            .object("memories_name_entry")
+            .expect("Could not get object `memories_name_entry` from builder.");
+    let recall_button: Button = builder
+            .object("memories_recall_button")
+            .expect("Could not get object `memories_recall_button` from builder.");
+    let store_button: Button = builder
+            .object("memories_store_button")
+            .expect("Could not get object `memories_store_button` from builder.");
+    let overwrite_button: Button = builder
+            .object("memories_overwrite_button")
+            .expect("Could not get object `memories_overwrite_button` from builder.");
+    let delete_button: Button = builder
+            .object("memories_delete_button")
+            .expect("Could not get object `memories_delete_button` from builder.");
+    let import_button: Button = builder
+            .object("memories_import_button")
+            .expect("Could not get object `memories_import_button` from builder.");
+    let export_button: Button = builder
+            .object("memories_export_button")
+            .expect("Could not get object `memories_export_button` from builder.");
+    let status_label: Label = builder
+            .object("memories_status_label")
+            .expect("Could not get object `memories_status_label` from builder.");
+
+    // ignore bank selection while the dropdown is being refilled
+    let filling = Rc::new(Cell::new(false));
+
+    update_bank_dropdown(&bank_dropdown, &filling, radio_mutex);
+    update_memories_list(&memories_list, radio_mutex);
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    let filling_clone = filling.clone();
+    bank_dropdown.connect_selected_notify(move |dropdown| {
+        if filling_clone.get() {
+            return;
+        }
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        let bank = dropdown.selected() as usize;
+        if bank < r.memories.banks.len() {
+            r.memories.bank = bank;
+            r.memories.channel = 0;
+        }
+        drop(r);
+        update_memories_list(&memories_list_clone, &radio_mutex_clone);
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    let bank_dropdown_clone = bank_dropdown.clone();
+    let bank_entry_clone = bank_entry.clone();
+    let filling_clone = filling.clone();
+    new_bank_button.connect_clicked(move |_| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        let mut name = bank_entry_clone.text().to_string();
+        if name.is_empty() {
+            name = format!("Bank {}", r.memories.banks.len() + 1);
+        }
+        r.memories.add_bank(name);
+        drop(r);
+        bank_entry_clone.set_text("");
+        update_bank_dropdown(&bank_dropdown_clone, &filling_clone, &radio_mutex_clone);
+        update_memories_list(&memories_list_clone, &radio_mutex_clone);
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let name_entry_clone = name_entry.clone();
+    memories_list.connect_row_selected(move |_, row| {
+        if let Some(row) = row {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            let channel = row.index() as usize;
+            r.memories.channel = channel;
+            if let Some(memory) = r.memories.get(channel) {
+                name_entry_clone.set_text(&memory.name);
+            }
+        }
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let rc_app_widgets_clone = rc_app_widgets.clone();
+    memories_list.connect_row_activated(move |_, row| {
+        recall_memory(&rc_app_widgets_clone, &radio_mutex_clone, row.index() as usize);
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let rc_app_widgets_clone = rc_app_widgets.clone();
+    let memories_list_clone = memories_list.clone();
+    recall_button.connect_clicked(move |_| {
+        if let Some(row) = memories_list_clone.selected_row() {
+            recall_memory(&rc_app_widgets_clone, &radio_mutex_clone, row.index() as usize);
+        }
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    let name_entry_clone = name_entry.clone();
+    store_button.connect_clicked(move |_| {
+        let mut r = radio_mutex_clone.radio.lock().unwrap();
+        let rx = if r.receiver[0].active { 0 } else { 1 };
+        let channel = r.memories.channels().len();
+        let channel = r.store_memory_channel(rx, channel);
+        let name = name_entry_clone.text().to_string();
+        if !name.is_empty() {
+            let bank = r.memories.bank;
+            r.memories.banks[bank].channels[channel].name = name;
+        }
+        drop(r);
+        update_memories_list(&memories_list_clone, &radio_mutex_clone);
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    let name_entry_clone = name_entry.clone();
+    overwrite_button.connect_clicked(move |_| {
+        if let Some(row) = memories_list_clone.selected_row() {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            let rx = if r.receiver[0].active { 0 } else { 1 };
+            let channel = r.store_memory_channel(rx, row.index() as usize);
+            let name = name_entry_clone.text().to_string();
+            if !name.is_empty() {
+                let bank = r.memories.bank;
+                r.memories.banks[bank].channels[channel].name = name;
+            }
+            drop(r);
+            update_memories_list(&memories_list_clone, &radio_mutex_clone);
+        }
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    delete_button.connect_clicked(move |_| {
+        if let Some(row) = memories_list_clone.selected_row() {
+            let mut r = radio_mutex_clone.radio.lock().unwrap();
+            r.memories.delete(row.index() as usize);
+            r.memories.channel = 0;
+            drop(r);
+            update_memories_list(&memories_list_clone, &radio_mutex_clone);
+        }
+    });
+
+    // a CHIRP file is imported as a new bank
+    let radio_mutex_clone = radio_mutex.clone();
+    let memories_list_clone = memories_list.clone();
+    let bank_dropdown_clone = bank_dropdown.clone();
+    let status_label_clone = status_label.clone();
+    let filling_clone = filling.clone();
+    let window_clone = window.clone();
+    import_button.connect_clicked(move |_| {
+        let dialog = FileDialog::builder()
+            .title("Import CHIRP CSV")
+            .modal(true)
+            .build();
+        let radio_mutex_clone = radio_mutex_clone.clone();
+        let memories_list_clone = memories_list_clone.clone();
+        let bank_dropdown_clone = bank_dropdown_clone.clone();
+        let status_label_clone = status_label_clone.clone();
+        let filling_clone = filling_clone.clone();
+        dialog.open(Some(&window_clone), gio::Cancellable::NONE, move |result| {
+            if let Ok(file) = result {
+                if let Some(path) = file.path() {
+                    let csv = std::fs::read_to_string(&path).map_err(|e| e.to_string());
+                    match csv.and_then(|csv| Memories::from_chirp_csv(&csv)) {
+                        Ok(channels) => {
+                            let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("CHIRP".to_string());
+                            status_label_clone.set_label(&format!("Imported {} channels", channels.len()));
+                            let mut r = radio_mutex_clone.radio.lock().unwrap();
+                            r.memories.add_bank(name);
+                            let bank = r.memories.bank;
+                            r.memories.banks[bank].channels = channels;
+                            drop(r);
+                            update_bank_dropdown(&bank_dropdown_clone, &filling_clone, &radio_mutex_clone);
+                            update_memories_list(&memories_list_clone, &radio_mutex_clone);
+                        }
+                        Err(e) => {
+                            eprintln!("CHIRP import {:?}: {}", path, e);
+                            status_label_clone.set_label("Import failed");
+                        }
+                    }
+                }
+            }
+        });
+    });
+
+    let radio_mutex_clone = radio_mutex.clone();
+    let status_label_clone = status_label.clone();
+    let window_clone = window.clone();
+    export_button.connect_clicked(move |_| {
+        let r = radio_mutex_clone.radio.lock().unwrap();
+        let csv = r.memories.to_chirp_csv();
+        let initial_name = format!("{}.csv", r.memories.banks[r.memories.bank].name);
+        drop(r);
+        let dialog = FileDialog::builder()
+            .title("Export CHIRP CSV")
+            .modal(true)
+            .initial_name(initial_name)
+            .build();
+        let status_label_clone = status_label_clone.clone();
+        dialog.save(Some(&window_clone), gio::Cancellable::NONE, move |result| {
+            if let Ok(file) = result {
+                if let Some(path) = file.path() {
+                    match std::fs::write(&path, csv.as_bytes()) {
+                        Ok(_) => status_label_clone.set_label("Exported"),
+                        Err(e) => {
+                            eprintln!("CHIRP export {:?}: {}", path, e);
+                            status_label_clone.set_label("Export failed");
+                        }
+                    }
+                }
+            }
+        });
+    });
+
+    window
+}
+
+// recall a channel on the active receiver and show the new VFO state
+fn recall_memory(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex, channel: usize) {
+    let mut r = radio_mutex.radio.lock().unwrap();
+    let rx = if r.receiver[0].active { 0 } else { 1 };
+    let recalled = r.recall_memory_channel(rx, channel);
+    drop(r);
+    if recalled {
+        update_memory_widgets(rc_app_widgets, radio_mutex);
+    }
+}
+
+// VFO labels, CTUN and split after a memory recall
+pub fn update_memory_widgets(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex: &RadioMutex) {
+    let r = radio_mutex.radio.lock().unwrap();
+    let rx = if r.receiver[0].active { 0 } else { 1 };
+    let frequency_a = if r.receiver[0].ctun { r.receiver[0].ctun_frequency } else { r.receiver[0].frequency };
+    let frequency_b = if r.receiver[1].ctun { r.receiver[1].ctun_frequency } else { r.receiver[1].frequency };
+    let ctun = r.receiver[rx].ctun;
+    let split = r.split;
+    drop(r);
+
+    let app_widgets = rc_app_widgets.borrow();
+    app_widgets.vfo_a_frequency.set_label(&format_u32_with_separators(frequency_a as u32));
+    app_widgets.vfo_b_frequency.set_label(&format_u32_with_separators(frequency_b as u32));
+    app_widgets.ctun_button.set_active(ctun);
+    app_widgets.split_button.set_active(split);
+}
+
+fn update_bank_dropdown(bank_dropdown: &DropDown, filling: &Rc<Cell<bool>>, radio_mutex: &RadioMutex) {
+    let r = radio_mutex.radio.lock().unwrap();
+    let string_list_model = StringList::new(&[]);
+    for bank in r.memories.banks.iter() {
+        string_list_model.append(&bank.name);
+    }
+    let bank = r.memories.bank;
+    drop(r);
+    filling.set(true);
+    bank_dropdown.set_model(Some(&string_list_model));
+    bank_dropdown.set_selected(bank as u32);
+    filling.set(false);
+}
+
+fn update_memories_list(memories_list: &ListBox, radio_mutex: &RadioMutex) {
+    while let Some(child) = memories_list.first_child() {
+        memories_list.remove(&child);
+    }
+    let r = radio_mutex.radio.lock().unwrap();
+    for (i, memory) in r.memories.channels().iter().enumerate() {
+        let row = ListBoxRow::new();
+        let hbox = gtk::Box::new(Orientation::Horizontal, 10);
+        let mode = match Modes::from_usize(memory.mode) {
+            Some(mode) => format!("{:?}", mode),
+            None => "?".to_string(),
+        };
+        let mut text = format!("{:03}  {:<16} {:>12}  {}", i, memory.name, format_u32_with_separators(memory.frequency as u32), mode);
+        if memory.split {
+            text.push_str(&format!("  TX {}", format_u32_with_separators(memory.tx_frequency as u32)));
+        }
+        if let Some(ctcss) = memory.ctcss {
+            text.push_str(&format!("  CTCSS {:.1}", ctcss));
+        }
+        let label = Label::new(Some(&text));
+        label.set_xalign(0.0); // Align text to the left
+        hbox.append(&label);
+        row.set_child(Some(&hbox));
+        memories_list.append(&row);
+    }
+    let channel = r.memories.channel;
+    drop(r);
+    if let Some(row) = memories_list.row_at_index(channel as i32) {
+        memories_list.select_row(Some(&row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_csv_plain_and_quoted_fields() {
+        assert_eq!(split_csv("a,b,,c"), vec!["a", "b", "", "c"]);
+        assert_eq!(split_csv("1,\"Net, Sunday\",3"), vec!["1", "Net, Sunday", "3"]);
+        assert_eq!(split_csv("\"say \"\"hi\"\"\",x"), vec!["say \"hi\"", "x"]);
+        assert_eq!(split_csv(""), vec![""]);
+    }
+
+    #[test]
+    fn chirp_round_trip() {
+        let mut memories = Memories::new();
+        let mut repeater = Memory::new("GB3XX".to_string(), 145_600_000.0, Modes::FMN.to_usize());
+        repeater.split = true;
+        repeater.tx_frequency = 145_000_000.0;
+        repeater.ctcss = Some(77.0);
+        memories.store(0, Memory::new("40m, FT8".to_string(), 7_074_000.0, Modes::USB.to_usize()));
+        memories.store(1, repeater);
+
+        let csv = memories.to_chirp_csv();
+        assert!(csv.starts_with(CHIRP_HEADER));
+        let channels = Memories::from_chirp_csv(&csv).unwrap();
+        assert_eq!(channels.len(), 2);
+
+        assert_eq!(channels[0].name, "40m  FT8");
+        assert!((channels[0].frequency - 7_074_000.0).abs() < 1.0);
+        assert_eq!(channels[0].mode, Modes::USB.to_usize());
+        assert!(!channels[0].split);
+        assert_eq!(channels[0].ctcss, None);
+
+        assert_eq!(channels[1].mode, Modes::FMN.to_usize());
+        assert!(channels[1].split);
+        assert!((channels[1].tx_frequency - 145_000_000.0).abs() < 1.0);
+        assert_eq!(channels[1].ctcss, Some(77.0));
+    }
+
+    #[test]
+    fn chirp_import_duplex_and_location_order() {
+        let csv = "Location,Name,Frequency,Duplex,Offset,Tone,rToneFreq,Mode\r\n\
+                   5,,433.500000,-,1.600000,,88.5,NFM\r\n\
+                   2,Up,29.620000,+,0.100000,TSQL,67.0,FM\r\n";
+        let channels = Memories::from_chirp_csv(csv).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].name, "Up");
+        assert!((channels[0].tx_frequency - 29_720_000.0).abs() < 1.0);
+        assert_eq!(channels[0].ctcss, Some(67.0));
+        assert_eq!(channels[1].name, "433.500000");
+        assert!((channels[1].tx_frequency - 431_900_000.0).abs() < 1.0);
+        assert_eq!(channels[1].ctcss, None);
+    }
+
+    #[test]
+    fn chirp_import_errors() {
+        assert!(Memories::from_chirp_csv("").is_err());
+        assert!(Memories::from_chirp_csv("Location,Name\r\n0,x\r\n").is_err());
+        assert!(Memories::from_chirp_csv("Location,Frequency\r\n0,abc\r\n").is_err());
+    }
+}
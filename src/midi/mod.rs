@@ -15,19 +15,80 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 use std::error::Error;
+use std::fs::{create_dir_all, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 use std::sync::mpsc::{self};
 use serde::{Deserialize, Serialize};
 
-use midir::{Ignore, MidiInput, MidiInputPort, MidiIO};
-use crate::radio::RadioMutex;
+use midir::{MidiInput, MidiOutput, MidiOutputConnection};
+use crate::cwkeyer;
+use crate::radio::{Radio, RadioMutex};
 
+const DEBUG_MIDI: bool = false;
 
-#[derive(Clone, Debug)]
-enum MidiAction {
+// notes use entries 0..127 and controllers 128..255 of the function table
+pub const MIDI_FUNCTIONS: usize = 256;
+const MIDI_CONTROL_OFFSET: usize = 128;
+const MIDI_FEEDBACK_MS: u64 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MidiAction {
     MidiNone,
     MidiVfoA,
     MidiVfoB,
+    MidiAFGain,
+    MidiFilterWidth,
+    MidiBandUp,
+    MidiBandDown,
+    MidiMode,
+    MidiMox,
+    MidiTune,
+    MidiNR,
+    MidiNB,
+    MidiCwDot,
+    MidiCwDash,
+}
+
+impl MidiAction {
+    pub fn all() -> Vec<MidiAction> {
+        vec![
+            MidiAction::MidiNone,
+            MidiAction::MidiVfoA,
+            MidiAction::MidiVfoB,
+            MidiAction::MidiAFGain,
+            MidiAction::MidiFilterWidth,
+            MidiAction::MidiBandUp,
+            MidiAction::MidiBandDown,
+            MidiAction::MidiMode,
+            MidiAction::MidiMox,
+            MidiAction::MidiTune,
+            MidiAction::MidiNR,
+            MidiAction::MidiNB,
+            MidiAction::MidiCwDot,
+            MidiAction::MidiCwDash,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MidiAction::MidiNone => "None",
+            MidiAction::MidiVfoA => "VFO A",
+            MidiAction::MidiVfoB => "VFO B",
+            MidiAction::MidiAFGain => "AF Gain",
+            MidiAction::MidiFilterWidth => "Filter Width",
+            MidiAction::MidiBandUp => "Band Up",
+            MidiAction::MidiBandDown => "Band Down",
+            MidiAction::MidiMode => "Mode",
+            MidiAction::MidiMox => "MOX",
+            MidiAction::MidiTune => "TUNE",
+            MidiAction::MidiNR => "NR",
+            MidiAction::MidiNB => "NB",
+            MidiAction::MidiCwDot => "CW Dot/Key",
+            MidiAction::MidiCwDash => "CW Dash",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -35,6 +96,17 @@ pub enum MidiMessage {
     UpdateMox(bool),
     StepFrequencyA(i32),
     StepFrequencyB(i32),
+    UpdateAFGain(f32),
+    StepAFGain(i32),
+    UpdateFilterWidth(f64),
+    StepFilterWidth(i32),
+    BandUp,
+    BandDown,
+    NextMode,
+    ToggleMox,
+    ToggleTune,
+    ToggleNR,
+    ToggleNB,
 }
 
 impl Default for MidiMessage {
@@ -43,18 +115,43 @@ impl Default for MidiMessage {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MidiType {
     MidiUnknown,
     MidiButton, // NOTE ON/OFF
     MidiKnob,   //  Value between 0 and 127
     MidiWheel,  // direction and speed
+    MidiJog,    // direction and speed, 1 is clockwise, 127 is anticlockwise
 }
 
-#[derive(Clone, Debug)]
+impl MidiType {
+    pub fn all() -> Vec<MidiType> {
+        vec![
+            MidiType::MidiUnknown,
+            MidiType::MidiButton,
+            MidiType::MidiKnob,
+            MidiType::MidiWheel,
+            MidiType::MidiJog,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MidiType::MidiUnknown => "Unknown",
+            MidiType::MidiButton => "Button",
+            MidiType::MidiKnob => "Knob",
+            MidiType::MidiWheel => "Wheel",
+            MidiType::MidiJog => "Jog Wheel",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MidiFunction {
-    midi_type: MidiType,
-    midi_action: MidiAction,
+    pub midi_type: MidiType,
+    pub midi_action: MidiAction,
+#[serde(default)]
+    pub channel: u8, // used to send feedback to the control
 }
 
 impl Default for MidiFunction {
@@ -62,14 +159,173 @@ impl Default for MidiFunction {
         Self {
             midi_type: MidiType::MidiUnknown,
             midi_action: MidiAction::MidiNone,
+            channel: 0,
         }
     }
 }
 
+// a control pressed or moved while learning
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MidiLearned {
+    pub index: usize,
+    pub midi_type: MidiType,
+    pub channel: u8,
+}
+
+// index into the function table for a message, None if not a note or controller
+pub fn midi_index(message: &[u8]) -> Option<usize> {
+    if message.len() < 3 {
+        return None;
+    }
+    match message[0] & 0xF0 {
+        0x80 | 0x90 => Some(message[1] as usize),
+        0xB0 => Some(MIDI_CONTROL_OFFSET + message[1] as usize),
+        _ => None,
+    }
+}
+
+// human readable name for a function table entry
+pub fn midi_index_name(index: usize) -> String {
+    if index >= MIDI_CONTROL_OFFSET {
+        format!("CC {}", index - MIDI_CONTROL_OFFSET)
+    } else {
+        format!("Note {}", index)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MidiControl {
+    Note(u8),
+    Control(u8),
+}
+
+// one mapped control in a profile
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub control: MidiControl,
+    pub channel: u8,
+    pub midi_type: MidiType,
+    pub midi_action: MidiAction,
+}
+
+// a named set of mappings for a controller, matched against the port name
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MidiProfile {
+    pub name: String,
+    pub device: String,
+    pub mappings: Vec<MidiMapping>,
+}
+
+const BUILTIN_PROFILES: [&str; 2] = [
+    include_str!("profiles/hercules_djcontrol_starlight.ron"),
+    include_str!("profiles/behringer_x_touch_mini.ron"),
+];
+
+impl MidiProfile {
+
+    pub fn from_functions(name: &str, device: &str, functions: &[MidiFunction]) -> Self {
+        let mut mappings = Vec::new();
+        for (index, function) in functions.iter().enumerate() {
+            if function.midi_action == MidiAction::MidiNone {
+                continue;
+            }
+            let control = if index < MIDI_CONTROL_OFFSET {
+                MidiControl::Note(index as u8)
+            } else {
+                MidiControl::Control((index - MIDI_CONTROL_OFFSET) as u8)
+            };
+            mappings.push(MidiMapping {
+                control,
+                channel: function.channel,
+                midi_type: function.midi_type,
+                midi_action: function.midi_action,
+            });
+        }
+        MidiProfile {
+            name: name.to_string(),
+            device: device.to_string(),
+            mappings,
+        }
+    }
+
+    pub fn to_functions(&self) -> Vec<MidiFunction> {
+        let mut functions = vec![MidiFunction::default(); MIDI_FUNCTIONS];
+        for mapping in self.mappings.iter() {
+            let index = match mapping.control {
+                MidiControl::Note(note) => (note & 0x7F) as usize,
+                MidiControl::Control(control) => MIDI_CONTROL_OFFSET + (control & 0x7F) as usize,
+            };
+            functions[index] = MidiFunction {
+                midi_type: mapping.midi_type,
+                midi_action: mapping.midi_action,
+                channel: mapping.channel & 0x0F,
+            };
+        }
+        functions
+    }
+
+    // JSON if the file ends in .json, otherwise RON
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        let mut s = String::new();
+        file.read_to_string(&mut s).map_err(|e| format!("{:?}: {}", path, e))?;
+        if path.extension().map(|e| e == "json").unwrap_or(false) {
+            serde_json::from_str::<MidiProfile>(&s).map_err(|e| format!("{:?}: {}", path, e))
+        } else {
+            ron::from_str::<MidiProfile>(&s).map_err(|e| format!("{:?}: {}", path, e))
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let s = if path.extension().map(|e| e == "json").unwrap_or(false) {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        } else {
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                create_dir_all(parent).map_err(|e| format!("{:?}: {}", parent, e))?;
+            }
+        }
+        let mut file = File::create(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        file.write_all(s.as_bytes()).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    pub fn builtin() -> Vec<MidiProfile> {
+        let mut profiles = Vec::new();
+        for s in BUILTIN_PROFILES.iter() {
+            match ron::from_str::<MidiProfile>(s) {
+                Ok(profile) => profiles.push(profile),
+                Err(e) => eprintln!("MIDI builtin profile: {}", e),
+            }
+        }
+        profiles
+    }
+
+    // user profile for a port, saved when mappings are changed
+    pub fn profile_path(device: &str) -> PathBuf {
+        let name: String = device.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let app_name = env!("CARGO_PKG_NAME");
+        let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        config_dir.join(app_name).join("midi").join(format!("{}.ron", name))
+    }
+
+    // the user profile for the port, or a builtin profile whose device is part of the port name
+    pub fn find(device: &str) -> Option<MidiProfile> {
+        let path = Self::profile_path(device);
+        if path.exists() {
+            match Self::load(&path) {
+                Ok(profile) => return Some(profile),
+                Err(e) => eprintln!("MIDI profile: {}", e),
+            }
+        }
+        Self::builtin().into_iter().find(|p| !p.device.is_empty() && device.contains(&p.device))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MIDI {
     pub device: String,
-    pub functions: Vec<MidiFunction>,
 }
 
 impl MIDI {
@@ -77,13 +333,24 @@ impl MIDI {
     pub fn new(d: String) ->Self {
 eprintln!("MIDI::new {}", d);
         let device = d;
-        let functions = vec![MidiFunction::default(); 256];
         MIDI {
             device,
-            functions,
         }
     }
 
+    // names of the available MIDI input ports
+    pub fn ports() -> Vec<String> {
+        let mut names = Vec::new();
+        if let Ok(midi_in) = MidiInput::new("rustyHPSDR-ports") {
+            for p in midi_in.ports().iter() {
+                if let Ok(name) = midi_in.port_name(p) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
     pub fn run(&self, radio_mutex: &RadioMutex, tx: &mpsc::Sender<MidiMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), Box<dyn Error>> {
 
 eprintln!("MIDI::run {}", self.device);
@@ -101,7 +368,7 @@ eprintln!("MIDI::run {}", self.device);
             eprintln!("Could not find midi port {}", self.device);
         } else {
             let in_port = &in_ports[port_index];
-            let functions = self.functions.clone();
+            let radio_mutex_clone = radio_mutex.clone();
             let tx_clone = tx.clone();
             let _conn_in = midi_in.connect(
                     &in_port,
@@ -110,46 +377,60 @@ eprintln!("MIDI::run {}", self.device);
                         // message[0] is the function
                         // message[1] is the id
                         // message[2] is the value (note that for Note On/Off it is 127 for On and 0 for Off
-                        //eprintln!("{:?} {:?} {:?}", message[0], message[1], message[2]);
-                        let index = message[1] as usize;
-                        match message[0] & 0xF0 {
-                            0x80 => {
-                                        eprintln!("Note OFF {}", message[1]);
-                                        if functions[index].midi_type != MidiType::MidiUnknown {
-                                            eprintln!("{:?}", functions[index]);
-                                        } else {
-                                            eprintln!("Unknown!");
-                                        }
-                                    },
-                            0x90 => {
-                                        eprintln!("Note ON {}", message[1]);
-                                        if functions[index].midi_type != MidiType::MidiUnknown {
-                                            eprintln!("{:?}", functions[index]);
-                                        } else {
-                                            eprintln!("Unknown!");
-                                        }
-                                    },
-                            0xB0 => {
-                                        eprintln!("Control {}", message[2]);
-                                        eprintln!("scroll {}", message[2] as i32 - 64);
-                                        if functions[index].midi_type != MidiType::MidiUnknown {
-                                            eprintln!("{:?}", functions[index]);
-                                        } else {
-                                            eprintln!("Unknown!");
-                                            if tx_clone.send(MidiMessage::StepFrequencyA(message[2] as i32 - 64)).is_err() {
-                                                eprintln!("TX_cmd: Main thread receiver was dropped.");
-                                            }
-                                        }
-                                    },
-                            _ => {
-                                 },
+if DEBUG_MIDI {eprintln!("{:?}", message);}
+                        let index = match midi_index(message) {
+                            Some(index) => index,
+                            None => return,
+                        };
+                        let mut r = radio_mutex_clone.radio.lock().unwrap();
+                        if r.midi_learn {
+                            // only a press or a move is learned, not the release
+                            if message[0] & 0xF0 == 0x80 || (message[0] & 0xF0 == 0x90 && message[2] == 0) {
+                                return;
+                            }
+                            let midi_type = if index < MIDI_CONTROL_OFFSET {
+                                MidiType::MidiButton
+                            } else if r.midi_functions[index].midi_type == MidiType::MidiUnknown {
+                                MidiType::MidiKnob
+                            } else {
+                                r.midi_functions[index].midi_type
+                            };
+                            r.midi_learned = Some(MidiLearned { index, midi_type, channel: message[0] & 0x0F });
+                            r.midi_learn = false;
+                            return;
+                        }
+                        let function = r.midi_functions[index].clone();
+                        if function.midi_action == MidiAction::MidiCwDot || function.midi_action == MidiAction::MidiCwDash {
+                            // paddles go straight to the keyer, the main thread is too slow
+                            let pressed = matches!(message[0] & 0xF0, 0x90 | 0xB0) && message[2] > 0;
+                            if function.midi_action == MidiAction::MidiCwDot {
+                                r.midi_dot = pressed;
+                            } else {
+                                r.midi_dash = pressed;
+                            }
+                            cwkeyer::wake();
+                            return;
+                        }
+                        drop(r);
+                        if function.midi_action == MidiAction::MidiNone {
+if DEBUG_MIDI {eprintln!("MIDI: {} not mapped", midi_index_name(index));}
+                            return;
+                        }
+                        if let Some(msg) = Self::dispatch(&function, message) {
+                            if tx_clone.send(msg).is_err() {
+                                eprintln!("TX_cmd: Main thread receiver was dropped.");
+                            }
                         }
-                        
                     },
                     (),
                 )?;
+             let mut conn_out = Self::connect_output(&self.device);
+             let mut sent: Vec<Option<u8>> = vec![None; MIDI_FUNCTIONS];
              while !stop_flag.load(Ordering::Relaxed) {
-                std::thread::sleep(std::time::Duration::from_secs(1));
+                if let Some(conn_out) = conn_out.as_mut() {
+                    Self::feedback(conn_out, radio_mutex, &mut sent);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(MIDI_FEEDBACK_MS));
              }
         }
 
@@ -157,4 +438,136 @@ eprintln!("MIDI::run {}", self.device);
         
     }
 
+    // controllers usually name the output port the same as the input port
+    fn connect_output(device: &str) -> Option<MidiOutputConnection> {
+        let midi_out = match MidiOutput::new("rustyHPSDR") {
+            Ok(midi_out) => midi_out,
+            Err(e) => {
+                eprintln!("MIDI output: {}", e);
+                return None;
+            }
+        };
+        let client = device.split(':').next().unwrap_or(device);
+        let out_ports = midi_out.ports();
+        let mut port = out_ports.iter().find(|p| midi_out.port_name(p).map(|n| n == device).unwrap_or(false));
+        if port.is_none() {
+            port = out_ports.iter().find(|p| midi_out.port_name(p).map(|n| n.starts_with(client)).unwrap_or(false));
+        }
+        match port {
+            Some(port) => {
+                match midi_out.connect(port, "rustyHPSDR-write-output") {
+                    Ok(conn_out) => Some(conn_out),
+                    Err(e) => {
+                        eprintln!("MIDI output: {}", e);
+                        None
+                    }
+                }
+            }
+            None => {
+                eprintln!("Could not find midi output port for {}", device);
+                None
+            }
+        }
+    }
+
+    // value to show on a control for the current radio state
+    fn feedback_value(r: &Radio, function: &MidiFunction) -> Option<u8> {
+        let rx = if r.receiver[0].active { 0 } else { 1 };
+        let on = |state: bool| if state { 127 } else { 0 };
+        match (function.midi_type, function.midi_action) {
+            (MidiType::MidiButton, MidiAction::MidiMox) => Some(on(r.mox)),
+            (MidiType::MidiButton, MidiAction::MidiTune) => Some(on(r.tune)),
+            (MidiType::MidiButton, MidiAction::MidiNR) => {
+                let receiver = &r.receiver[rx];
+                Some(on(receiver.nr || receiver.nr2 || receiver.nr3 || receiver.nr4))
+            }
+            (MidiType::MidiButton, MidiAction::MidiNB) => Some(on(r.receiver[rx].nb || r.receiver[rx].nb2)),
+            (MidiType::MidiKnob, MidiAction::MidiAFGain) => Some((r.receiver[rx].afgain * 127.0).round().clamp(0.0, 127.0) as u8),
+            (MidiType::MidiKnob, MidiAction::MidiFilterWidth) => Some(((r.receiver_passband(rx) - 50.0) / 50.0).round().clamp(0.0, 127.0) as u8),
+            _ => None,
+        }
+    }
+
+    // send LED and fader updates for any mapped state that has changed
+    fn feedback(conn_out: &mut MidiOutputConnection, radio_mutex: &RadioMutex, sent: &mut [Option<u8>]) {
+        let r = radio_mutex.radio.lock().unwrap();
+        let mut messages = Vec::new();
+        for (index, function) in r.midi_functions.iter().enumerate() {
+            let value = Self::feedback_value(&r, function);
+            if value.is_some() && value != sent[index] {
+                sent[index] = value;
+                let value = value.unwrap();
+                let message = if index < MIDI_CONTROL_OFFSET {
+                    [0x90 | function.channel, index as u8, value]
+                } else {
+                    [0xB0 | function.channel, (index - MIDI_CONTROL_OFFSET) as u8, value]
+                };
+                messages.push(message);
+            }
+        }
+        drop(r);
+        for message in messages {
+if DEBUG_MIDI {eprintln!("MIDI >>> {:?}", message);}
+            if let Err(e) = conn_out.send(&message) {
+                eprintln!("MIDI output: {}", e);
+            }
+        }
+    }
+
+    // turn a mapped note or controller into a message for the main thread
+    fn dispatch(function: &MidiFunction, message: &[u8]) -> Option<MidiMessage> {
+        let value = message[2];
+        let pressed = match message[0] & 0xF0 {
+            0x90 => value > 0,
+            0xB0 => value > 0, // some controllers send buttons as CC 127/0
+            _ => false,
+        };
+        match function.midi_type {
+            MidiType::MidiButton => {
+                if !pressed {
+                    return None;
+                }
+                match function.midi_action {
+                    MidiAction::MidiBandUp => Some(MidiMessage::BandUp),
+                    MidiAction::MidiBandDown => Some(MidiMessage::BandDown),
+                    MidiAction::MidiMode => Some(MidiMessage::NextMode),
+                    MidiAction::MidiMox => Some(MidiMessage::ToggleMox),
+                    MidiAction::MidiTune => Some(MidiMessage::ToggleTune),
+                    MidiAction::MidiNR => Some(MidiMessage::ToggleNR),
+                    MidiAction::MidiNB => Some(MidiMessage::ToggleNB),
+                    _ => None,
+                }
+            }
+            MidiType::MidiKnob => {
+                match function.midi_action {
+                    MidiAction::MidiAFGain => Some(MidiMessage::UpdateAFGain(value as f32 / 127.0)),
+                    // 50Hz to 6400Hz
+                    MidiAction::MidiFilterWidth => Some(MidiMessage::UpdateFilterWidth(50.0 + (value as f64 * 50.0))),
+                    _ => None,
+                }
+            }
+            MidiType::MidiWheel | MidiType::MidiJog => {
+                let increment = if function.midi_type == MidiType::MidiJog {
+                    // 7 bit two's complement
+                    if value < 64 { value as i32 } else { value as i32 - 128 }
+                } else {
+                    value as i32 - 64
+                };
+                if increment == 0 {
+                    return None;
+                }
+                match function.midi_action {
+                    MidiAction::MidiVfoA => Some(MidiMessage::StepFrequencyA(increment)),
+                    MidiAction::MidiVfoB => Some(MidiMessage::StepFrequencyB(increment)),
+                    MidiAction::MidiAFGain => Some(MidiMessage::StepAFGain(increment)),
+                    MidiAction::MidiFilterWidth => Some(MidiMessage::StepFilterWidth(increment)),
+                    MidiAction::MidiBandUp if increment > 0 => Some(MidiMessage::BandUp),
+                    MidiAction::MidiBandDown if increment < 0 => Some(MidiMessage::BandDown),
+                    _ => None,
+                }
+            }
+            MidiType::MidiUnknown => None,
+        }
+    }
+
 }
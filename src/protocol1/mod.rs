@@ -31,10 +31,11 @@ use std::net::{UdpSocket, SocketAddr};
 
 use crate::antenna::Antenna;
 use crate::audio::Audio;
+use crate::cwkeyer;
 use crate::receiver::AudioOutput;
-use crate::discovery::Device;
+use crate::discovery::{Boards, Device};
 use crate::modes::Modes;
-use crate::radio::RadioMutex;
+use crate::radio::{Radio, RadioMutex};
 
 const OZY_BUFFER_SIZE: usize = 512;
 const METIS_BUFFER_SIZE: usize = (OZY_BUFFER_SIZE * 2) + 8;
@@ -78,7 +79,7 @@ impl Protocol1 {
         let receive_sequence: u32 = 0;
         let send_sequence: u32 = 0;
         let wide_sequence: u32 = 0;
-        let receivers: u8 = 2; 
+        let receivers: u8 = 2; // set from the radio by set_receivers
         let current_receiver: u8 = 0;
         let iq_samples: i32 = (512 - 8)/((receivers as i32 * 6) + 2);
         let n_samples: i32 = 0;
@@ -91,7 +92,7 @@ impl Protocol1 {
         let metis_buffer_offset: usize = 8;
 
         let mut rx_audio: Vec<Audio> = Vec::new();
-        for _i in 0..receivers {
+        for _i in 0..2 {
             rx_audio.push(Audio::new());
         }
         let tx_audio: Audio = Audio::new();
@@ -119,6 +120,35 @@ impl Protocol1 {
                           }
     }
 
+    // DDCs carrying the RX (coupler) and TX (DAC) PureSignal feedback while transmitting
+    fn ps_feedback_ddcs(board: Boards) -> (usize, usize) {
+        match board {
+            Boards::Angelia | Boards::Orion => (2, 3),
+            Boards::Orion2 => (3, 4),
+            _ => (0, 1),
+        }
+    }
+
+    // RX1 and RX2, followed by the PureSignal feedback DDCs only while PureSignal is enabled
+    fn ddcs(&self, r: &Radio) -> u8 {
+        if r.transmitter.puresignal && r.transmitter.puresignal_supported() {
+            let (_, tx_feedback) = Protocol1::ps_feedback_ddcs(self.device.board);
+            (tx_feedback as u8 + 1).max(2)
+        } else {
+            2
+        }
+    }
+
+    // the IQ frame layout changes with the number of DDCs, the radio follows the
+    // next C0=0x00 command so a frame or two may be misread when PureSignal is toggled
+    fn set_receivers(&mut self, receivers: u8) {
+        if receivers != self.receivers {
+            self.receivers = receivers;
+            self.iq_samples = (512 - 8)/((receivers as i32 * 6) + 2);
+            self.current_receiver = 0;
+        }
+    }
+
     pub fn run(&mut self, radio_mutex: &RadioMutex) {
 
         // setup local audio nput and output if configured
@@ -218,8 +248,12 @@ impl Protocol1 {
 
             // check for local microphone
             let mut r = radio_mutex.radio.lock().unwrap();
-            if r.transmitter.local_input && !r.transmitter.local_input_changed {
-                let (mic_buffer, count) = self.tx_audio.read_input();
+            if r.transmitter.tci_input || (r.transmitter.local_input && !r.transmitter.local_input_changed) {
+                let (mic_buffer, count) = if r.transmitter.tci_input {
+                    r.transmitter.read_tci_input()
+                } else {
+                    self.tx_audio.read_input()
+                };
                 for i in 0..count {
                     if r.transmitter.add_mic_sample(mic_buffer[i]) && r.is_transmitting() {
                         for j in 0..r.transmitter.output_samples {
@@ -235,7 +269,11 @@ impl Protocol1 {
 
                             // TX IQ samples
                             let ix = j * 2;
-                            let i_sample: i16 = (r.transmitter.iq_buffer[ix as usize] * 32767.0) as i16;
+                            let mut i_sample: i16 = (r.transmitter.iq_buffer[ix as usize] * 32767.0) as i16;
+                            if r.cw_keyer_internal && r.cw_sender.is_active() {
+                                // CWX key is the LSB of the I sample
+                                i_sample = (i_sample & !0x01) | r.cwx() as i16;
+                            }
                             let q_sample: i16 = (r.transmitter.iq_buffer[(ix+1) as usize]* 32767.0)  as i16;
                             self.ozy_buffer[self.ozy_buffer_offset] = ((i_sample >> 8) & 0xFF) as u8;
                             self.ozy_buffer_offset += 1;
@@ -383,11 +421,19 @@ impl Protocol1 {
         r.ptt = (c0 & 0x01) == 0x01;
         r.dash = (c0 & 0x02) == 0x02;
         r.dot = (c0 & 0x04) == 0x04;
+        if (r.ptt || r.dot || r.dash) && !r.check_transmit() {
+            r.ptt = false;
+            r.dash = false;
+            r.dot = false;
+        }
 
         if r.ptt != previous_ptt || r.dot != previous_dot || r.dash != previous_dash {
 eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
             r.set_state();
         }
+        if r.dot != previous_dot || r.dash != previous_dash {
+            cwkeyer::wake();
+        }
 
         let address = (c0>>3) & 0x1F;
         match address {
@@ -409,10 +455,18 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                 }
         }
 
+        let ps_feedback = r.transmitter.ps_feedback(r.is_transmitting());
+        let (rx_feedback, tx_feedback) = Protocol1::ps_feedback_ddcs(self.device.board);
+        let mut rx_feedback_i = 0.0;
+        let mut rx_feedback_q = 0.0;
+        let diversity = r.diversity_active();
+        let mut diversity_i = 0.0;
+        let mut diversity_q = 0.0;
+
         // colleact the RX IQ samples and MIC Audio Samples
         for _s in 0..self.iq_samples {
             // IQ samples for each receiver
-            for rx in 0..r.receivers {
+            for rx in 0..self.receivers {
                 let ddc = rx as usize;
                 let i_sample = if buffer[b] & 0x80 != 0 {
                                    u32::from_be_bytes([0xFF, buffer[b], buffer[b+1], buffer[b+2]]) as i32
@@ -427,6 +481,30 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                                };
                 b += 3;
 
+                if ps_feedback && ddc == rx_feedback {
+                    rx_feedback_i = i_sample as f64/8388607.0;
+                    rx_feedback_q = q_sample as f64/8388607.0;
+                    continue;
+                }
+                if ps_feedback && ddc == tx_feedback {
+                    r.transmitter.add_ps_iq_samples(i_sample as f64/8388607.0, q_sample as f64/8388607.0, rx_feedback_i, rx_feedback_q);
+                    continue;
+                }
+                if diversity && ddc == 0 {
+                    diversity_i = i_sample as f64/16777215.0;
+                    diversity_q = q_sample as f64/16777215.0;
+                    continue;
+                }
+                if diversity && ddc == 1 {
+                    if r.add_diversity_iq_samples(diversity_i, diversity_q, i_sample as f64/16777215.0, q_sample as f64/16777215.0) {
+                        process_rx_audio = true;
+                    }
+                    continue;
+                }
+                if ddc >= r.receivers as usize {
+                    continue;
+                }
+
                 let i = r.receiver[ddc].samples*2;
                 r.receiver[ddc].iq_input_buffer[i]=i_sample as f64/16777215.0;
                 r.receiver[ddc].iq_input_buffer[i+1]=q_sample as f64/16777215.0;
@@ -441,7 +519,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
             let mic_sample = (i16::from_be_bytes([buffer[b], buffer[b+1]])) as f32/ 32767.0;
             b += 2;
 
-            if !r.transmitter.local_input {
+            if !r.transmitter.local_input && !r.transmitter.tci_input {
                 // discard replicated samples
                 if mic_samples == 0 {
                     if r.transmitter.add_mic_sample(mic_sample) && r.is_transmitting() {
@@ -458,7 +536,11 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
 
                             // TX IQ samples
                             let ix = j * 2;
-                            let i_sample: i16 = (r.transmitter.iq_buffer[ix as usize] * 32767.0) as i16;
+                            let mut i_sample: i16 = (r.transmitter.iq_buffer[ix as usize] * 32767.0) as i16;
+                            if r.cw_keyer_internal && r.cw_sender.is_active() {
+                                // CWX key is the LSB of the I sample
+                                i_sample = (i_sample & !0x01) | r.cwx() as i16;
+                            }
                             let q_sample: i16 = (r.transmitter.iq_buffer[(ix+1) as usize]* 32767.0)  as i16;
                             self.ozy_buffer[self.ozy_buffer_offset] = ((i_sample >> 8) & 0xFF) as u8;
                             self.ozy_buffer_offset += 1;
@@ -495,7 +577,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                     let mut left_sample: f32 = 0.0;
                     let mut right_sample: f32 = 0.0;
                     for  rx in 0..r.receivers {
-                        if rx == 0 || (rx == 1 && r.rx2_enabled) {
+                        if rx == 0 || (rx == 1 && r.rx2_enabled && !diversity) {
                         match r.receiver[rx as usize].audio_output {
                             AudioOutput::Stereo | AudioOutput::Left => {
                                 left_sample += r.receiver[rx as usize].audio_buffer[ix] as f32;
@@ -597,15 +679,6 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
         let mut c4: u8 = 0x00;
 
         let r = radio_mutex.radio.lock().unwrap();
-        let mut frequency = r.receiver[0].frequency;
-        if r.receiver[0].ctun {
-            frequency = r.receiver[0].ctun_frequency;
-        }
-        let mut frequency_b = r.receiver[1].frequency;
-        if r.receiver[1].ctun {
-            frequency_b = r.receiver[1].ctun_frequency;
-        }
-
         let mut b = r.receiver[rx as usize].band.to_usize();
         let mut attenuation = r.receiver[rx as usize].band_info[b].attenuation;
         if r.dev == 6 {
@@ -614,6 +687,8 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
         }
 
         if self.metis_buffer_offset == 8 {
+            let receivers = self.ddcs(&r);
+            self.set_receivers(receivers);
             c0 = 0x00;
             c1 = match r.receiver[0].sample_rate {
                 48000 => {0x00},
@@ -622,7 +697,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                 384000 => {0x03},
                 _ => {00}, 
             };
-            c2 = 0x00; // TODO Class E and OC
+            c2 = r.oc_outputs() << 1; // TODO Class E
             c3 = 0x00;
             c3 |= r.receiver[0].band_info[b].attenuation as u8;
             c3 |= match r.receiver[0].band_info[b].ext_antenna {
@@ -663,13 +738,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                 1 => {
                     c0 = 0x02; // C0
                     // TX frequency
-                    let mut f = if r.split {
-                                    frequency_b as i32
-                                } else {
-                                    frequency as i32
-                                };
-                    f = f - r.receiver[0].band_info[b].lo as i32;
-                    f = f + r.receiver[0].band_info[b].lo_error as i32;
+                    let f = r.transmit_if_frequency() as i32;
                     c1 = (f >> 24) as u8; // C1
                     c2 = (f>>16) as u8; // C2
                     c3 = (f>>8) as u8; // C3
@@ -677,10 +746,15 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                      },
                 2 => {
                     c0 = 0x04 + (self.current_receiver * 2); // C0
-                    // RX frequency
-                    let mut f = r.receiver[self.current_receiver as usize].frequency as i32;
-                    f = f - r.receiver[0].band_info[b].lo as i32;
-                    f = f + r.receiver[0].band_info[b].lo_error as i32;
+                    // RX frequency, the PureSignal feedback DDCs follow the TX frequency
+                    let ddc = self.current_receiver as usize;
+                    let f = if ddc >= r.receivers as usize || r.transmitter.ps_feedback(r.is_transmitting()) {
+                                r.transmit_if_frequency() as i32
+                            } else if ddc == 1 && r.diversity_active() {
+                                r.receiver_if_frequency(0) as i32
+                            } else {
+                                r.receiver_if_frequency(ddc) as i32
+                            };
                     c1 = (f >> 24) as u8; // C1
                     c2 = (f>>16) as u8; // C2
                     c3 = (f>>8) as u8; // C3
@@ -699,7 +773,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                             b = r.receiver[1].band.to_usize();
                         }
 
-                        let power = r.transmitter.drive;
+                        let power = r.transmit_drive();
 
                         let mut target_dbm = 10.0 * ((power * 1000.0).log10());
                         let gbb = r.transmitter.pa_calibration[b];
@@ -747,6 +821,9 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                         c1 |= 0x10;
                     }
                     c2 = 0x00; // C2
+                    if r.transmitter.puresignal && r.transmitter.puresignal_supported() {
+                        c2 |= 0x40; // PureSignal
+                    }
                     c3 = 0x00; // C3
                     c4 = 0x00; // C4
                     if self.device.device == 6 { // HERMES_LITE
@@ -777,8 +854,12 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                 6 => {
                     c0 = 0x1C; // C0
                     c1 = 0x00; // C1
-                    c1 |= r.receiver[0].adc as u8;
-                    c1 |= (r.receiver[1].adc as u8) << 2;
+                    if r.diversity_active() {
+                        c1 |= 0x01 << 2; // DDC0 from ADC1 and DDC1 from ADC2 for diversity
+                    } else {
+                        c1 |= r.receiver[0].adc as u8;
+                        c1 |= (r.receiver[1].adc as u8) << 2;
+                    }
                     c2 = 0x00; // C2
                     c3 = 0x00; // C3
                     c4 = 0x00; // C4
@@ -786,7 +867,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
                 7 => {
                     c0 = 0x1E; // C0
                     c1 = 0x00; // C1
-                    if (r.transmitter.mode == Modes::CWL.to_usize() || r.transmitter.mode == Modes::CWU.to_usize()) && r.is_transmitting() {
+                    if r.cw_keyer_internal && (r.transmitter.mode == Modes::CWL.to_usize() || r.transmitter.mode == Modes::CWU.to_usize()) && r.is_transmitting() {
                         c1 |= 0x01;
                     }
                     c2 = r.cw_keyer_sidetone_volume as u8; // C2
@@ -831,7 +912,7 @@ eprintln!("ptt {} dot {} dash {}", r.ptt, r.dot, r.dash);
             }
         }
 
-        if r.is_transmitting() {
+        if r.is_transmitting() && !r.transmit_inhibited() {
             c0 |= 0x01;
         }
 
@@ -21,9 +21,10 @@ use socket2::{Socket, Domain, Type, Protocol};
 use crate::alex::*;
 use crate::antenna::Antenna;
 use crate::audio::*;
+use crate::cwkeyer;
 use crate::discovery::{Device, Boards};
 use crate::modes::Modes;
-use crate::radio::{Keyer, RadioMutex};
+use crate::radio::{Keyer, Radio, RadioMutex};
 use crate::receiver::{AudioOutput, Receiver};
 
 const HEADER_SIZE: usize  = 16;  // 16 byte header
@@ -140,6 +141,7 @@ impl Protocol2 {
 
         let mut tx_iq_buffer: Vec<f64> = vec![0.0; IQ_BUFFER_SIZE*2];
         let mut tx_iq_buffer_offset: usize = 0;
+        let mut ps_feedback = false;
 
         self.send_general();
         self.send_high_priority(radio_mutex);
@@ -161,6 +163,11 @@ impl Protocol2 {
                                     r.ptt = (buffer[4] & 0x01) == 0x01;
                                     r.dot = ((buffer[4] >> 1) & 0x01) == 0x01;
                                     r.dash = ((buffer[4] >> 2) & 0x01) == 0x01;
+                                    if (r.ptt || r.dot || r.dash) && !r.check_transmit() {
+                                        r.ptt = false;
+                                        r.dot = false;
+                                        r.dash = false;
+                                    }
 
                                     r.pll_locked = ((buffer[5] >> 2) & 0x01) == 0x01;
                                     r.transmitter.alex_forward_power = u16::from_be_bytes([buffer[14], buffer[15]]);
@@ -170,6 +177,9 @@ impl Protocol2 {
                                     if r.ptt != previous_ptt || r.dot != previous_dot || r.dash != previous_dash {
                                         r.set_state();
                                     }
+                                    if r.dot != previous_dot || r.dash != previous_dash {
+                                        cwkeyer::wake();
+                                    }
 
                                     r.received = true;
 
@@ -180,7 +190,7 @@ impl Protocol2 {
                         1026 => { // Mic/Line In Samples
                                 let mut r = radio_mutex.radio.lock().unwrap();
                                 // use samples from radio microphone if not local microphone or tuning
-                                if !r.transmitter.local_input || r.tune {
+                                if (!r.transmitter.local_input && !r.transmitter.tci_input) || r.tune {
                                     let data_size = MIC_SAMPLES * MIC_SAMPLE_SIZE;
                                     let mut b = MIC_HEADER_SIZE;
                                     if size >= MIC_HEADER_SIZE + data_size {
@@ -210,7 +220,55 @@ impl Protocol2 {
                             let ddc = (src.port()-1035) as usize;
                             let mut r = radio_mutex.radio.lock().unwrap();
 
-                            if ddc ==0 || (ddc == 1 && r.rx2_enabled) {
+                            if r.transmitter.ps_feedback(r.is_transmitting()) {
+                                // synchronized DDC0 (RX feedback) and DDC1 (TX feedback) samples
+                                if ddc == 0 {
+                                    let iq_sample_count = u16::from_be_bytes([buffer[14], buffer[15]]) as usize;
+                                    let data_size = iq_sample_count * SAMPLE_SIZE * INTERLEAVE_FACTOR * 2;
+                                    let mut b = HEADER_SIZE;
+                                    if size >= HEADER_SIZE + data_size {
+                                        for _i in 0..iq_sample_count {
+                                            let mut samples = [0.0f64; 4];
+                                            for sample in samples.iter_mut() {
+                                                let value = if buffer[b] & 0x80 != 0 {
+                                                                u32::from_be_bytes([0xFF, buffer[b], buffer[b+1], buffer[b+2]]) as i32
+                                                            } else {
+                                                                u32::from_be_bytes([0, buffer[b], buffer[b+1], buffer[b+2]]) as i32
+                                                            };
+                                                *sample = value as f64/8388607.0;
+                                                b += 3;
+                                            }
+                                            r.transmitter.add_ps_iq_samples(samples[2], samples[3], samples[0], samples[1]);
+                                        }
+                                    }
+                                }
+                                r.received = true;
+                            } else if r.diversity_active() {
+                                // synchronized DDC0 (ADC1) and DDC1 (ADC2) samples combined for RX1
+                                if ddc == 0 {
+                                    let iq_sample_count = u16::from_be_bytes([buffer[14], buffer[15]]) as usize;
+                                    let data_size = iq_sample_count * SAMPLE_SIZE * INTERLEAVE_FACTOR * 2;
+                                    let mut b = HEADER_SIZE;
+                                    if size >= HEADER_SIZE + data_size {
+                                        for _i in 0..iq_sample_count {
+                                            let mut samples = [0.0f64; 4];
+                                            for sample in samples.iter_mut() {
+                                                let value = if buffer[b] & 0x80 != 0 {
+                                                                u32::from_be_bytes([0xFF, buffer[b], buffer[b+1], buffer[b+2]]) as i32
+                                                            } else {
+                                                                u32::from_be_bytes([0, buffer[b], buffer[b+1], buffer[b+2]]) as i32
+                                                            };
+                                                *sample = value as f64/16777215.0;
+                                                b += 3;
+                                            }
+                                            if r.add_diversity_iq_samples(samples[0], samples[1], samples[2], samples[3]) {
+                                                self.process_rx_audio(&mut r, 0);
+                                            }
+                                        }
+                                    }
+                                }
+                                r.received = true;
+                            } else if ddc ==0 || (ddc == 1 && r.rx2_enabled) {
 
                             let iq_sample_count = u16::from_be_bytes([buffer[14], buffer[15]]) as usize;
                             let data_size = iq_sample_count * SAMPLE_SIZE * INTERLEAVE_FACTOR;
@@ -238,82 +296,7 @@ impl Protocol2 {
                                     if r.receiver[ddc].samples >= r.receiver[ddc].buffer_size {
                                         r.receiver[ddc].process_iq_samples();
                                         r.receiver[ddc].samples = 0;
-                                        for i in 0..r.receiver[ddc].output_samples {
-                                            let ix = i * 2;
-                                            let left_sample: f32 = (r.receiver[ddc].audio_buffer[ix] * 32767.0) as f32;
-                                            let right_sample: f32 = (r.receiver[ddc].audio_buffer[ix+1] * 32767.0) as f32;
-                                            let rox = r.receiver[ddc].remote_audio_buffer_offset;
-
-                                            // always stereo to radio
-                                            r.receiver[ddc].remote_audio_buffer[rox] = (left_sample as i16 >> 8) as u8;
-                                            r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
-                                            r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample as i16 >> 8) as u8;
-                                            r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
-                                            /*
-                                            match r.receiver[ddc].audio_output {
-                                                AudioOutput::Stereo => {
-                                                    r.receiver[ddc].remote_audio_buffer[rox] = (left_sample >> 8) as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample >> 8) as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
-                                                },
-                                                AudioOutput::Left => {
-                                                    r.receiver[ddc].remote_audio_buffer[rox] = (left_sample >> 8) as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+2] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+3] = 0;
-                                                },
-                                                AudioOutput::Right => {
-                                                    r.receiver[ddc].remote_audio_buffer[rox] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+1] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample >> 8) as u8;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
-                                                },
-                                                AudioOutput::Mute => {
-                                                    r.receiver[ddc].remote_audio_buffer[rox] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+1] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+2] = 0;
-                                                    r.receiver[ddc].remote_audio_buffer[rox+3] = 0;
-                                                },
-                                            }
-                                            */
-
-                                            r.receiver[ddc].remote_audio_buffer_offset += 4;
-                                            if r.receiver[ddc].remote_audio_buffer_offset >= r.receiver[ddc].remote_audio_buffer_size {
-                                                if r.receiver[ddc].active {
-                                                    self.send_audio(r.receiver[ddc].clone());
-                                                }
-                                                r.receiver[ddc].remote_audio_buffer_offset = 4;
-                                            }
-
-                                            if r.receiver[ddc].local_output {
-                                                let lox=r.receiver[ddc].local_audio_buffer_offset * 2;
-                                                match r.receiver[ddc].audio_output {
-                                                    AudioOutput::Stereo => {
-                                                        r.receiver[ddc].local_audio_buffer[lox]=left_sample;
-                                                        r.receiver[ddc].local_audio_buffer[lox+1]=right_sample;
-                                                    },
-                                                    AudioOutput::Left => {
-                                                        r.receiver[ddc].local_audio_buffer[lox]=left_sample;
-                                                        r.receiver[ddc].local_audio_buffer[lox+1]=0.0;
-                                                    },
-                                                    AudioOutput::Right => {
-                                                        r.receiver[ddc].local_audio_buffer[lox]=0.0;
-                                                        r.receiver[ddc].local_audio_buffer[lox+1]=right_sample;
-                                                    },
-                                                    AudioOutput::Mute => {
-                                                        r.receiver[ddc].local_audio_buffer[lox]=0.0;
-                                                        r.receiver[ddc].local_audio_buffer[lox+1]=0.0;
-                                                    },
-                                                }
-                                                r.receiver[ddc].local_audio_buffer_offset += 1;
-                                                if r.receiver[ddc].local_audio_buffer_offset == r.receiver[ddc].local_audio_buffer_size {
-                                                    r.receiver[ddc].local_audio_buffer_offset = 0;
-                                                    let buffer_clone = r.receiver[ddc].local_audio_buffer.clone();
-                                                    let _ = self.rx_audio[ddc].write_output(&buffer_clone);
-                                                }
-                                            }
-                                        }
+                                        self.process_rx_audio(&mut r, ddc);
                                     }
                                 }
                             }
@@ -329,8 +312,12 @@ impl Protocol2 {
             }
 
             let mut r = radio_mutex.radio.lock().unwrap();
-            if r.transmitter.local_input && !r.transmitter.local_input_changed && !r.tune {
-                let (mic_buffer, count) = self.tx_audio.read_input();
+            if (r.transmitter.tci_input || (r.transmitter.local_input && !r.transmitter.local_input_changed)) && !r.tune {
+                let (mic_buffer, count) = if r.transmitter.tci_input {
+                    r.transmitter.read_tci_input()
+                } else {
+                    self.tx_audio.read_input()
+                };
                 //eprintln!("mic samples {}", count);
                 for i in 0..count {
                     if r.transmitter.add_mic_sample(mic_buffer[i]) && r.is_transmitting() {
@@ -352,8 +339,14 @@ impl Protocol2 {
 
             // check for any changes we need to handle here
             let mut r = radio_mutex.radio.lock().unwrap();
-            let updated = r.updated;
+            let mut updated = r.updated;
             let keepalive = r.keepalive;
+            // the DDCs are reconfigured when PureSignal feedback starts or stops
+            let feedback = r.transmitter.ps_feedback(r.is_transmitting());
+            if feedback != ps_feedback {
+                ps_feedback = feedback;
+                updated = true;
+            }
             r.updated = false;
             r.keepalive = false;
             let local_input = r.transmitter.local_input;
@@ -477,17 +470,24 @@ impl Protocol2 {
         buf[3] = ((self.high_priority_sequence) & 0xFF) as u8;
     
         buf[4] = 0x01; // running
-        if r.is_transmitting() {
+        if r.is_transmitting() && !r.transmit_inhibited() {
             buf[4] |= 0x02;
         }
+        if r.cwx() {
+            buf[5] |= 0x01; // CWX
+        }
     
-        // receiver frequency
+        // receiver frequency, DDC0 and DDC1 follow the TX frequency for PureSignal feedback
+        let ps_feedback = r.transmitter.ps_feedback(r.is_transmitting());
         for i in 0..r.receivers {
             // convert frequency to phase
-            let mut f = r.receiver[i as usize].frequency;
-            let b = r.receiver[i as usize].band.to_usize();
-            f = f - r.receiver[i as usize].band_info[b].lo;
-            f = f - r.receiver[i as usize].band_info[b].lo_error;
+            let f = if ps_feedback {
+                        r.transmit_if_frequency()
+                    } else if i == 1 && r.diversity_active() {
+                        r.receiver_if_frequency(0)
+                    } else {
+                        r.receiver_if_frequency(i as usize)
+                    };
 
             let phase = ((4294967296.0*f)/122880000.0) as u32;
             buf[(9+(i*4)) as usize] = ((phase>>24) & 0xFF) as u8;
@@ -497,25 +497,7 @@ impl Protocol2 {
         }
 
         // transmit frequency
-        let f = if r.split {
-                    let mut f = r.receiver[1].frequency;
-                    if r.receiver[1].ctun {
-                        f = r.receiver[1].ctun_frequency;
-                    }
-                    let b = r.receiver[1].band.to_usize();
-                    f = f - r.receiver[1].band_info[b].lo;
-                    f = f - r.receiver[1].band_info[b].lo_error;
-                    f
-                } else {
-                    let mut f = r.receiver[0].frequency;
-                    if r.receiver[0].ctun {
-                        f = r.receiver[0].ctun_frequency;
-                    }
-                    let b = r.receiver[0].band.to_usize();
-                    f = f - r.receiver[0].band_info[b].lo;
-                    f = f - r.receiver[0].band_info[b].lo_error;
-                    f
-                };
+        let f = r.transmit_if_frequency();
         let phase = ((4294967296.0*f)/122880000.0) as u32;
         buf[329] = ((phase>>24) & 0xFF) as u8;
         buf[330] = ((phase>>16) & 0xFF) as u8;
@@ -523,8 +505,8 @@ impl Protocol2 {
         buf[332] = (phase & 0xFF) as u8;
 
         // transmit power
-        let power = if r.is_transmitting() {
-                            let mut p = r.transmitter.drive * 255.0 / 100.0;
+        let power = if r.is_transmitting() && !r.transmit_inhibited() {
+                            let mut p = r.transmit_drive() * 255.0 / 100.0;
                             if p > 255.0 {
                                 p = 255.0;
                             }
@@ -569,7 +551,7 @@ impl Protocol2 {
         }
 
         // set BPF
-        let mut f = r.receiver[0].frequency;
+        let mut f = r.receiver_if_frequency(0);
         if f < 1500000.0 {
             filter1 |= HPF_BYPASS;
         } else if f < 2100000.0 {
@@ -612,7 +594,7 @@ impl Protocol2 {
         buf[1435]=(filter1 & 0xFF) as u8;
  
         let mut filter2: u32 = 0x00000000;
-        f = r.receiver[1].frequency;
+        f = r.receiver_if_frequency(1);
         if self.device.board == Boards::Orion2 {
             if f < 1500000.0 {
                 filter2 |= HPF_BYPASS; // BYPASS
@@ -645,6 +627,9 @@ impl Protocol2 {
             filter2 |= 0x08;
         }
 
+        // open collector outputs
+        buf[1401] = r.oc_outputs() << 1;
+
         buf[1430] = ((filter2>>8)&0xFF) as u8;
         buf[1431] = (filter2&0xFF) as u8;
 
@@ -668,6 +653,86 @@ impl Protocol2 {
         self.high_priority_sequence += 1;
     }
 
+    // send the demodulated audio of a receiver to the radio and the local output
+    fn process_rx_audio(&mut self, r: &mut Radio, ddc: usize) {
+        for i in 0..r.receiver[ddc].output_samples {
+            let ix = i * 2;
+            let left_sample: f32 = (r.receiver[ddc].audio_buffer[ix] * 32767.0) as f32;
+            let right_sample: f32 = (r.receiver[ddc].audio_buffer[ix+1] * 32767.0) as f32;
+            let rox = r.receiver[ddc].remote_audio_buffer_offset;
+
+            // always stereo to radio
+            r.receiver[ddc].remote_audio_buffer[rox] = (left_sample as i16 >> 8) as u8;
+            r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
+            r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample as i16 >> 8) as u8;
+            r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
+            /*
+            match r.receiver[ddc].audio_output {
+                AudioOutput::Stereo => {
+                    r.receiver[ddc].remote_audio_buffer[rox] = (left_sample >> 8) as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample >> 8) as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
+                },
+                AudioOutput::Left => {
+                    r.receiver[ddc].remote_audio_buffer[rox] = (left_sample >> 8) as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+1] = left_sample as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+2] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+3] = 0;
+                },
+                AudioOutput::Right => {
+                    r.receiver[ddc].remote_audio_buffer[rox] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+1] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+2] = (right_sample >> 8) as u8;
+                    r.receiver[ddc].remote_audio_buffer[rox+3] = right_sample as u8;
+                },
+                AudioOutput::Mute => {
+                    r.receiver[ddc].remote_audio_buffer[rox] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+1] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+2] = 0;
+                    r.receiver[ddc].remote_audio_buffer[rox+3] = 0;
+                },
+            }
+            */
+
+            r.receiver[ddc].remote_audio_buffer_offset += 4;
+            if r.receiver[ddc].remote_audio_buffer_offset >= r.receiver[ddc].remote_audio_buffer_size {
+                if r.receiver[ddc].active {
+                    self.send_audio(r.receiver[ddc].clone());
+                }
+                r.receiver[ddc].remote_audio_buffer_offset = 4;
+            }
+
+            if r.receiver[ddc].local_output {
+                let lox=r.receiver[ddc].local_audio_buffer_offset * 2;
+                match r.receiver[ddc].audio_output {
+                    AudioOutput::Stereo => {
+                        r.receiver[ddc].local_audio_buffer[lox]=left_sample;
+                        r.receiver[ddc].local_audio_buffer[lox+1]=right_sample;
+                    },
+                    AudioOutput::Left => {
+                        r.receiver[ddc].local_audio_buffer[lox]=left_sample;
+                        r.receiver[ddc].local_audio_buffer[lox+1]=0.0;
+                    },
+                    AudioOutput::Right => {
+                        r.receiver[ddc].local_audio_buffer[lox]=0.0;
+                        r.receiver[ddc].local_audio_buffer[lox+1]=right_sample;
+                    },
+                    AudioOutput::Mute => {
+                        r.receiver[ddc].local_audio_buffer[lox]=0.0;
+                        r.receiver[ddc].local_audio_buffer[lox+1]=0.0;
+                    },
+                }
+                r.receiver[ddc].local_audio_buffer_offset += 1;
+                if r.receiver[ddc].local_audio_buffer_offset == r.receiver[ddc].local_audio_buffer_size {
+                    r.receiver[ddc].local_audio_buffer_offset = 0;
+                    let buffer_clone = r.receiver[ddc].local_audio_buffer.clone();
+                    let _ = self.rx_audio[ddc].write_output(&buffer_clone);
+                }
+            }
+        }
+    }
+
     pub fn send_audio(&mut self, mut rx: Receiver) {
         // port 1028
         rx.remote_audio_buffer[0] = ((self.audio_sequence >> 24) & 0xFF) as u8;
@@ -703,6 +768,24 @@ impl Protocol2 {
           buf[(22+(i*6)) as usize] = 24;  // 24 bits per sample
         }
 
+        if r.diversity_active() {
+            // diversity: DDC0 from ADC0 and DDC1 from ADC1 at the RX1 sample rate
+            buf[17] = 0;
+            buf[23] = 1;
+            buf[24] = buf[18];
+            buf[25] = buf[19];
+            buf[1363] = 0x02; // DDC1 synchronized with DDC0
+        } else if r.transmitter.ps_feedback(r.is_transmitting()) {
+            // PureSignal: DDC0 is the RX feedback from ADC0, DDC1 the TX feedback from the DAC
+            buf[17] = 0;
+            buf[23] = r.adc.len() as u8;
+            for i in 0..2 {
+                buf[18+(i*6)] = 0;
+                buf[19+(i*6)] = 192; // 192 kHz
+            }
+            buf[1363] = 0x02; // DDC1 synchronized with DDC0
+        }
+
         self.device.address.set_port(1025);
         //println!("send_receive_specific: 1025");
         self.socket.send_to(&buf, self.device.address).expect("couldn't send data");
@@ -729,7 +812,7 @@ impl Protocol2 {
             buf[5] = 0x00;
         }
 
-        if tx.mode == Modes::CWL.to_usize()  || tx.mode == Modes::CWU.to_usize() {
+        if r.cw_keyer_internal && (tx.mode == Modes::CWL.to_usize()  || tx.mode == Modes::CWU.to_usize()) {
             buf[5] |= 0x02;
         }
         if r.cw_keys_reversed {
@@ -34,9 +34,22 @@ use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 
 
+use crate::antenna::Antenna;
+use crate::bandplan::BandPlan;
+use crate::bands::Bands;
+use crate::cat::CatMessage;
+use crate::cwkeyer::{CwKeyer, CwKeyerSettings};
+use crate::cwsender::{CwSender, CW_MACROS, expand_macro};
 use crate::discovery::{Boards,Device};
+use crate::filters::{FilterGrid,Filters};
+use crate::memories::{Memories, Memory};
+use crate::midi::{MidiFunction, MidiLearned, MidiMessage, MIDI_FUNCTIONS};
 use crate::modes::Modes;
 use crate::receiver::Receiver;
+use crate::rigctl::RIGCTLMessage;
+use crate::scanner::{ScanAction, ScanMode, Scanner};
+use crate::diversity::Diversity;
+use crate::tci::{TCIDataMessage, TCIMessage};
 use crate::transmitter::Transmitter;
 use crate::wdsp::*;
 use crate::audio::*;
@@ -165,6 +178,10 @@ pub struct Radio {
     pub dot: bool,
 #[serde(skip_serializing, skip_deserializing)]
     pub dash: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub midi_dot: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub midi_dash: bool,
 #[serde(skip_serializing, skip_deserializing)]
     pub external_mox: bool,
     pub audio: Vec<Audio>,
@@ -238,9 +255,113 @@ pub struct Radio {
     pub rigctl_enabled: bool,
     pub midi_enabled: bool,
     pub tci_enabled: bool,
+#[serde(default)]
+    pub cat_pty_enabled: bool,
+#[serde(default = "default_cat_pty_path")]
+    pub cat_pty_path: String,
+#[serde(default = "default_cat_max_clients")]
+    pub cat_max_clients: usize,
+#[serde(default = "default_cat_address")]
+    pub cat_address: String,
+#[serde(default = "default_rigctl_address")]
+    pub rigctl_address: String,
+#[serde(default = "default_tci_address")]
+    pub tci_address: String,
+#[serde(default)]
+    pub midi_device: String,
+#[serde(default = "default_midi_functions")]
+    pub midi_functions: Vec<MidiFunction>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub midi_learn: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub midi_learned: Option<MidiLearned>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub memories: Memories, // saved in memories.json
+#[serde(default)]
+    pub scanner: Scanner,
+#[serde(default = "default_band_plan_name")]
+    pub band_plan_name: String,
+#[serde(skip_serializing, skip_deserializing)]
+    pub band_plan: BandPlan,
+#[serde(default)]
+    pub tx_lockout: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub tx_lockout_reason: String,
+#[serde(default = "default_cw_macros")]
+    pub cw_macros: Vec<String>,
+#[serde(default)]
+    pub cw_my_call: String,
+#[serde(skip_serializing, skip_deserializing)]
+    pub cw_call: String,
+#[serde(default = "default_cw_rst")]
+    pub cw_rst: String,
+#[serde(default = "default_cw_serial")]
+    pub cw_serial: u32,
+#[serde(skip_serializing, skip_deserializing)]
+    pub cw_sender: CwSender,
+#[serde(skip_serializing, skip_deserializing)]
+    pub cw_keyer: CwKeyer,
+#[serde(default)]
+    pub diversity: Diversity,
+
+}
+
+// defaults for settings missing from a configuration saved by an older version
+fn default_cat_pty_path() -> String {
+    String::from("/tmp/rustyHPSDR-cat")
+}
+
+fn default_cat_max_clients() -> usize {
+    4
+}
+
+// server addresses used by headless mode
+fn default_cat_address() -> String {
+    String::from("127.0.0.1:19001")
+}
+
+fn default_rigctl_address() -> String {
+    String::from("127.0.0.1:4532")
+}
 
+fn default_tci_address() -> String {
+    String::from("0.0.0.0:50001")
 }
 
+fn default_midi_functions() -> Vec<MidiFunction> {
+    vec![MidiFunction::default(); MIDI_FUNCTIONS]
+}
+
+fn default_band_plan_name() -> String {
+    String::from("IARU Region 1")
+}
+
+fn default_cw_macros() -> Vec<String> {
+    let mut cw_macros = vec![
+        String::from("CQ CQ CQ DE {MYCALL} {MYCALL} K"),
+        String::from("{CALL} DE {MYCALL}"),
+        String::from("{CALL} {RST} {RST}"),
+        String::from("{CALL} {RST} {NR}"),
+        String::from("TU {MYCALL}"),
+        String::from("AGN?"),
+        String::from("QRZ?"),
+        String::from("73"),
+    ];
+    cw_macros.resize(CW_MACROS, String::new());
+    cw_macros
+}
+
+fn default_cw_rst() -> String {
+    String::from("599")
+}
+
+fn default_cw_serial() -> u32 {
+    1
+}
+
+// highest frequency the radio can transmit, half the 122.88MHz clock
+const MAX_TX_IF_FREQUENCY: f64 = 61440000.0;
+
 #[derive(Clone)]
 pub struct RadioMutex {
     pub radio: Arc<Mutex<Radio>>,
@@ -310,10 +431,6 @@ impl Radio {
         for i in 0..receivers {
             receiver.push(Receiver::new(i, device.protocol, spectrum_width));
         }
-// Pure Signal
-        //receivers = receivers + 2;
-        //receiver.push(Receiver::new(receivers, device.protocol, 0));  // TX Feedback
-        //receiver.push(Receiver::new(receivers+1, device.protocol, 0)); // RX Feedback
 
         let s_meter_dbm = -121.0;
         let ptt = false;
@@ -322,12 +439,15 @@ impl Radio {
         let tune = false;
         let dot = false;
         let dash = false;
+        let midi_dot = false;
+        let midi_dash = false;
         let external_mox = false;
         let mut audio: Vec<Audio> = Vec::new();
         for _i in 0..receivers {
             audio.push(Audio::new());
         }
-        let transmitter = Transmitter::new(8, device.protocol, device.board);
+        let mut transmitter = Transmitter::new(8, device.protocol, device.board);
+        transmitter.ps_feedback_rate = sample_rate;
         let filter_board = match device.board {
             Boards::HermesLite => FilterBoards::N2ADR,
             Boards::HermesLite2 => FilterBoards::N2ADR,
@@ -395,6 +515,30 @@ impl Radio {
         let rigctl_enabled = false;
         let midi_enabled = false;
         let tci_enabled = false;
+        let cat_pty_enabled = false;
+        let cat_pty_path = default_cat_pty_path();
+        let cat_max_clients = default_cat_max_clients();
+        let cat_address = default_cat_address();
+        let rigctl_address = default_rigctl_address();
+        let tci_address = default_tci_address();
+        let midi_device = String::from("");
+        let midi_functions = default_midi_functions();
+        let midi_learn = false;
+        let midi_learned = None;
+        let memories = Memories::new();
+        let scanner = Scanner::new();
+        let diversity = Diversity::new();
+        let band_plan_name = default_band_plan_name();
+        let band_plan = BandPlan::default();
+        let tx_lockout = false;
+        let tx_lockout_reason = String::new();
+        let cw_macros = default_cw_macros();
+        let cw_my_call = String::new();
+        let cw_call = String::new();
+        let cw_rst = default_cw_rst();
+        let cw_serial = default_cw_serial();
+        let cw_sender = CwSender::new();
+        let cw_keyer = CwKeyer::new();
 
         Radio {
             name,
@@ -419,6 +563,8 @@ impl Radio {
             tune,
             dot,
             dash,
+            midi_dot,
+            midi_dash,
             external_mox,
             audio,
             transmitter,
@@ -476,6 +622,30 @@ impl Radio {
             rigctl_enabled,
             midi_enabled,
             tci_enabled,
+            cat_pty_enabled,
+            cat_pty_path,
+            cat_max_clients,
+            cat_address,
+            rigctl_address,
+            tci_address,
+            midi_device,
+            midi_functions,
+            midi_learn,
+            midi_learned,
+            memories,
+            scanner,
+            band_plan_name,
+            band_plan,
+            tx_lockout,
+            tx_lockout_reason,
+            cw_macros,
+            cw_my_call,
+            cw_call,
+            cw_rst,
+            cw_serial,
+            cw_sender,
+            cw_keyer,
+            diversity,
         }
     }
 
@@ -487,7 +657,15 @@ impl Radio {
         self.tune = false;
         self.dot = false;
         self.dash = false;
+        self.midi_dot = false;
+        self.midi_dash = false;
         self.external_mox = false;
+        self.cw_sender = CwSender::new();
+        self.cw_keyer = CwKeyer::new();
+        // lists saved by an older version may be shorter
+        self.midi_functions.resize(MIDI_FUNCTIONS, MidiFunction::default());
+        self.cw_macros.resize(CW_MACROS, String::new());
+        self.transmitter.ps_feedback_rate = self.sample_rate;
         self.updated = false;
 
         self.pll_locked = false;
@@ -498,9 +676,10 @@ impl Radio {
     }
 
     pub fn is_transmitting(&self) -> bool {
-        let cw = (self.dot | self.dash) && (self.receiver[0].mode == Modes::CWL.to_usize() || self.receiver[0].mode == Modes::CWU.to_usize());
+        // the software keyer raises its own PTT with hang time
+        let cw = (self.dot | self.dash) && self.cw_keyer_internal && (self.receiver[0].mode == Modes::CWL.to_usize() || self.receiver[0].mode == Modes::CWU.to_usize());
 
-        self.mox | self.ptt | cw | self.vox | self.tune | self.external_mox
+        self.mox | self.ptt | cw | self.vox | self.tune | self.external_mox | self.cw_sender.ptt | self.cw_keyer.ptt
     }
 
     pub fn run(&self) {
@@ -535,6 +714,9 @@ impl Radio {
                 GetPixels(channel, 0, pixels.as_mut_ptr(), &mut flag);
             }
         }
+        if !self.is_transmitting() {
+            self.remove_gain_offset(0, &mut pixels);
+        }
         (flag, pixels)
     }
     
@@ -549,6 +731,7 @@ impl Radio {
                 GetPixels(channel, 0, pixels.as_mut_ptr(), &mut flag);
             }
         }
+        self.remove_gain_offset(1, &mut pixels);
         (flag, pixels)
     }
     
@@ -572,6 +755,9 @@ impl Radio {
                 GetPixels(channel, 1, pixels.as_mut_ptr(), &mut flag);
             }
         }
+        if !self.is_transmitting() {
+            self.remove_gain_offset(self.active_receiver, &mut pixels);
+        }
         (flag, pixels)
     }
 
@@ -588,9 +774,53 @@ impl Radio {
                 GetPixels(channel, 1, pixels.as_mut_ptr(), &mut flag);
             }
         }
+        self.remove_gain_offset(1, &mut pixels);
         (flag, pixels)
     }
 
+    // transverter gain in front of the receiver
+    pub fn receiver_gain_offset(&self, rx: usize) -> f32 {
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        if info.is_xvtr() { info.xvtr_rx_gain } else { 0.0 }
+    }
+
+    fn remove_gain_offset(&self, rx: usize, pixels: &mut [f32]) {
+        let offset = self.receiver_gain_offset(rx);
+        if offset != 0.0 {
+            for pixel in pixels.iter_mut() {
+                *pixel -= offset;
+            }
+        }
+    }
+
+    // signal level in dBm at the antenna, corrected for any transverter gain
+    pub fn receiver_level(&self, rx: usize) -> f64 {
+        let dbm = unsafe { GetRXAMeter(self.receiver[rx].channel, rxaMeterType_RXA_S_AV as i32) };
+        dbm - self.receiver_gain_offset(rx) as f64
+    }
+
+    // RX1 is the combination of DDC0 (ADC1) and DDC1 (ADC2), RX2 is not available
+    pub fn diversity_active(&self) -> bool {
+        self.diversity.enabled && self.adc.len() > 1 && !self.transmitter.ps_feedback(self.is_transmitting())
+    }
+
+    // sample pair from the two ADCs, RX1 is processed when the combiner has a buffer
+    pub fn add_diversity_iq_samples(&mut self, i0: f64, q0: f64, i1: f64, q1: f64) -> bool {
+        let buffer_size = self.receiver[0].buffer_size;
+        if self.diversity.buffer_size != buffer_size {
+            // the combined buffer must fill exactly one receiver buffer
+            self.diversity.init(buffer_size);
+        }
+        if !self.diversity.add_iq_samples(i0, q0, i1, q1) {
+            return false;
+        }
+        let size = (buffer_size * 2).min(self.receiver[0].iq_input_buffer.len()).min(self.diversity.output.len());
+        self.receiver[0].iq_input_buffer[..size].copy_from_slice(&self.diversity.output[..size]);
+        self.receiver[0].process_iq_samples();
+        true
+    }
+
     pub fn set_state(&self) {
         if self.is_transmitting() {
             unsafe {
@@ -602,7 +832,9 @@ impl Radio {
                 }
                 SetChannelState(self.transmitter.channel, 1, 0);
             }
+            self.transmitter.set_ps_mox(true);
         } else {
+            self.transmitter.set_ps_mox(false);
             unsafe {
                 SetChannelState(self.transmitter.channel, 0, 1);
                 SetChannelState(self.receiver[0].channel, 1, 0);
@@ -620,9 +852,14 @@ impl Radio {
         config_dir.join(app_name).join(d).join("radio.json")
     }
 
+    // memories are kept next to radio.json so they survive a reset of the radio settings
+    fn memories_file_path(device: Device) -> PathBuf {
+        Self::config_file_path(device).with_file_name("memories.json")
+    }
+
     pub fn load(device: Device, spectrum_width: i32) -> Self {
         let path = Self::config_file_path(device);
-        if path.exists() {
+        let mut radio = if path.exists() {
             match File::open(&path) {
                 Ok(mut file) => {
                     let mut s = String::new();
@@ -644,10 +881,17 @@ impl Radio {
             }
         } else {
             Self::new(device, spectrum_width)
+        };
+        radio.memories = Memories::load(&Self::memories_file_path(device));
+        let band_plan_name = radio.band_plan_name.clone();
+        if let Err(e) = radio.set_band_plan(&band_plan_name) {
+            eprintln!("Band plan: {}", e);
         }
+        radio
     }
 
     pub fn save(&self, device: Device) {
+        self.memories.save(&Self::memories_file_path(device));
         let path = Self::config_file_path(device);
         if let Some(parent) = path.parent() {
             if !parent.exists() {
@@ -698,9 +942,920 @@ impl Radio {
         for i in 0..self.receivers {
             self.receiver[i as usize].sample_rate_changed(rate);
         }
+        self.transmitter.set_ps_feedback_rate(rate);
         self.sample_rate_changed = true;
     }
 
+    // set the mode of a receiver (and the transmitter) using its current filter
+    pub fn set_receiver_mode(&mut self, rx: usize, mode: usize) {
+        self.receiver[rx].mode = mode;
+        let (mut low, mut high) = FilterGrid::filter_values(mode, self.receiver[rx].filter);
+        if mode == Modes::CWL.to_usize() {
+            low += -self.receiver[rx].cw_pitch;
+            high += -self.receiver[rx].cw_pitch;
+        } else if mode == Modes::CWU.to_usize() {
+            low += self.receiver[rx].cw_pitch;
+            high += self.receiver[rx].cw_pitch;
+        }
+        self.receiver[rx].filter_low = low;
+        self.receiver[rx].filter_high = high;
+        self.receiver[rx].set_mode();
+        self.transmitter.mode = mode;
+        self.transmitter.set_mode();
+        self.transmitter.filter_low = low;
+        self.transmitter.filter_high = high;
+        self.transmitter.set_filter();
+    }
+
+    // set a receiver passband width in Hz using the Var1 filter
+    pub fn set_receiver_passband(&mut self, rx: usize, passband: f64) {
+        let mode = self.receiver[rx].mode;
+        let cw_pitch = self.receiver[rx].cw_pitch;
+        let (low, high) = match Modes::from_usize(mode) {
+            Some(Modes::LSB) | Some(Modes::DIGL) => (-150.0 - passband, -150.0),
+            Some(Modes::USB) | Some(Modes::DIGU) => (150.0, 150.0 + passband),
+            Some(Modes::CWL) => (-cw_pitch - (passband / 2.0), -cw_pitch + (passband / 2.0)),
+            Some(Modes::CWU) => (cw_pitch - (passband / 2.0), cw_pitch + (passband / 2.0)),
+            _ => (-passband / 2.0, passband / 2.0),
+        };
+        self.set_receiver_filter_edges(rx, low, high);
+    }
+
+    // set the receiver filter edges in Hz using the Var1 filter
+    pub fn set_receiver_filter_edges(&mut self, rx: usize, low: f64, high: f64) {
+        self.receiver[rx].filter = Filters::FVar1.to_usize();
+        self.receiver[rx].filter_low = low;
+        self.receiver[rx].filter_high = high;
+        self.receiver[rx].set_filter();
+        self.transmitter.filter_low = low;
+        self.transmitter.filter_high = high;
+        self.transmitter.set_filter();
+    }
+
+    // change band the same way as the band buttons in the VFO dialog
+    pub fn set_receiver_band(&mut self, rx: usize, index: usize) {
+        let b = self.receiver[rx].band.to_usize();
+        if b == index || index >= self.receiver[rx].band_info.len() {
+            return;
+        }
+        self.select_receiver_band(rx, index);
+    }
+
+    // band button, selecting the current band again moves to its next band stacking register
+    pub fn select_receiver_band(&mut self, rx: usize, index: usize) {
+        if index >= self.receiver[rx].band_info.len() {
+            return;
+        }
+        let b = self.receiver[rx].band.to_usize();
+        // save current band info
+        self.receiver[rx].band_info[b].current = self.receiver[rx].frequency;
+        self.receiver[rx].band_info[b].ctun = self.receiver[rx].ctun_frequency;
+        self.receiver[rx].band_info[b].mode = Modes::from_usize(self.receiver[rx].mode).expect("Invalid mode");
+        self.receiver[rx].band_info[b].filter = Filters::from_usize(self.receiver[rx].filter).expect("Invalid Filter");
+        self.receiver[rx].band_info[b].store_stack();
+        if b == index {
+            self.receiver[rx].band_info[index].next_stack();
+        }
+
+        // get new band info
+        self.receiver[rx].band = Bands::from_usize(index).expect("invalid band index");
+        self.receiver[rx].frequency = self.receiver[rx].band_info[index].current;
+        self.receiver[rx].ctun_frequency = self.receiver[rx].band_info[index].ctun;
+        if self.receiver[rx].ctun {
+            self.receiver[rx].set_ctun_frequency();
+        }
+        if !self.receiver[rx].filters_manual {
+            self.receiver[rx].filters = self.receiver[rx].band_info[index].filters;
+        }
+        self.receiver[rx].filter = self.receiver[rx].band_info[index].filter.to_usize();
+        let mode = self.receiver[rx].band_info[index].mode.to_usize();
+        self.set_receiver_mode(rx, mode);
+        unsafe {
+            RXANBPSetTuneFrequency(self.receiver[rx].channel, self.receiver[rx].frequency);
+        }
+    }
+
+    // NB on/off from a remote control, turning off clears NB2 as well
+    pub fn set_receiver_nb(&mut self, rx: usize, state: bool) {
+        self.receiver[rx].nb = state;
+        self.receiver[rx].set_nb();
+        if !state && self.receiver[rx].nb2 {
+            self.receiver[rx].nb2 = false;
+            self.receiver[rx].set_nb2();
+        }
+    }
+
+    // NR on/off from a remote control, turning off clears NR2/NR3/NR4 as well
+    pub fn set_receiver_nr(&mut self, rx: usize, state: bool) {
+        self.receiver[rx].nr = state;
+        self.receiver[rx].set_nr();
+        if !state {
+            self.receiver[rx].nr2 = false;
+            self.receiver[rx].set_nr2();
+            self.receiver[rx].nr3 = false;
+            self.receiver[rx].set_nr3();
+            self.receiver[rx].nr4 = false;
+            self.receiver[rx].set_nr4();
+        }
+    }
+
+    // band limits, segments and the band grid come from the band plan,
+    // the default plan is used if the plan cannot be loaded
+    pub fn set_band_plan(&mut self, name: &str) -> Result<(), String> {
+        let result = BandPlan::find(name);
+        self.band_plan = match &result {
+            Ok(plan) => plan.clone(),
+            Err(_) => BandPlan::find(&default_band_plan_name())?,
+        };
+        self.band_plan_name = name.to_string();
+        for receiver in self.receiver.iter_mut() {
+            self.band_plan.apply(&mut receiver.band_info);
+        }
+        result.map(|_| ())
+    }
+
+    // snapshot of a receiver for a memory channel
+    pub fn receiver_memory(&self, rx: usize, name: String) -> Memory {
+        let receiver = &self.receiver[rx];
+        let frequency = if receiver.ctun { receiver.ctun_frequency } else { receiver.frequency };
+        let mut memory = Memory::new(name, frequency, receiver.mode);
+        memory.filter = receiver.filter;
+        memory.filter_low = receiver.filter_low;
+        memory.filter_high = receiver.filter_high;
+        if receiver.ctun {
+            memory.ctun_offset = receiver.ctun_frequency - receiver.frequency;
+        }
+        memory.antenna = receiver.band_info[receiver.band.to_usize()].antenna;
+        if rx == 0 && self.split {
+            memory.split = true;
+            memory.tx_frequency = if self.receiver[1].ctun { self.receiver[1].ctun_frequency } else { self.receiver[1].frequency };
+        }
+        if self.transmitter.ctcss {
+            memory.ctcss = Some(self.transmitter.ctcss_frequency);
+        }
+        memory
+    }
+
+    // tune a receiver to a memory channel, split uses VFO B for the transmit frequency
+    pub fn recall_memory(&mut self, rx: usize, memory: &Memory) {
+        if let Some(band_info) = self.receiver[rx].find_band_from_frequency(memory.frequency) {
+            self.set_receiver_band(rx, band_info.band.to_usize());
+        }
+        let b = self.receiver[rx].band.to_usize();
+        if memory.antenna != Antenna::NONE {
+            self.receiver[rx].band_info[b].antenna = memory.antenna;
+        }
+        if memory.ctun_offset != 0.0 {
+            self.receiver[rx].ctun = false;
+            self.receiver[rx].set_frequency(memory.frequency - memory.ctun_offset);
+            self.receiver[rx].ctun = true;
+            self.receiver[rx].set_frequency(memory.frequency);
+            self.receiver[rx].set_ctun(true);
+        } else {
+            self.receiver[rx].ctun = false;
+            self.receiver[rx].set_ctun(false);
+            self.receiver[rx].set_frequency(memory.frequency);
+        }
+        if memory.filter == Filters::FVar1.to_usize() || memory.filter == Filters::FVar2.to_usize() {
+            self.receiver[rx].mode = memory.mode;
+            self.set_receiver_filter_edges(rx, memory.filter_low, memory.filter_high);
+        } else {
+            self.receiver[rx].filter = memory.filter;
+            self.set_receiver_mode(rx, memory.mode);
+        }
+        if rx == 0 {
+            self.split = memory.split;
+            if memory.split && self.receivers > 1 {
+                if let Some(band_info) = self.receiver[1].find_band_from_frequency(memory.tx_frequency) {
+                    self.set_receiver_band(1, band_info.band.to_usize());
+                }
+                self.receiver[1].set_frequency(memory.tx_frequency);
+            }
+        }
+        self.transmitter.set_ctcss(memory.ctcss);
+        self.updated = true;
+    }
+
+    // recall a channel of the current bank, false if the channel is empty
+    pub fn recall_memory_channel(&mut self, rx: usize, channel: usize) -> bool {
+        match self.memories.get(channel).cloned() {
+            Some(memory) => {
+                self.memories.channel = channel;
+                self.recall_memory(rx, &memory);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // store a receiver in a channel of the current bank keeping the channel name
+    pub fn store_memory_channel(&mut self, rx: usize, channel: usize) -> usize {
+        let name = match self.memories.get(channel) {
+            Some(memory) => memory.name.clone(),
+            None => format!("{}", self.memories.channels().len()),
+        };
+        let memory = self.receiver_memory(rx, name);
+        self.memories.channel = self.memories.store(channel, memory);
+        self.memories.channel
+    }
+
+    // scanning tunes the receiver directly so CTUN is turned off
+    pub fn start_scan(&mut self, rx: usize) {
+        if self.receiver[rx].ctun {
+            let frequency = self.receiver[rx].ctun_frequency;
+            self.receiver[rx].ctun = false;
+            self.receiver[rx].set_ctun(false);
+            self.receiver[rx].set_frequency(frequency);
+        }
+        self.scanner.start(rx);
+    }
+
+    pub fn stop_scan(&mut self) {
+        self.scanner.stop();
+    }
+
+    // called every SCAN_TICK_MS, true when the receiver has been retuned
+    pub fn scan(&mut self) -> bool {
+        if !self.scanner.is_running() {
+            return false;
+        }
+        if self.is_transmitting() {
+            self.scanner.stop();
+            return false;
+        }
+        let rx = self.scanner.rx;
+        let dbm = self.receiver_level(rx);
+        let fm = self.receiver[rx].mode == Modes::FMN.to_usize();
+        let stop_dbm = self.scanner.stop_level(fm, self.receiver[rx].squelch_level());
+        match self.scanner.tick(dbm, stop_dbm) {
+            ScanAction::Next => self.scan_next(rx),
+            ScanAction::None => false,
+        }
+    }
+
+    fn scan_next(&mut self, rx: usize) -> bool {
+        match self.scanner.mode {
+            ScanMode::Band => {
+                let receiver = &mut self.receiver[rx];
+                let b = receiver.band.to_usize();
+                let low = receiver.band_info[b].low;
+                let high = receiver.band_info[b].high;
+                let current = if receiver.ctun { receiver.ctun_frequency } else { receiver.frequency };
+                let mut frequency = current + self.scanner.step;
+                if frequency > high || frequency < low {
+                    frequency = low;
+                }
+                if receiver.ctun {
+                    // recentre the panadapter when the next channel is off the display
+                    let frequency_range = receiver.sample_rate as f64;
+                    let display_frequency_range = frequency_range / receiver.zoom as f64;
+                    let display_frequency_offset = ((frequency_range - display_frequency_range) / 100.0) * receiver.pan as f64;
+                    let display_frequency_low = receiver.frequency - (frequency_range / 2.0) + display_frequency_offset;
+                    let display_frequency_high = display_frequency_low + display_frequency_range;
+                    if frequency < display_frequency_low || frequency > display_frequency_high {
+                        receiver.frequency = frequency;
+                        if receiver.zoom > 1 {
+                            receiver.pan = 50;
+                        }
+                    }
+                }
+                receiver.set_frequency(frequency);
+            }
+            ScanMode::Memory => {
+                let channels = self.memories.channels().len();
+                if channels == 0 {
+                    self.scanner.stop();
+                    return false;
+                }
+                let channel = (self.memories.channel + 1) % channels;
+                self.recall_memory_channel(rx, channel);
+            }
+        }
+        self.scanner.retuned();
+        true
+    }
+
+    // MOX button, clears TUNE, refused when transmit is locked out
+    pub fn set_mox(&mut self, state: bool) -> bool {
+        if state && !self.check_transmit() {
+            return false;
+        }
+        self.mox = state;
+        if self.mox && self.tune {
+            self.tune = false;
+            self.transmitter.set_tuning(self.tune, self.cw_keyer_sidetone_frequency);
+        }
+        self.updated = true;
+        self.set_state();
+        true
+    }
+
+    // TUNE button, clears MOX, refused when transmit is locked out
+    pub fn set_tune(&mut self, state: bool) -> bool {
+        if state && !self.check_transmit() {
+            return false;
+        }
+        self.tune = state;
+        if self.tune && self.mox {
+            self.mox = false;
+        }
+        self.transmitter.set_tuning(self.tune, self.cw_keyer_sidetone_frequency);
+        self.updated = true;
+        self.set_state();
+        true
+    }
+
+    // MOX from CAT, RIGCTL or TCI, refused when transmit is locked out
+    fn set_remote_mox(&mut self, state: bool) -> bool {
+        if state && !self.check_transmit() {
+            self.external_mox = false;
+            return false;
+        }
+        self.mox = state;
+        self.updated = true;
+        self.set_state();
+        true
+    }
+
+    // off, NR, NR2, NR3, NR4, off like the NR button
+    pub fn cycle_receiver_nr(&mut self, rx: usize) {
+        let receiver = &mut self.receiver[rx];
+        if receiver.nr {
+            receiver.nr = false;
+            receiver.set_nr();
+            receiver.nr2 = true;
+            receiver.set_nr2();
+        } else if receiver.nr2 {
+            receiver.nr2 = false;
+            receiver.set_nr2();
+            receiver.nr3 = true;
+            receiver.set_nr3();
+        } else if receiver.nr3 {
+            receiver.nr3 = false;
+            receiver.set_nr3();
+            receiver.nr4 = true;
+            receiver.set_nr4();
+        } else if receiver.nr4 {
+            receiver.nr4 = false;
+            receiver.set_nr4();
+        } else {
+            receiver.nr = true;
+            receiver.set_nr();
+        }
+    }
+
+    // off, NB, NB2, off like the NB button
+    pub fn cycle_receiver_nb(&mut self, rx: usize) {
+        let receiver = &mut self.receiver[rx];
+        if receiver.nb {
+            receiver.nb = false;
+            receiver.set_nb();
+            receiver.nb2 = true;
+            receiver.set_nb2();
+        } else if receiver.nb2 {
+            receiver.nb2 = false;
+            receiver.set_nb2();
+        } else {
+            receiver.nb = true;
+            receiver.set_nb();
+        }
+    }
+
+    // move a VFO by a number of tuning steps, used by the scroll wheel and MIDI
+    pub fn step_vfo_frequency(&mut self, rx: usize, steps: f64) -> f64 {
+        let mut frequency = if self.receiver[rx].ctun {
+            self.receiver[rx].ctun_frequency
+        } else {
+            self.receiver[rx].frequency
+        };
+        frequency += self.receiver[rx].step * steps;
+        self.receiver[rx].set_frequency(frequency);
+        frequency
+    }
+
+    // VFO A from CAT or RIGCTL turns off CTUN, false if the frequency is outside the bands
+    fn set_remote_frequency_a(&mut self, f: f64) -> bool {
+        match self.receiver[0].find_band_from_frequency(f) {
+            Some(band_info) => {
+                self.receiver[0].ctun = false;
+                if self.receiver[0].band != band_info.band {
+                    self.receiver[0].band = band_info.band;
+                }
+                self.receiver[0].set_frequency(f);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // apply a CAT command, shared by the UI and headless mode
+    // returns false if the command was refused or ignored
+    pub fn handle_cat_message(&mut self, msg: &CatMessage) -> bool {
+        match *msg {
+            CatMessage::UpdateMox(state) => self.set_remote_mox(state),
+            CatMessage::UpdateFrequencyA(f) => self.set_remote_frequency_a(f),
+            CatMessage::UpdateFrequencyB(f) => {
+                match self.receiver[0].find_band_from_frequency(f) {
+                    Some(band_info) => {
+                        self.receiver[1].ctun = false;
+                        self.receiver[1].band = band_info.band;
+                        self.receiver[1].frequency = f;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            CatMessage::UpdateMode(rx, mode) => {
+                self.set_receiver_mode(rx, mode);
+                true
+            }
+            CatMessage::UpdateFilter(rx, low, high) => {
+                self.set_receiver_filter_edges(rx, low, high);
+                true
+            }
+            CatMessage::UpdateAFGain(rx, gain) => {
+                self.receiver[rx].afgain = gain;
+                self.receiver[rx].set_afgain();
+                true
+            }
+            CatMessage::UpdateNR(rx, state) => {
+                self.set_receiver_nr(rx, state);
+                true
+            }
+            CatMessage::UpdateNB(rx, state) => {
+                self.set_receiver_nb(rx, state);
+                true
+            }
+            CatMessage::UpdateANF(rx, state) => {
+                self.receiver[rx].anf = state;
+                self.receiver[rx].set_anf();
+                true
+            }
+            CatMessage::UpdateSplit(state) => {
+                self.split = state;
+                true
+            }
+            CatMessage::UpdateDrive(drive) => {
+                self.transmitter.drive = drive;
+                self.updated = true;
+                true
+            }
+            CatMessage::UpdateBand(rx, band) => {
+                self.set_receiver_band(rx, band);
+                true
+            }
+            CatMessage::RecallMemory(rx, channel) => self.recall_memory_channel(rx, channel),
+        }
+    }
+
+    // apply a RIGCTL command, shared by the UI and headless mode
+    // returns false if the command was refused or ignored
+    pub fn handle_rigctl_message(&mut self, msg: &RIGCTLMessage) -> bool {
+        match *msg {
+            RIGCTLMessage::ClientConnected() | RIGCTLMessage::ClientDisconnected() => true,
+            RIGCTLMessage::UpdateMox(state) => self.set_remote_mox(state),
+            RIGCTLMessage::UpdateFrequencyA(f) => self.set_remote_frequency_a(f),
+            RIGCTLMessage::UpdateFrequencyB(f) => {
+                match self.receiver[1].find_band_from_frequency(f) {
+                    Some(band_info) => {
+                        self.receiver[1].ctun = false;
+                        self.receiver[1].band = band_info.band;
+                        self.receiver[1].set_frequency(f);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            RIGCTLMessage::UpdateMode(rx, mode, passband) => {
+                self.set_receiver_mode(rx, mode);
+                if passband > 0.0 {
+                    self.set_receiver_passband(rx, passband);
+                }
+                true
+            }
+            RIGCTLMessage::UpdateSplit(state) => {
+                self.split = state;
+                true
+            }
+            RIGCTLMessage::UpdateAFGain(rx, gain) => {
+                self.receiver[rx].afgain = gain;
+                self.receiver[rx].set_afgain();
+                true
+            }
+            RIGCTLMessage::UpdateAGCGain(rx, gain) => {
+                self.receiver[rx].agcgain = gain;
+                self.receiver[rx].set_agcgain();
+                true
+            }
+            RIGCTLMessage::UpdateDrive(drive) => {
+                self.transmitter.drive = drive;
+                self.updated = true;
+                true
+            }
+            RIGCTLMessage::UpdateNB(rx, state) => {
+                self.set_receiver_nb(rx, state);
+                true
+            }
+            RIGCTLMessage::UpdateNR(rx, state) => {
+                self.set_receiver_nr(rx, state);
+                true
+            }
+            RIGCTLMessage::UpdateANF(rx, state) => {
+                self.receiver[rx].anf = state;
+                self.receiver[rx].set_anf();
+                true
+            }
+            RIGCTLMessage::RecallMemory(rx, channel) => self.recall_memory_channel(rx, channel),
+            RIGCTLMessage::StoreMemory(rx, channel) => {
+                self.store_memory_channel(rx, channel);
+                true
+            }
+        }
+    }
+
+    // apply a TCI command, shared by the UI and headless mode, IQ for the client goes to tci_data
+    // returns false if the command was refused or ignored
+    pub fn handle_tci_message(&mut self, msg: &TCIMessage, tci_data: &tokio::sync::mpsc::Sender<TCIDataMessage>) -> bool {
+        match *msg {
+            TCIMessage::ClientConnected() => true,
+            TCIMessage::ClientDisconnected() => {
+                for receiver in self.receiver.iter_mut() {
+                    receiver.tci_send_iq_samples = false;
+                }
+                true
+            }
+            TCIMessage::IQStart(rx) => {
+                self.receiver[rx].enable_tci_iq(tci_data.clone());
+                true
+            }
+            TCIMessage::UpdateMox(state) => self.set_remote_mox(state),
+            TCIMessage::UpdateDDS(rx, f) => {
+                // the DDS is the centre of the panadapter, the VFO stays on it with CTUN
+                self.receiver[rx].ctun = true;
+                match self.receiver[rx].find_band_from_frequency(f) {
+                    Some(band_info) => {
+                        if self.receiver[rx].band != band_info.band {
+                            self.receiver[rx].band = band_info.band;
+                        }
+                        self.receiver[rx].frequency = f;
+                        self.receiver[rx].ctun_frequency = f;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            TCIMessage::UpdateVFO(rx, f) => {
+                match self.receiver[rx].find_band_from_frequency(f) {
+                    Some(band_info) => {
+                        if self.receiver[rx].band != band_info.band {
+                            self.receiver[rx].band = band_info.band;
+                        }
+                        self.receiver[rx].ctun = true;
+                        self.receiver[rx].set_frequency(f);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            TCIMessage::UpdateAFGain(gain) => {
+                self.receiver[0].afgain = (gain.clamp(-60.0, 0.0) + 60.0) / 60.0;
+                self.receiver[0].set_afgain();
+                true
+            }
+            TCIMessage::UpdateMode(rx, mode) => {
+                self.set_receiver_mode(rx, mode);
+                true
+            }
+            TCIMessage::UpdateDrive(drive) => {
+                self.transmitter.drive = drive;
+                self.updated = true;
+                true
+            }
+            TCIMessage::UpdateSplit(state) => {
+                self.split = state;
+                true
+            }
+            TCIMessage::UpdateSquelch(rx, state) => {
+                self.receiver[rx].set_squelch(state);
+                true
+            }
+            TCIMessage::UpdateSquelchLevel(rx, level) => {
+                self.receiver[rx].set_squelch_level(level);
+                true
+            }
+            TCIMessage::UpdateANF(rx, state) => {
+                self.receiver[rx].anf = state;
+                self.receiver[rx].set_anf();
+                true
+            }
+        }
+    }
+
+    // apply a MIDI control to the active receiver, the UI sends the
+    // transmit, AF gain, NR and NB controls through its own widgets
+    pub fn handle_midi_message(&mut self, msg: &MidiMessage) {
+        let rx = if self.receiver[0].active { 0 } else { 1 };
+        match *msg {
+            MidiMessage::UpdateMox(state) => {
+                if self.mox != state {
+                    self.set_mox(state);
+                }
+            }
+            MidiMessage::StepFrequencyA(increment) => {
+                self.step_vfo_frequency(0, increment as f64);
+            }
+            MidiMessage::StepFrequencyB(increment) => {
+                self.step_vfo_frequency(1, increment as f64);
+            }
+            MidiMessage::UpdateAFGain(gain) => {
+                self.receiver[rx].afgain = gain.clamp(0.0, 1.0);
+                self.receiver[rx].set_afgain();
+            }
+            MidiMessage::StepAFGain(increment) => {
+                self.receiver[rx].afgain = (self.receiver[rx].afgain + increment as f32 / 100.0).clamp(0.0, 1.0);
+                self.receiver[rx].set_afgain();
+            }
+            MidiMessage::UpdateFilterWidth(width) => self.set_receiver_passband(rx, width),
+            MidiMessage::StepFilterWidth(increment) => {
+                let width = self.receiver_passband(rx) + (increment as f64 * 50.0);
+                self.set_receiver_passband(rx, width.clamp(50.0, 10000.0));
+            }
+            MidiMessage::BandUp | MidiMessage::BandDown => {
+                let bands = self.band_plan.grid_bands();
+                let band = self.receiver[rx].next_band(&bands, matches!(msg, MidiMessage::BandUp));
+                self.set_receiver_band(rx, band);
+            }
+            MidiMessage::NextMode => {
+                let mode = (self.receiver[rx].mode + 1) % (Modes::DRM.to_usize() + 1);
+                self.set_receiver_mode(rx, mode);
+            }
+            MidiMessage::ToggleMox => {
+                let state = !self.mox;
+                self.set_mox(state);
+            }
+            MidiMessage::ToggleTune => {
+                let state = !self.tune;
+                self.set_tune(state);
+            }
+            MidiMessage::ToggleNR => self.cycle_receiver_nr(rx),
+            MidiMessage::ToggleNB => self.cycle_receiver_nb(rx),
+        }
+    }
+
+    // the receiver whose VFO sets the transmit frequency
+    pub fn transmit_receiver(&self) -> usize {
+        if self.split {1} else {0}
+    }
+
+    // transmit frequency as shown on the VFO, before any XVTR offset
+    pub fn transmit_frequency(&self) -> f64 {
+        let rx = self.transmit_receiver();
+        if self.receiver[rx].ctun {
+            self.receiver[rx].ctun_frequency
+        } else {
+            self.receiver[rx].frequency
+        }
+    }
+
+    // receiver centre frequency sent to the radio, IF = RF - LO + error for transverters
+    pub fn receiver_if_frequency(&self, rx: usize) -> f64 {
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        self.receiver[rx].frequency - info.lo + info.lo_error
+    }
+
+    // transmit frequency sent to the radio, IF = RF - LO + error for transverters
+    pub fn transmit_if_frequency(&self) -> f64 {
+        let rx = self.transmit_receiver();
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        self.transmit_frequency() - info.lo + info.lo_error
+    }
+
+    // drive level limited by the transverter setting
+    pub fn transmit_drive(&self) -> f32 {
+        let rx = self.transmit_receiver();
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        if info.is_xvtr() {
+            self.transmitter.drive.min(info.xvtr_drive_limit)
+        } else {
+            self.transmitter.drive
+        }
+    }
+
+    // open collector outputs 1..7 as bits 0..6, the transverter PTT pin follows transmit
+    pub fn oc_outputs(&self) -> u8 {
+        let rx = self.transmit_receiver();
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        if info.is_xvtr() && (1..=7).contains(&info.xvtr_ptt_pin) && self.is_transmitting() && !self.transmit_inhibited() {
+            1 << (info.xvtr_ptt_pin - 1)
+        } else {
+            0
+        }
+    }
+
+    // why the transmitted passband is not inside an allowed TX range, if it is not
+    fn transmit_range_error(&self) -> Option<String> {
+        let rx = self.transmit_receiver();
+        // a transverter band only counts while it is selected, its LO sets the IF
+        let current = self.receiver[rx].band;
+        let f = self.transmit_frequency();
+        let low = f + self.transmitter.filter_low.min(self.transmitter.filter_high);
+        let high = f + self.transmitter.filter_low.max(self.transmitter.filter_high);
+        let allowed = self.receiver[rx].band_info.iter().find(|band| {
+            band.enabled && band.tx_allowed && !(band.is_xvtr() && (band.xvtr_rx_only || band.band != current)) && low >= band.low && high <= band.high
+        });
+        match allowed {
+            Some(band) => {
+                if band.lo != 0.0 {
+                    let f = f - band.lo + band.lo_error;
+                    if f <= 0.0 || f >= MAX_TX_IF_FREQUENCY {
+                        return Some(format!("XVTR IF {} Hz is outside the radio range", format_u32_with_separators(f.max(0.0) as u32)));
+                    }
+                }
+                None
+            }
+            None => {
+                let band = self.receiver[rx].band_info.iter().find(|band| {
+                    band.enabled && !(band.is_xvtr() && band.band != current) && f >= band.low && f < band.high
+                });
+                match band {
+                    Some(band) if !band.tx_allowed => Some(format!("{} band is receive only", band.label)),
+                    Some(band) if band.is_xvtr() && band.xvtr_rx_only => Some(format!("{} transverter is receive only", band.label)),
+                    _ => Some(format!("{}-{} Hz is outside the allowed TX ranges", format_u32_with_separators(low as u32), format_u32_with_separators(high as u32))),
+                }
+            }
+        }
+    }
+
+    // true if the transmitted passband falls outside the band plan allocation
+    pub fn transmit_out_of_band(&self) -> bool {
+        self.transmit_range_error().is_some()
+    }
+
+    // a receive only transverter never transmits, whatever the TX lockout setting
+    fn xvtr_rx_only_error(&self) -> Option<String> {
+        let rx = self.transmit_receiver();
+        let b = self.receiver[rx].band.to_usize();
+        let info = &self.receiver[rx].band_info[b];
+        if info.is_xvtr() && info.xvtr_rx_only {
+            Some(format!("{} transverter is receive only", info.label))
+        } else {
+            None
+        }
+    }
+
+    // reason transmit is refused, receive only transverters or the TX lockout
+    pub fn transmit_lockout(&self) -> Option<String> {
+        if let Some(reason) = self.xvtr_rx_only_error() {
+            return Some(format!("TX inhibited: {}", reason));
+        }
+        if !self.tx_lockout {
+            return None;
+        }
+        self.transmit_range_error().map(|reason| format!("TX locked out: {}", reason))
+    }
+
+    pub fn transmit_inhibited(&self) -> bool {
+        self.transmit_lockout().is_some()
+    }
+
+    // check before keying, keeps the reason for the UI
+    pub fn check_transmit(&mut self) -> bool {
+        match self.transmit_lockout() {
+            Some(reason) => {
+                if reason != self.tx_lockout_reason {
+                    eprintln!("{}", reason);
+                    self.tx_lockout_reason = reason;
+                }
+                false
+            }
+            None => {
+                self.tx_lockout_reason.clear();
+                true
+            }
+        }
+    }
+
+    // queue text for the CW sender, refused outside CW or when TX is locked out
+    pub fn cw_send_text(&mut self, text: &str) -> bool {
+        if self.transmitter.mode != Modes::CWL.to_usize() && self.transmitter.mode != Modes::CWU.to_usize() {
+            eprintln!("CW sender: not in a CW mode");
+            return false;
+        }
+        if !self.check_transmit() {
+            return false;
+        }
+        let text = expand_macro(text, &self.cw_my_call, &self.cw_call, &self.cw_rst, self.cw_serial);
+        self.cw_sender.queue(&text, self.cw_keyer_speed, self.cw_keyer_weight);
+        true
+    }
+
+    pub fn cw_send_macro(&mut self, index: usize) -> bool {
+        let Some(text) = self.cw_macros.get(index).cloned() else {
+            return false;
+        };
+        let sent = self.cw_send_text(&format!("{} ", text));
+        if sent && text.contains("{NR}") {
+            self.cw_serial += 1;
+        }
+        sent
+    }
+
+    pub fn cw_abort(&mut self) {
+        let ptt = self.cw_sender.ptt;
+        self.cw_sender.abort();
+        self.cw_key_update();
+        if ptt {
+            self.set_state();
+        }
+    }
+
+    pub fn cw_sender_tick(&mut self) {
+        let (ptt_changed, key_changed) = self.cw_sender.tick(std::time::Instant::now(), self.mox, self.cw_breakin, self.cw_keyer_ptt_delay, self.cw_keyer_hang_time);
+        if self.cw_sender.ptt && !self.check_transmit() {
+            self.cw_abort();
+            return;
+        }
+        if ptt_changed || key_changed {
+            self.cw_key_update();
+        }
+        if ptt_changed {
+            self.set_state();
+        }
+    }
+
+    // software keyer for the paddles when the radio keyer is not used
+    pub fn cw_keyer_tick(&mut self, now: std::time::Instant) {
+        let mut dot = self.dot || self.midi_dot;
+        let mut dash = self.dash || self.midi_dash;
+        if self.cw_keys_reversed {
+            std::mem::swap(&mut dot, &mut dash);
+        }
+        if (dot || dash) && self.cw_sender.is_active() {
+            // the paddles break into the text being sent
+            self.cw_abort();
+        }
+        let settings = CwKeyerSettings {
+            mode: self.cw_keyer_mode,
+            speed: self.cw_keyer_speed,
+            weight: self.cw_keyer_weight,
+            spacing: self.cw_keyer_spacing != 0,
+            breakin: self.cw_breakin,
+            mox: self.mox,
+            ptt_delay: self.cw_keyer_ptt_delay,
+            hang_time: self.cw_keyer_hang_time,
+        };
+        let (mut ptt_changed, mut key_changed) = self.cw_keyer.tick(now, dot, dash, &settings);
+        if self.cw_keyer.ptt && !self.check_transmit() {
+            self.cw_keyer.abort();
+            ptt_changed = true;
+            key_changed = true;
+        }
+        if ptt_changed || key_changed {
+            self.cw_key_update();
+        }
+        if ptt_changed {
+            self.set_state();
+        }
+    }
+
+    pub fn cw_keyer_abort(&mut self) {
+        let ptt = self.cw_keyer.ptt;
+        self.cw_keyer.abort();
+        self.cw_key_update();
+        if ptt {
+            self.set_state();
+        }
+    }
+
+    pub fn cw_key_down(&self) -> bool {
+        self.cw_sender.key_down || self.cw_keyer.key_down
+    }
+
+    // the radio keyer takes the CWX bit, otherwise key the WDSP tone
+    fn cw_key_update(&mut self) {
+        if !self.cw_keyer_internal {
+            if self.cw_sender.is_active() || self.cw_keyer.is_active() {
+                self.transmitter.set_cw_key(self.cw_key_down(), self.cw_keyer_sidetone_frequency);
+            } else {
+                self.transmitter.set_tuning(self.tune, self.cw_keyer_sidetone_frequency);
+            }
+        }
+        self.updated = true;
+    }
+
+    pub fn cwx(&self) -> bool {
+        self.cw_keyer_internal && self.cw_sender.key_down
+    }
+
+    // current receiver passband width in Hz
+    pub fn receiver_passband(&self, rx: usize) -> f64 {
+        (self.receiver[rx].filter_high - self.receiver[rx].filter_low).abs()
+    }
+
 }
 
 fn format_u32_with_separators(value: u32) -> String {
@@ -773,3 +1928,88 @@ fn draw_waterfall(cr: &Context, width: i32, height: i32, pixbuf: &Rc<RefCell<Opt
         cr.fill().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bandplan::BandPlanBand;
+
+    fn radio() -> Radio {
+        let address = "127.0.0.1:1024".parse().unwrap();
+        let device = Device{address, my_address: address, device: 1, board: Boards::Hermes, protocol: 1, version: 32, status: 2, mac: [0;6], supported_receivers: 2, supported_transmitters: 1, adcs: 1, frequency_min: 0, frequency_max: 61440000};
+        let mut radio = Radio::new(device, 1024);
+        radio.set_band_plan("IARU Region 1").unwrap();
+        radio
+    }
+
+    fn tune(radio: &mut Radio, band: Bands, frequency: f64) {
+        radio.receiver[0].band = band;
+        radio.receiver[0].ctun = false;
+        radio.receiver[0].frequency = frequency;
+    }
+
+    #[test]
+    fn transmit_in_band() {
+        let mut radio = radio();
+        tune(&mut radio, Bands::Band20, 14200000.0);
+        assert_eq!(radio.transmit_range_error(), None);
+        radio.tx_lockout = true;
+        assert!(radio.check_transmit());
+        assert!(!radio.transmit_inhibited());
+    }
+
+    #[test]
+    fn transmit_out_of_band() {
+        let mut radio = radio();
+        // the USB passband runs past the top of the band
+        tune(&mut radio, Bands::Band20, 14349000.0);
+        assert!(radio.transmit_range_error().unwrap().contains("outside the allowed TX ranges"));
+        assert!(radio.transmit_out_of_band());
+        assert!(radio.check_transmit());
+        radio.tx_lockout = true;
+        assert!(!radio.check_transmit());
+        assert!(radio.tx_lockout_reason.starts_with("TX locked out"));
+        assert!(radio.transmit_inhibited());
+
+        tune(&mut radio, Bands::Band11, 27185000.0);
+        assert_eq!(radio.transmit_range_error().unwrap(), "11 band is receive only");
+    }
+
+    #[test]
+    fn transverter_transmit() {
+        let mut radio = radio();
+        let b = Bands::XVTR1.to_usize();
+        tune(&mut radio, Bands::XVTR1, 145000000.0);
+        assert_eq!(radio.transmit_range_error(), None);
+        assert_eq!(radio.transmit_if_frequency(), 29000000.0);
+
+        radio.receiver[0].band_info[b].xvtr_rx_only = true;
+        assert_eq!(radio.transmit_range_error().unwrap(), "144 transverter is receive only");
+        radio.tx_lockout = true;
+        assert!(!radio.check_transmit());
+        radio.receiver[0].band_info[b].xvtr_rx_only = false;
+
+        radio.receiver[0].band_info[b].lo = 80000000.0;
+        assert_eq!(radio.transmit_range_error().unwrap(), "XVTR IF 65.000.000 Hz is outside the radio range");
+        radio.receiver[0].band_info[b].lo = 150000000.0;
+        assert_eq!(radio.transmit_range_error().unwrap(), "XVTR IF 0 Hz is outside the radio range");
+    }
+
+    #[test]
+    fn band_plan_user_band() {
+        let mut radio = radio();
+        let last = radio.band_plan.bands.last().unwrap().band;
+        radio.band_plan.bands.push(BandPlanBand{band: Bands::USER1, label: String::from("MW"), low: 530000.0, high: 1700000.0, tx: false, segments: Vec::new()});
+        radio.band_plan.apply(&mut radio.receiver[0].band_info);
+        // found before GEN
+        assert_eq!(radio.receiver[0].find_band_from_frequency(1000000.0).unwrap().band, Bands::USER1);
+        assert_eq!(radio.receiver[0].find_band_from_frequency(1000.0).map(|info| info.band), None);
+        // band up and down follow the band grid
+        let bands = radio.band_plan.grid_bands();
+        radio.receiver[0].band = last;
+        assert_eq!(radio.receiver[0].next_band(&bands, true), Bands::USER1.to_usize());
+        radio.receiver[0].band = Bands::USER1;
+        assert_eq!(radio.receiver[0].next_band(&bands, false), last.to_usize());
+        assert_eq!(radio.receiver[0].next_band(&bands, true), Bands::XVTR1.to_usize());
+    }
+}
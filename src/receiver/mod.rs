@@ -25,6 +25,7 @@ use tokio::sync::mpsc::{self};
 
 use crate::agc::AGC;
 use crate::bands::{Bands, BandInfo};
+use crate::cwdecoder::CwDecoder;
 use crate::filters::Filters;
 use crate::modes::Modes;
 use crate::wdsp::*;
@@ -149,6 +150,8 @@ pub struct Receiver {
     pub cw_decoder_audio_buffer_offset: usize,
 #[serde(skip_serializing, skip_deserializing)]
     pub cw_decoder_audio_buffer: Vec<f32>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub cw_decoder_state: CwDecoder,
 
     pub equalizer_enabled: bool,
     pub equalizer_preamp: f32,
@@ -267,6 +270,7 @@ impl Receiver {
         let cw_decoder: bool =  false;
         let cw_decoder_audio_buffer_offset: usize =0;
         let cw_decoder_audio_buffer = vec![0.0f32; local_audio_buffer_size];
+        let cw_decoder_state = CwDecoder::new();
         let equalizer_enabled: bool = true;
         let equalizer_preamp: f32 = 0.0;
         let equalizer_low: f32 = 0.0;
@@ -374,6 +378,7 @@ impl Receiver {
                             cw_decoder,
                             cw_decoder_audio_buffer_offset,
                             cw_decoder_audio_buffer,
+                            cw_decoder_state,
                             equalizer_enabled,
                             equalizer_preamp,
                             equalizer_low,
@@ -412,7 +417,7 @@ impl Receiver {
         self.remote_audio_buffer_offset = 4;
         self.cw_decoder_audio_buffer_offset = 0;
         self.cw_decoder_audio_buffer = vec![0.0f32; self.local_audio_buffer_size];
-        //self.morsedecoder = MorseDecoder::new((self.output_rate/4) as f32, 20.0);
+        self.cw_decoder_state = CwDecoder::new();
 
         self.init_wdsp(self.channel);
         self.create_display(self.channel);
@@ -752,6 +757,44 @@ impl Receiver {
         }
     }
 
+    // level is in dB (-140..0) as used by TCI
+    pub fn set_squelch_level(&mut self, level: f64) {
+        let threshold = (level + 140.0).clamp(0.0, 100.0);
+        if self.mode == Modes::FMN.to_usize() {
+            self.fm_squelch_threshold = threshold;
+        } else {
+            self.am_squelch_threshold = threshold;
+        }
+        self.set_squelch_threshold();
+    }
+
+    // squelch level in dB (-140..0), None when squelch is off or in FM, where the
+    // squelch gates on noise and WDSP does not report whether it is open
+    pub fn squelch_level(&self) -> Option<f64> {
+        if self.mode == Modes::FMN.to_usize() {
+            None
+        } else if self.am_squelch {
+            Some(self.am_squelch_threshold - 140.0)
+        } else {
+            None
+        }
+    }
+
+    // turn squelch on/off without changing the threshold
+    pub fn set_squelch(&mut self, state: bool) {
+        if self.mode == Modes::FMN.to_usize() {
+            self.fm_squelch = state;
+            unsafe {
+                SetRXAFMSQRun(self.channel, self.fm_squelch as i32);
+            }
+        } else {
+            self.am_squelch = state;
+            unsafe {
+                SetRXAAMSQRun(self.channel, self.am_squelch as i32);
+            }
+        }
+    }
+
     pub fn set_nr2(&self) {
         unsafe {
             SetRXAEMNRRun(self.channel, self.nr2 as i32);
@@ -836,20 +879,37 @@ impl Receiver {
             Spectrum0(1, self.channel, 0, 0, raw_ptr);
         }
 
+        if self.cw_decoder && (self.mode == Modes::CWL.to_usize() || self.mode == Modes::CWU.to_usize()) {
+            self.decode_cw();
+        }
+
         if self.tci_send_iq_samples {
             if let Some(ref tx) = self.tci_iq_tx {
-                let msg = TCIDataMessage::IQData(self.iq_input_buffer.clone());
+                let msg = TCIDataMessage::IQData(self.channel as usize, self.iq_input_buffer.clone());
                 if let Err(e) = tx.try_send(msg) {
                 }
             }
             if let Some(ref tx) = self.tci_iq_tx {
-                let msg = TCIDataMessage::AudioData(self.audio_buffer.clone());
+                let msg = TCIDataMessage::AudioData(self.channel as usize, self.audio_buffer.clone());
                 if let Err(e) = tx.try_send(msg) {
                 }
             }
         }
     }
 
+    // the demodulated audio has the CW tone at cw_pitch
+    fn decode_cw(&mut self) {
+        for i in 0..self.output_samples {
+            let offset = self.cw_decoder_audio_buffer_offset;
+            self.cw_decoder_audio_buffer[offset] = self.audio_buffer[i * 2] as f32;
+            self.cw_decoder_audio_buffer_offset += 1;
+            if self.cw_decoder_audio_buffer_offset >= self.cw_decoder_audio_buffer.len() {
+                self.cw_decoder_state.process(&self.cw_decoder_audio_buffer, self.output_rate as f32, self.cw_pitch as f32);
+                self.cw_decoder_audio_buffer_offset = 0;
+            }
+        }
+    }
+
     pub fn sample_rate_changed(&mut self, rate: i32) {
         self.sample_rate = rate;
         self.output_samples = self.buffer_size/(self.sample_rate/48000) as usize;
@@ -927,10 +987,29 @@ impl Receiver {
         }
     }
 
+    // the narrowest band containing the frequency, so a band added by the
+    // band plan is found before GEN or SWBC
     pub fn find_band_from_frequency(&self, frequency: f64) -> Option<BandInfo> {
-        self.band_info.iter().find(|band| {
-            frequency >= band.low && frequency < band.high
-        }).cloned()
+        self.band_info.iter().filter(|band| {
+            band.enabled && frequency >= band.low && frequency < band.high
+        }).min_by(|a, b| (a.high - a.low).total_cmp(&(b.high - b.low))).cloned()
+    }
+
+    // the next or previous band in band grid order
+    pub fn next_band(&self, bands: &[usize], up: bool) -> usize {
+        let current = self.band.to_usize();
+        let Some(position) = bands.iter().position(|b| *b == current) else {
+            return bands.first().copied().unwrap_or(current);
+        };
+        let count = bands.len();
+        let mut index = position;
+        for _ in 0..count {
+            index = if up { (index + 1) % count } else { (index + count - 1) % count };
+            if self.band_info.get(bands[index]).is_some_and(|info| info.enabled) {
+                return bands[index];
+            }
+        }
+        current
     }
 
     pub fn enable_tci_iq(&mut self, tx_from_tci: mpsc::Sender<TCIDataMessage>) {
@@ -16,20 +16,35 @@
 */
 
 use std::io::{self, BufReader, BufWriter, Read, Write};
-use std::net::TcpListener;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
 use std::sync::mpsc::{self};
+use std::thread;
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
+use crate::modes::Modes;
 use crate::radio::RadioMutex;
 
 
 const DEBUG_RIGCTL: bool = false;
+const ACCEPT_POLL_MS: u64 = 100;
 
 pub enum RIGCTLMessage {
     ClientConnected(),
     ClientDisconnected(),
     UpdateMox(bool),
     UpdateFrequencyA(f64),
+    UpdateFrequencyB(f64),
+    UpdateMode(usize, usize, f64),
+    UpdateSplit(bool),
+    UpdateAFGain(usize, f32),
+    UpdateAGCGain(usize, f32),
+    UpdateDrive(f32),
+    UpdateNB(usize, bool),
+    UpdateNR(usize, bool),
+    UpdateANF(usize, bool),
+    RecallMemory(usize, usize),
+    StoreMemory(usize, usize),
 }
 
 impl Default for RIGCTLMessage {
@@ -38,10 +53,29 @@ impl Default for RIGCTLMessage {
     }
 }
 
+// counts a client until its thread ends, even if it panics
+struct RIGCTLClient {
+    peer: SocketAddr,
+    clients: Arc<AtomicUsize>,
+    tx: mpsc::Sender<RIGCTLMessage>,
+}
+
+impl Drop for RIGCTLClient {
+    fn drop(&mut self) {
+        let active = self.clients.fetch_sub(1, Ordering::SeqCst) - 1;
+        eprintln!("RIGCTL: client {} disconnected ({} active)", self.peer, active);
+        if active == 0 {
+            let _ = self.tx.send(RIGCTLMessage::ClientDisconnected());
+        }
+    }
+}
+
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RIGCTL {
     pub address: String,
+#[serde(skip_serializing, skip_deserializing)]
+    vfo: usize,
 }
 
 impl RIGCTL {
@@ -49,8 +83,10 @@ impl RIGCTL {
     pub fn new(network_address: String) -> Self {
 if DEBUG_RIGCTL {eprintln!("RIGCTL::new");}
         let address = network_address;
+        let vfo = 0;
         RIGCTL {
             address,
+            vfo,
         }
     }
 
@@ -60,28 +96,68 @@ if DEBUG_RIGCTL {eprintln!("RIGCTL::new");}
 
     pub fn run(&mut self, radio_mutex: &RadioMutex, tx: &mpsc::Sender<RIGCTLMessage>, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
 if DEBUG_RIGCTL {eprintln!("RIGCTL::run");}
-        // lsten for a connection
+        // lsten for connections, non blocking so the stop flag is seen
         let listener = TcpListener::bind(&self.address)?;
+        listener.set_nonblocking(true)?;
+        let clients = Arc::new(AtomicUsize::new(0));
 
-
+if DEBUG_RIGCTL {eprintln!("RIGCTL::listening");}
         while !stop_flag.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    let r = radio_mutex.radio.lock().unwrap();
+                    let max_clients = r.cat_max_clients;
+                    drop(r);
+                    if clients.load(Ordering::SeqCst) >= max_clients {
+                        eprintln!("RIGCTL: rejected client {} (limit of {} clients)", peer, max_clients);
+                        continue;
+                    }
+                    let active = clients.fetch_add(1, Ordering::SeqCst) + 1;
+                    eprintln!("RIGCTL: client {} connected ({} active)", peer, active);
+                    let _ = tx.send(RIGCTLMessage::ClientConnected());
 
-if DEBUG_RIGCTL {eprintln!("RIGCTL::listening");}
-        let stream = listener.incoming().next().expect("Failed to accept connection")?;
+                    // each client has its own VFO context
+                    let mut client = self.clone();
+                    let radio_mutex_clone = radio_mutex.clone();
+                    let tx_clone = tx.clone();
+                    let stop_flag_clone = stop_flag.clone();
+                    let rigctl_client = RIGCTLClient{peer, clients: clients.clone(), tx: tx.clone()};
+                    thread::spawn(move || {
+                        let _rigctl_client = rigctl_client;
+                        if let Err(e) = client.serve_client(stream, &radio_mutex_clone, &tx_clone, stop_flag_clone) {
+                            eprintln!("RIGCTL: client {} error {}", peer, e);
+                        }
+                    });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+                }
+                Err(e) => {
+                    eprintln!("RIGCTL: accept error {}", e);
+                    thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+                }
+            }
+        }
+if DEBUG_RIGCTL {eprintln!("RIGCTL::run: exiting");}
+        Ok(())
+    }
+
+    fn serve_client(&mut self, stream: TcpStream, radio_mutex: &RadioMutex, tx: &mpsc::Sender<RIGCTLMessage>, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        // wake up regularly so the stop flag is seen
+        stream.set_read_timeout(Some(Duration::from_millis(ACCEPT_POLL_MS)))?;
         let reader_stream = stream.try_clone()?;
-        let mut writer_stream = stream.try_clone()?;
+        let writer_stream = stream.try_clone()?;
         let mut reader = BufReader::new(reader_stream);
         let mut writer = BufWriter::new(writer_stream);
         let mut received_data = [0; 1024];
 
-        let _ = tx.send(RIGCTLMessage::ClientConnected());
+        self.vfo = 0;
 if DEBUG_RIGCTL {eprintln!("RIGCTL::running");}
         while !stop_flag.load(Ordering::Relaxed) {
             match reader.read(&mut received_data) {
                 Ok(0) => {
                     // client closed
-                    eprintln!("RIGCTL: client closed connection");
-                    let _ = tx.send(RIGCTLMessage::ClientDisconnected());
                     break;
                     }
                 Ok(bytes_read) => {
@@ -99,37 +175,88 @@ if DEBUG_RIGCTL {eprintln!("RIGCTL::running");}
                         }
                     }
                     }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    // no command from the client
+                    }
                 Err(e) => {
-                    eprintln!("RIGCTL error {}", e);
-                    break;
+                    return Err(e);
                     }
             }
         }
-        }
-if DEBUG_RIGCTL {eprintln!("RIGCTL::run: exiting");}
         Ok(())
     }
 
-    fn parse_commands(&self, input: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<RIGCTLMessage>) -> String {
-    // line.lines() already stripped the \n, so just trim whitespace
-    let cmd = input.trim();
-    if cmd.is_empty() { return "".to_string(); }
+    fn vfo_name(vfo: usize) -> &'static str {
+        if vfo == 0 { "VFOA" } else { "VFOB" }
+    }
 
-    let is_extended = cmd.starts_with('\\') || cmd.starts_with('+');
-    let clean_cmd = if is_extended { &cmd[1..] } else { cmd };
+    fn vfo_from_name(name: &str) -> Option<usize> {
+        match name {
+            "VFOA" | "Main" | "MainA" | "RX" => Some(0),
+            "VFOB" | "Sub" | "MainB" | "TX" => Some(1),
+            _ => None,
+        }
+    }
+
+    fn mode_to_hamlib(mode: usize) -> &'static str {
+        match Modes::from_usize(mode) {
+            Some(Modes::LSB) => "LSB",
+            Some(Modes::USB) => "USB",
+            Some(Modes::DSB) => "DSB",
+            Some(Modes::CWL) => "CWR",
+            Some(Modes::CWU) => "CW",
+            Some(Modes::FMN) => "FM",
+            Some(Modes::AM) => "AM",
+            Some(Modes::DIGU) => "PKTUSB",
+            Some(Modes::SPEC) => "USB",
+            Some(Modes::DIGL) => "PKTLSB",
+            Some(Modes::SAM) => "SAM",
+            Some(Modes::DRM) => "AM",
+            None => "USB",
+        }
+    }
 
-    match clean_cmd {
-        "dump_state" | "_" => {
+    fn mode_from_hamlib(name: &str) -> Option<usize> {
+        match name {
+            "LSB" => Some(Modes::LSB.to_usize()),
+            "USB" => Some(Modes::USB.to_usize()),
+            "DSB" => Some(Modes::DSB.to_usize()),
+            "CWR" => Some(Modes::CWL.to_usize()),
+            "CW" => Some(Modes::CWU.to_usize()),
+            "FM" => Some(Modes::FMN.to_usize()),
+            "AM" => Some(Modes::AM.to_usize()),
+            "PKTUSB" => Some(Modes::DIGU.to_usize()),
+            "PKTLSB" => Some(Modes::DIGL.to_usize()),
+            "SAM" => Some(Modes::SAM.to_usize()),
+            _ => None,
+        }
+    }
 
-            format!(
+    fn dump_state(&self, radio_mutex: &RadioMutex) -> String {
+        let r = radio_mutex.radio.lock().unwrap();
+        let low = r.frequency_min as f64;
+        let high = r.frequency_max as f64;
+        format!(
 "0
-1
 2
-100000.0 54000000.0 0x1ef 0 -1 0x0 0x0
+2
+{low:.1} {high:.1} 0x90caf -1 -1 0x3 0x1
 0 0 0 0 0 0 0
-100000.0 54000000.0 0x1ef 0 -1 0x0 0x0
+{low:.1} {high:.1} 0x90caf 1000 100000 0x3 0x1
 0 0 0 0 0 0 0
+0x90caf 1
+0x90caf 10
+0x90caf 100
+0x90caf 1000
 0 0
+0x82 500
+0x82 250
+0xc0c 2400
+0xc0c 2700
+0xc0c 1800
+0x1 6000
+0x20 12000
+0x10 2400
 0 0
 0
 0
@@ -137,52 +264,259 @@ if DEBUG_RIGCTL {eprintln!("RIGCTL::run: exiting");}
 0
 
 
+0x302
+0x302
+0x50001018
+0x1018
 0x0
 0x0
-0x0
-0x0
-0x0
-0
-")
+", low = low, high = high)
+    }
+
+    fn get_level(&self, level: &str, radio_mutex: &RadioMutex) -> String {
+        let r = radio_mutex.radio.lock().unwrap();
+        let rx = self.vfo;
+        match level {
+            "AF" => format!("{:.6}\n", r.receiver[rx].afgain),
+            "RF" => format!("{:.6}\n", (r.receiver[rx].agcgain + 20.0) / 140.0),
+            "RFPOWER" => format!("{:.6}\n", r.transmitter.drive / 100.0),
+            "STRENGTH" => format!("{}\n", (r.receiver_level(rx) + 73.0) as i32),
+            "SWR" => format!("{:.6}\n", r.transmitter.power_and_swr().1),
+            _ => "RPRT -1\n".to_string(),
+        }
+    }
+
+    fn set_level(&self, level: &str, value: &str, tx: &mpsc::Sender<RIGCTLMessage>) -> String {
+        let rx = self.vfo;
+        let v = match value.parse::<f32>() {
+            Ok(v) => v,
+            Err(_) => return "RPRT -1\n".to_string(),
+        };
+        match level {
+            "AF" => {
+                let _ = tx.send(RIGCTLMessage::UpdateAFGain(rx, v.clamp(0.0, 1.0)));
+                "RPRT 0\n".to_string()
+            }
+            "RF" => {
+                let _ = tx.send(RIGCTLMessage::UpdateAGCGain(rx, (v.clamp(0.0, 1.0) * 140.0) - 20.0));
+                "RPRT 0\n".to_string()
+            }
+            "RFPOWER" => {
+                let _ = tx.send(RIGCTLMessage::UpdateDrive(v.clamp(0.0, 1.0) * 100.0));
+                "RPRT 0\n".to_string()
+            }
+            _ => "RPRT -1\n".to_string(),
+        }
+    }
+
+    fn get_func(&self, func: &str, radio_mutex: &RadioMutex) -> String {
+        let r = radio_mutex.radio.lock().unwrap();
+        let rx = self.vfo;
+        match func {
+            "NB" => format!("{}\n", (r.receiver[rx].nb || r.receiver[rx].nb2) as i32),
+            "NR" => format!("{}\n", (r.receiver[rx].nr || r.receiver[rx].nr2) as i32),
+            "ANF" => format!("{}\n", r.receiver[rx].anf as i32),
+            _ => "RPRT -1\n".to_string(),
+        }
+    }
+
+    fn set_func(&self, func: &str, value: &str, tx: &mpsc::Sender<RIGCTLMessage>) -> String {
+        let rx = self.vfo;
+        let state = value == "1";
+        match func {
+            "NB" => {
+                let _ = tx.send(RIGCTLMessage::UpdateNB(rx, state));
+                "RPRT 0\n".to_string()
+            }
+            "NR" => {
+                let _ = tx.send(RIGCTLMessage::UpdateNR(rx, state));
+                "RPRT 0\n".to_string()
+            }
+            "ANF" => {
+                let _ = tx.send(RIGCTLMessage::UpdateANF(rx, state));
+                "RPRT 0\n".to_string()
+            }
+            _ => "RPRT -1\n".to_string(),
         }
-        "get_powerstat" => {
-            format!("1\n")
+    }
+
+    fn parse_commands(&mut self, input: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<RIGCTLMessage>) -> String {
+    // line.lines() already stripped the \n, so just trim whitespace
+    let cmd = input.trim();
+    if cmd.is_empty() { return "".to_string(); }
+
+    let is_extended = cmd.starts_with('\\') || cmd.starts_with('+');
+    let clean_cmd = if is_extended { &cmd[1..] } else { cmd };
+
+    let mut args: Vec<&str> = clean_cmd.split_whitespace().collect();
+    if args.is_empty() { return "RPRT -1\n".to_string(); }
+    let name = args.remove(0);
+
+    // with --vfo the first argument is the VFO the command applies to, S then has the split and TX VFO
+    let current_vfo = self.vfo;
+    if !args.is_empty() && name != "V" && name != "set_vfo" {
+        if let Some(vfo) = Self::vfo_from_name(args[0]) {
+            self.vfo = vfo;
+            args.remove(0);
+        } else if args[0] == "currVFO" {
+            args.remove(0);
         }
-        "get_freq" | "f" | "F" => {
+    }
+
+    let reply = match name {
+        "dump_state" | "_" => self.dump_state(radio_mutex),
+        "get_powerstat" => "1\n".to_string(),
+        "get_freq" | "f" => {
             let r = radio_mutex.radio.lock().unwrap();
-            let freq = if r.receiver[0].ctun { r.receiver[0].ctun_frequency } else { r.receiver[0].frequency };
-            format!("{}\n", freq as u32)
+            let rx = self.vfo;
+            let freq = if r.receiver[rx].ctun { r.receiver[rx].ctun_frequency } else { r.receiver[rx].frequency };
+            format!("{}\n", freq as u64)
         }
-        "get_ptt" | "t" | "T" => {
+        "set_freq" | "F" => {
+            let val = args.first().unwrap_or(&"0");
+            if let Ok(f) = val.parse::<f64>() {
+                if self.vfo == 0 {
+                    let _ = tx.send(RIGCTLMessage::UpdateFrequencyA(f));
+                } else {
+                    let _ = tx.send(RIGCTLMessage::UpdateFrequencyB(f));
+                }
+                "RPRT 0\n".to_string()
+            } else {
+                "RPRT -1\n".to_string()
+            }
+        }
+        "get_mode" | "m" => {
+            let r = radio_mutex.radio.lock().unwrap();
+            let rx = self.vfo;
+            format!("{}\n{}\n", Self::mode_to_hamlib(r.receiver[rx].mode), r.receiver_passband(rx) as i32)
+        }
+        "set_mode" | "M" => {
+            let mode = match args.first().and_then(|m| Self::mode_from_hamlib(m)) {
+                Some(mode) => mode,
+                None => return "RPRT -1\n".to_string(),
+            };
+            // 0 = default passband for the mode, -1 = leave unchanged
+            let passband = args.get(1).and_then(|p| p.parse::<f64>().ok()).unwrap_or(0.0);
+            let _ = tx.send(RIGCTLMessage::UpdateMode(self.vfo, mode, passband));
+            "RPRT 0\n".to_string()
+        }
+        "get_ptt" | "t" => {
             let r = radio_mutex.radio.lock().unwrap();
             format!("{}\n", if r.is_transmitting() { 1 } else { 0 })
         }
-        "get_vfo" | "v" | "V" => {
-            // return current vfo - currently always VFOA
-            "VFOA\n".to_string()
+        "set_ptt" | "T" => {
+            let val = args.first().unwrap_or(&"0");
+            if *val != "0" {
+                let mut r = radio_mutex.radio.lock().unwrap();
+                if !r.check_transmit() {
+                    return "RPRT -9\n".to_string(); // rejected
+                }
+            }
+            let _ = tx.send(RIGCTLMessage::UpdateMox(*val != "0"));
+            "RPRT 0\n".to_string()
+        }
+        "get_vfo" | "v" => {
+            format!("{}\n", Self::vfo_name(self.vfo))
         }
         "set_vfo" | "V" => {
-            // set the current vfo - currently always set VFOA
+            match args.first().and_then(|v| Self::vfo_from_name(v)) {
+                Some(vfo) => {
+                    self.vfo = vfo;
+                    "RPRT 0\n".to_string()
+                }
+                None => "RPRT -1\n".to_string(),
+            }
+        }
+        "get_split_vfo" | "s" => {
+            let r = radio_mutex.radio.lock().unwrap();
+            if r.split {
+                "1\nVFOB\n".to_string()
+            } else {
+                "0\nVFOA\n".to_string()
+            }
+        }
+        "set_split_vfo" | "S" => {
+            let val = args.first().unwrap_or(&"0");
+            let _ = tx.send(RIGCTLMessage::UpdateSplit(*val == "1"));
             "RPRT 0\n".to_string()
         }
-        c if c.starts_with("set_freq") || c.starts_with('F') => {
-            // Use split_whitespace to get the actual number regardless of prefix length
-            let val = c.split_whitespace().last().unwrap_or("0");
+        "get_split_freq" | "i" => {
+            // the transmit frequency when split is VFO B
+            let r = radio_mutex.radio.lock().unwrap();
+            let freq = if r.receiver[1].ctun { r.receiver[1].ctun_frequency } else { r.receiver[1].frequency };
+            format!("{}\n", freq as u64)
+        }
+        "set_split_freq" | "I" => {
+            let val = args.first().unwrap_or(&"0");
             if let Ok(f) = val.parse::<f64>() {
-                let _ = tx.send(RIGCTLMessage::UpdateFrequencyA(f));
+                let _ = tx.send(RIGCTLMessage::UpdateFrequencyB(f));
                 "RPRT 0\n".to_string()
             } else {
                 "RPRT -1\n".to_string()
             }
         }
-        c if c.starts_with("set_ptt") || c.starts_with('T') => {
-            let val = c.split_whitespace().last().unwrap_or("0");
-            let _ = tx.send(RIGCTLMessage::UpdateMox(val == "1"));
-            "RPRT 0\n".to_string()
+        "get_level" | "l" => {
+            match args.first() {
+                Some(level) => self.get_level(level, radio_mutex),
+                None => "RPRT -1\n".to_string(),
+            }
+        }
+        "set_level" | "L" => {
+            match (args.first(), args.get(1)) {
+                (Some(level), Some(value)) => self.set_level(level, value, &tx),
+                _ => "RPRT -1\n".to_string(),
+            }
+        }
+        "get_func" | "u" => {
+            match args.first() {
+                Some(func) => self.get_func(func, radio_mutex),
+                None => "RPRT -1\n".to_string(),
+            }
+        }
+        "set_func" | "U" => {
+            match (args.first(), args.get(1)) {
+                (Some(func), Some(value)) => self.set_func(func, value, &tx),
+                _ => "RPRT -1\n".to_string(),
+            }
+        }
+        "get_mem" | "e" => {
+            let r = radio_mutex.radio.lock().unwrap();
+            format!("{}\n", r.memories.channel)
+        }
+        "set_mem" | "E" => {
+            let mut r = radio_mutex.radio.lock().unwrap();
+            match args.first().and_then(|c| c.parse::<usize>().ok()) {
+                Some(channel) if r.memories.get(channel).is_some() => {
+                    r.memories.channel = channel;
+                    let _ = tx.send(RIGCTLMessage::RecallMemory(self.vfo, channel));
+                    "RPRT 0\n".to_string()
+                }
+                _ => "RPRT -1\n".to_string(),
+            }
+        }
+        "vfo_op" | "G" => {
+            let channel = radio_mutex.radio.lock().unwrap().memories.channel;
+            match args.first() {
+                Some(&"TO_VFO") => {
+                    let _ = tx.send(RIGCTLMessage::RecallMemory(self.vfo, channel));
+                    "RPRT 0\n".to_string()
+                }
+                Some(&"FROM_VFO") => {
+                    let _ = tx.send(RIGCTLMessage::StoreMemory(self.vfo, channel));
+                    "RPRT 0\n".to_string()
+                }
+                _ => "RPRT -11\n".to_string(),
+            }
         }
         "chk_vfo" => "CHKVFO 0\n".to_string(),
         _ => "RPRT 0\n".to_string(), // Better to return 0 (Success) than -4 (Error) for stability
+    };
+
+    // a VFO given with the command only applies to that command
+    if name != "V" && name != "set_vfo" {
+        self.vfo = current_vfo;
     }
+    reply
 }
 
 /*
@@ -0,0 +1,333 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+const DEBUG_SCANNER: bool = false;
+
+// how often the main loop calls Radio::scan
+pub const SCAN_TICK_MS: u64 = 50;
+
+// time for the S meter average to settle after a retune
+const SCAN_SETTLE_MS: u64 = 150;
+
+fn default_fm_level() -> f64 {
+    -115.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScanMode {
+    Band,   // step through the current band
+    Memory, // step through the current memory bank
+}
+
+impl ScanMode {
+    pub fn from_usize(value: usize) -> Option<Self> {
+        match value {
+            0 => Some(ScanMode::Band),
+            1 => Some(ScanMode::Memory),
+            _ => None,
+        }
+    }
+
+    pub fn to_usize(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScanStop {
+    Squelch, // stop when the receiver squelch would open, fm_level in FM
+    Level,   // stop when the signal exceeds the threshold
+}
+
+impl ScanStop {
+    pub fn from_usize(value: usize) -> Option<Self> {
+        match value {
+            0 => Some(ScanStop::Squelch),
+            1 => Some(ScanStop::Level),
+            _ => None,
+        }
+    }
+
+    pub fn to_usize(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScanState {
+    #[default]
+    Stopped,
+    Scanning, // listening on a channel for the dwell time
+    Signal,   // stopped on a signal
+    Hang,     // signal gone, waiting for the hang time
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanAction {
+    None,
+    Next, // move to the next channel
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scanner {
+    pub mode: ScanMode,
+    pub stop: ScanStop,
+    pub step: f64,      // Hz, band scan
+    pub dwell: u64,     // ms on each channel
+    pub hang: u64,      // ms after the signal goes before resuming
+    pub threshold: f64, // dBm for ScanStop::Level
+#[serde(default = "default_fm_level")]
+    pub fm_level: f64,  // dBm for ScanStop::Squelch in FM
+#[serde(skip_serializing, skip_deserializing)]
+    pub rx: usize,
+#[serde(skip_serializing, skip_deserializing)]
+    pub state: ScanState,
+#[serde(skip_serializing, skip_deserializing)]
+    since: Option<Instant>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+
+    pub fn new() -> Self {
+        let mode = ScanMode::Band;
+        let stop = ScanStop::Squelch;
+        let step = 5000.0;
+        let dwell = 500;
+        let hang = 3000;
+        let threshold = -100.0;
+        let fm_level = default_fm_level();
+        let rx = 0;
+        let state = ScanState::Stopped;
+        let since = None;
+
+        Scanner {
+            mode,
+            stop,
+            step,
+            dwell,
+            hang,
+            threshold,
+            fm_level,
+            rx,
+            state,
+            since,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state != ScanState::Stopped
+    }
+
+    pub fn start(&mut self, rx: usize) {
+        self.rx = rx;
+        self.set_state(ScanState::Scanning);
+    }
+
+    pub fn stop(&mut self) {
+        self.set_state(ScanState::Stopped);
+    }
+
+    // the receiver has been moved to the next channel
+    pub fn retuned(&mut self) {
+        self.set_state(ScanState::Scanning);
+    }
+
+    fn set_state(&mut self, state: ScanState) {
+        if DEBUG_SCANNER {
+            eprintln!("Scanner: {:?} -> {:?}", self.state, state);
+        }
+        self.state = state;
+        self.since = Some(Instant::now());
+    }
+
+    fn elapsed(&self, ms: u64) -> bool {
+        match self.since {
+            Some(since) => since.elapsed() >= Duration::from_millis(ms),
+            None => true,
+        }
+    }
+
+    // the level that counts as a signal, FM squelch gates on noise and has no level,
+    // AM squelch has none when it is off
+    pub fn stop_level(&self, fm: bool, squelch_level: Option<f64>) -> f64 {
+        match self.stop {
+            ScanStop::Squelch if fm => self.fm_level,
+            ScanStop::Squelch => squelch_level.unwrap_or(self.threshold),
+            ScanStop::Level => self.threshold,
+        }
+    }
+
+    // advance the scan given the signal level and the level that counts as a signal
+    pub fn tick(&mut self, dbm: f64, stop_dbm: f64) -> ScanAction {
+        let open = dbm > stop_dbm;
+        match self.state {
+            ScanState::Stopped => ScanAction::None,
+            ScanState::Scanning => {
+                if open && self.elapsed(SCAN_SETTLE_MS) {
+                    self.set_state(ScanState::Signal);
+                    ScanAction::None
+                } else if self.elapsed(self.dwell.max(SCAN_SETTLE_MS)) {
+                    ScanAction::Next
+                } else {
+                    ScanAction::None
+                }
+            }
+            ScanState::Signal => {
+                if !open {
+                    self.set_state(ScanState::Hang);
+                }
+                ScanAction::None
+            }
+            ScanState::Hang => {
+                if open {
+                    self.set_state(ScanState::Signal);
+                    ScanAction::None
+                } else if self.elapsed(self.hang) {
+                    ScanAction::Next
+                } else {
+                    ScanAction::None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STOP_DBM: f64 = -100.0;
+
+    // pretend the current state started ms ago
+    fn age(scanner: &mut Scanner, ms: u64) {
+        scanner.since = Instant::now().checked_sub(Duration::from_millis(ms));
+    }
+
+    fn scanning(dwell: u64, hang: u64) -> Scanner {
+        let mut scanner = Scanner::new();
+        scanner.dwell = dwell;
+        scanner.hang = hang;
+        scanner.start(1);
+        scanner
+    }
+
+    #[test]
+    fn stopped_does_nothing() {
+        let mut scanner = Scanner::new();
+        assert!(!scanner.is_running());
+        assert_eq!(scanner.tick(-50.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Stopped);
+    }
+
+    #[test]
+    fn moves_on_after_the_dwell_time() {
+        let mut scanner = scanning(500, 3000);
+        assert!(scanner.is_running());
+        assert_eq!(scanner.rx, 1);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+        age(&mut scanner, 500);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::Next);
+        scanner.retuned();
+        assert_eq!(scanner.state, ScanState::Scanning);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+    }
+
+    #[test]
+    fn dwell_is_at_least_the_settle_time() {
+        let mut scanner = scanning(10, 3000);
+        age(&mut scanner, 20);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+        age(&mut scanner, SCAN_SETTLE_MS);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::Next);
+    }
+
+    #[test]
+    fn ignores_a_signal_until_settled() {
+        let mut scanner = scanning(500, 3000);
+        assert_eq!(scanner.tick(-50.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Scanning);
+        age(&mut scanner, SCAN_SETTLE_MS);
+        assert_eq!(scanner.tick(-50.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Signal);
+    }
+
+    #[test]
+    fn stays_on_a_signal_then_hangs() {
+        let mut scanner = scanning(500, 3000);
+        age(&mut scanner, SCAN_SETTLE_MS);
+        scanner.tick(-50.0, STOP_DBM);
+        age(&mut scanner, 60000);
+        assert_eq!(scanner.tick(-50.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Signal);
+
+        // signal gone, reopening during the hang time keeps the channel
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Hang);
+        age(&mut scanner, 1000);
+        assert_eq!(scanner.tick(-50.0, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Signal);
+
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+        age(&mut scanner, 2999);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::None);
+        age(&mut scanner, 3000);
+        assert_eq!(scanner.tick(-120.0, STOP_DBM), ScanAction::Next);
+    }
+
+    #[test]
+    fn stop_level_is_exclusive() {
+        let mut scanner = scanning(500, 3000);
+        age(&mut scanner, SCAN_SETTLE_MS);
+        assert_eq!(scanner.tick(STOP_DBM, STOP_DBM), ScanAction::None);
+        assert_eq!(scanner.state, ScanState::Scanning);
+        scanner.stop();
+        assert!(!scanner.is_running());
+    }
+
+    #[test]
+    fn stop_level_by_mode() {
+        let mut scanner = Scanner::new();
+        assert_eq!(scanner.stop_level(false, Some(-90.0)), -90.0);
+        assert_eq!(scanner.stop_level(false, None), scanner.threshold);
+        assert_eq!(scanner.stop_level(true, None), scanner.fm_level);
+        scanner.stop = ScanStop::Level;
+        assert_eq!(scanner.stop_level(false, Some(-90.0)), scanner.threshold);
+        assert_eq!(scanner.stop_level(true, None), scanner.threshold);
+    }
+
+    #[test]
+    fn mode_and_stop_indexes() {
+        for mode in [ScanMode::Band, ScanMode::Memory] {
+            assert_eq!(ScanMode::from_usize(mode.to_usize()), Some(mode));
+        }
+        for stop in [ScanStop::Squelch, ScanStop::Level] {
+            assert_eq!(ScanStop::from_usize(stop.to_usize()), Some(stop));
+        }
+        assert_eq!(ScanMode::from_usize(2), None);
+        assert_eq!(ScanStop::from_usize(2), None);
+    }
+}
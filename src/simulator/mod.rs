@@ -0,0 +1,962 @@
+/*
+    Copyright (C) 2025  John Melton G0ORX/N6LYT
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*
+    Software simulation of an Open HPSDR radio so that discovery and the
+    protocol code can be exercised without any hardware connected.
+
+    The Protocol 1 simulator behaves like a Metis/Hermes board. It answers
+    the 0xEFFE discovery packet, handles the start/stop command and streams
+    EP6 frames containing a synthetic carrier plus noise for each receiver.
+    The C0..C4 control bytes in the EP2 frames sent to it are decoded so the
+    sample rate, number of receivers and receiver frequencies follow what
+    Protocol1 asks for.
+
+    The Protocol 2 simulator behaves like an Orion/Saturn class board. It
+    listens on the standard ports for the general (1024), receive specific
+    (1025), transmit specific (1026), high priority (1027), audio (1028) and
+    transmit IQ (1029) packets, streams IQ for each enabled DDC from ports
+    1035 onwards, microphone samples from port 1026 and the high priority
+    status (PTT, forward/reverse power and supply volts) from port 1025.
+*/
+
+use std::f64::consts::PI;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::time::{Duration, Instant};
+
+const DEBUG_SIMULATOR: bool = false;
+
+const OZY_BUFFER_SIZE: usize = 512;
+const METIS_BUFFER_SIZE: usize = (OZY_BUFFER_SIZE * 2) + 8;
+const SYNC: u8 = 0x7F;
+const MAX_RECEIVERS: usize = 8;
+
+const P2_GENERAL_PORT: u16 = 1024;
+const P2_RECEIVE_SPECIFIC_PORT: u16 = 1025;
+const P2_TRANSMIT_SPECIFIC_PORT: u16 = 1026;
+const P2_HIGH_PRIORITY_PORT: u16 = 1027;
+const P2_AUDIO_PORT: u16 = 1028;
+const P2_TX_IQ_PORT: u16 = 1029;
+const P2_DDC_PORT: u16 = 1035;
+const P2_DDC_SAMPLES: usize = 238; // 238 24 bit IQ samples per packet
+const P2_MIC_SAMPLES: usize = 64;
+const P2_STATUS_INTERVAL: Duration = Duration::from_millis(50);
+
+// simple noise source so we do not need an extra crate
+#[derive(Clone, Debug)]
+pub struct Noise {
+    state: u64,
+}
+
+impl Noise {
+
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { 0x2545F4914F6CDD1D } else { seed };
+        Noise {
+            state,
+        }
+    }
+
+    // uniform sample in the range -1.0..1.0
+    pub fn uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        ((self.state >> 11) as f64 / (1u64 << 53) as f64) * 2.0 - 1.0
+    }
+}
+
+// a carrier at an absolute frequency that is mixed into every receiver that can see it
+#[derive(Clone, Debug)]
+pub struct Carrier {
+    pub frequency: f64,
+    pub level: f64, // dBFS
+}
+
+impl Carrier {
+
+    pub fn new(frequency: f64, level: f64) -> Self {
+        Carrier {
+            frequency,
+            level,
+        }
+    }
+}
+
+// generates the IQ samples for one receiver
+#[derive(Clone, Debug)]
+pub struct SignalGenerator {
+    pub frequency: f64,
+    pub sample_rate: f64,
+    phase: Vec<f64>,
+    noise: Noise,
+}
+
+impl SignalGenerator {
+
+    pub fn new(frequency: f64, sample_rate: f64, seed: u64) -> Self {
+        let phase = Vec::new();
+        let noise = Noise::new(seed);
+        SignalGenerator {
+            frequency,
+            sample_rate,
+            phase,
+            noise,
+        }
+    }
+
+    // returns the next I and Q samples in the range -1.0..1.0
+    pub fn next_sample(&mut self, carriers: &[Carrier], noise_level: f64) -> (f64, f64) {
+        if self.phase.len() != carriers.len() {
+            self.phase = vec![0.0; carriers.len()];
+        }
+        let noise_amplitude = 10.0_f64.powf(noise_level / 20.0);
+        let mut i_sample = self.noise.uniform() * noise_amplitude;
+        let mut q_sample = self.noise.uniform() * noise_amplitude;
+        for (c, carrier) in carriers.iter().enumerate() {
+            let offset = carrier.frequency - self.frequency;
+            if offset.abs() < self.sample_rate / 2.0 {
+                let amplitude = 10.0_f64.powf(carrier.level / 20.0);
+                i_sample += amplitude * self.phase[c].cos();
+                q_sample += amplitude * self.phase[c].sin();
+                self.phase[c] += 2.0 * PI * offset / self.sample_rate;
+                if self.phase[c] > PI {
+                    self.phase[c] -= 2.0 * PI;
+                } else if self.phase[c] < -PI {
+                    self.phase[c] += 2.0 * PI;
+                }
+            }
+        }
+        (i_sample.clamp(-1.0, 1.0), q_sample.clamp(-1.0, 1.0))
+    }
+}
+
+// write a sample as a signed 24 bit big endian value
+pub fn put_24_bit(buffer: &mut [u8], offset: usize, sample: f64) {
+    let value = (sample * 8388607.0) as i32;
+    buffer[offset] = (value >> 16) as u8;
+    buffer[offset+1] = (value >> 8) as u8;
+    buffer[offset+2] = value as u8;
+}
+
+pub struct Protocol1Simulator {
+    pub address: String,
+    pub board: u8,
+    pub version: u8,
+    pub mac: [u8; 6],
+    pub carriers: Vec<Carrier>,
+    pub noise_level: f64,
+    running: bool,
+    client: Option<SocketAddr>,
+    sample_rate: i32,
+    receivers: usize,
+    ptt: bool,
+    drive: u8,
+    tx_frequency: i32,
+    generators: Vec<SignalGenerator>,
+    send_sequence: u32,
+    status_address: u8,
+}
+
+impl Protocol1Simulator {
+
+    pub fn new(address: String) -> Self {
+        let board: u8 = 1; // Hermes
+        let version: u8 = 32;
+        let mac: [u8; 6] = [0x00, 0x1C, 0xC0, 0xA2, 0x13, 0xDD];
+        let carriers = vec![Carrier::new(14200000.0, -73.0), Carrier::new(7100000.0, -93.0)];
+        let noise_level: f64 = -120.0;
+        let running = false;
+        let client = None;
+        let sample_rate: i32 = 48000;
+        let receivers: usize = 1;
+        let ptt = false;
+        let drive: u8 = 0;
+        let tx_frequency: i32 = 0;
+        let mut generators = Vec::new();
+        for i in 0..MAX_RECEIVERS {
+            generators.push(SignalGenerator::new(14200000.0, sample_rate as f64, (i + 1) as u64));
+        }
+        let send_sequence: u32 = 0;
+        let status_address: u8 = 0;
+        Protocol1Simulator {
+            address,
+            board,
+            version,
+            mac,
+            carriers,
+            noise_level,
+            running,
+            client,
+            sample_rate,
+            receivers,
+            ptt,
+            drive,
+            tx_frequency,
+            generators,
+            send_sequence,
+            status_address,
+        }
+    }
+
+    pub fn set_board(&mut self, board: u8) {
+        self.board = board;
+    }
+
+    pub fn run(&mut self, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
+        let socket = UdpSocket::bind(&self.address)?;
+        socket.set_broadcast(true)?;
+        socket.set_read_timeout(Some(Duration::from_millis(1)))?;
+        eprintln!("Protocol1Simulator: listening on {}", self.address);
+
+        let mut buffer = vec![0u8; 2048];
+        let mut start_time = Instant::now();
+        let mut samples_sent: u64 = 0;
+
+        while !stop_flag.load(Ordering::Relaxed) {
+            match socket.recv_from(&mut buffer) {
+                Ok((size, src)) => {
+                    self.process_packet(&socket, &buffer[..size], src);
+                    if self.running && samples_sent == 0 {
+                        start_time = Instant::now();
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                }
+                Err(e) => {
+                    eprintln!("Protocol1Simulator: recv error {}", e);
+                }
+            }
+
+            if self.running {
+                // keep the output at the requested sample rate
+                let iq_samples = ((OZY_BUFFER_SIZE - 8) / ((self.receivers * 6) + 2)) as u64;
+                let expected = (start_time.elapsed().as_secs_f64() * self.sample_rate as f64) as u64;
+                while samples_sent + (iq_samples * 2) <= expected {
+                    if let Some(client) = self.client {
+                        let frame = self.build_ep6_frame();
+                        if let Err(e) = socket.send_to(&frame, client) {
+                            eprintln!("Protocol1Simulator: send error {}", e);
+                        }
+                    }
+                    samples_sent += iq_samples * 2;
+                }
+            } else {
+                samples_sent = 0;
+            }
+        }
+        eprintln!("Protocol1Simulator: exiting");
+        Ok(())
+    }
+
+    fn process_packet(&mut self, socket: &UdpSocket, buffer: &[u8], src: SocketAddr) {
+        if buffer.len() < 4 || buffer[0] != 0xEF || buffer[1] != 0xFE {
+            if DEBUG_SIMULATOR {eprintln!("Protocol1Simulator: bad packet from {}", src);}
+            return;
+        }
+        match buffer[2] {
+            0x01 => { // EP2 data
+                if buffer[3] == 0x02 && buffer.len() >= METIS_BUFFER_SIZE {
+                    self.process_ozy_buffer(&buffer[8..520]);
+                    self.process_ozy_buffer(&buffer[520..1032]);
+                }
+                },
+            0x02 => { // discovery
+                let mut reply = [0u8; 60];
+                reply[0] = 0xEF;
+                reply[1] = 0xFE;
+                reply[2] = if self.running { 3 } else { 2 };
+                reply[3..9].copy_from_slice(&self.mac);
+                reply[9] = self.version;
+                reply[10] = self.board;
+                if self.board == 6 {
+                    reply[19] = 4; // HL2 receivers
+                }
+                if DEBUG_SIMULATOR {eprintln!("Protocol1Simulator: discovery from {}", src);}
+                if let Err(e) = socket.send_to(&reply, src) {
+                    eprintln!("Protocol1Simulator: discovery reply error {}", e);
+                }
+                },
+            0x04 => { // start/stop
+                if buffer[3] & 0x01 == 0x01 {
+                    eprintln!("Protocol1Simulator: start from {}", src);
+                    self.client = Some(src);
+                    self.running = true;
+                    self.send_sequence = 0;
+                } else {
+                    eprintln!("Protocol1Simulator: stop from {}", src);
+                    self.running = false;
+                }
+                },
+            _ => {
+                if DEBUG_SIMULATOR {eprintln!("Protocol1Simulator: unexpected packet type {}", buffer[2]);}
+                },
+        }
+    }
+
+    fn process_ozy_buffer(&mut self, buffer: &[u8]) {
+        if buffer[0] != SYNC || buffer[1] != SYNC || buffer[2] != SYNC {
+            eprintln!("Protocol1Simulator: lost sync");
+            return;
+        }
+        let c0 = buffer[3];
+        let c1 = buffer[4];
+        let c2 = buffer[5];
+        let c3 = buffer[6];
+        let c4 = buffer[7];
+
+        self.ptt = (c0 & 0x01) == 0x01;
+        let f = i32::from_be_bytes([c1, c2, c3, c4]);
+        match c0 & 0xFE {
+            0x00 => {
+                let sample_rate = match c1 & 0x03 {
+                    0 => 48000,
+                    1 => 96000,
+                    2 => 192000,
+                    _ => 384000,
+                };
+                let receivers = (((c4 >> 3) & 0x07) + 1) as usize;
+                if sample_rate != self.sample_rate || receivers != self.receivers {
+                    if DEBUG_SIMULATOR {eprintln!("Protocol1Simulator: sample_rate={} receivers={}", sample_rate, receivers);}
+                    self.sample_rate = sample_rate;
+                    self.receivers = receivers;
+                    for generator in self.generators.iter_mut() {
+                        generator.sample_rate = sample_rate as f64;
+                    }
+                }
+                },
+            0x02 => {
+                self.tx_frequency = f;
+                },
+            0x04 | 0x06 | 0x08 | 0x0A | 0x0C | 0x0E | 0x10 => {
+                let rx = ((c0 & 0xFE) - 0x04) as usize / 2;
+                self.generators[rx].frequency = f as f64;
+                },
+            0x12 => {
+                self.drive = c1;
+                },
+            _ => {
+                },
+        }
+    }
+
+    fn build_ep6_frame(&mut self) -> Vec<u8> {
+        let mut frame = vec![0u8; METIS_BUFFER_SIZE];
+        frame[0] = 0xEF;
+        frame[1] = 0xFE;
+        frame[2] = 0x01;
+        frame[3] = 0x06; // EP6
+        frame[4..8].copy_from_slice(&self.send_sequence.to_be_bytes());
+        self.send_sequence = self.send_sequence.wrapping_add(1);
+        self.fill_ozy_buffer(&mut frame[8..520]);
+        self.fill_ozy_buffer(&mut frame[520..1032]);
+        frame
+    }
+
+    fn fill_ozy_buffer(&mut self, buffer: &mut [u8]) {
+        buffer[0] = SYNC;
+        buffer[1] = SYNC;
+        buffer[2] = SYNC;
+
+        // rotate through the status addresses like a real radio
+        let mut c0 = self.status_address << 3;
+        let mut c1: u8 = 0;
+        let mut c2: u8 = 0;
+        let mut c3: u8 = 0;
+        let mut c4: u8 = 0;
+        if self.ptt {
+            c0 |= 0x01;
+        }
+        match self.status_address {
+            0 => {
+                c4 = self.version;
+                },
+            1 => {
+                // exciter and forward power follow the drive level when transmitting
+                let power: u16 = if self.ptt { self.drive as u16 * 8 } else { 0 };
+                c1 = (power >> 8) as u8;
+                c2 = power as u8;
+                c3 = (power >> 8) as u8;
+                c4 = power as u8;
+                },
+            2 => {
+                let power: u16 = if self.ptt { self.drive as u16 / 4 } else { 0 };
+                c1 = (power >> 8) as u8;
+                c2 = power as u8;
+                },
+            _ => {
+                },
+        }
+        self.status_address += 1;
+        if self.status_address > 4 {
+            self.status_address = 0;
+        }
+        buffer[3] = c0;
+        buffer[4] = c1;
+        buffer[5] = c2;
+        buffer[6] = c3;
+        buffer[7] = c4;
+
+        let iq_samples = (OZY_BUFFER_SIZE - 8) / ((self.receivers * 6) + 2);
+        let mut b = 8;
+        for _s in 0..iq_samples {
+            for rx in 0..self.receivers {
+                let (i_sample, q_sample) = self.generators[rx].next_sample(&self.carriers, self.noise_level);
+                put_24_bit(buffer, b, i_sample);
+                b += 3;
+                put_24_bit(buffer, b, q_sample);
+                b += 3;
+            }
+            // silent microphone
+            buffer[b] = 0;
+            buffer[b+1] = 0;
+            b += 2;
+        }
+    }
+
+}
+
+pub struct Protocol2Simulator {
+    pub address: String,
+    pub board: u8,
+    pub version: u8,
+    pub mac: [u8; 6],
+    pub carriers: Vec<Carrier>,
+    pub noise_level: f64,
+    pub supply_volts: u16,
+    running: bool,
+    client: Option<SocketAddr>,
+    ddc_enabled: Vec<bool>,
+    ddc_sample_rate: Vec<i32>,
+    ddc_sequence: Vec<u32>,
+    ddc_samples_sent: Vec<u64>,
+    generators: Vec<SignalGenerator>,
+    ptt: bool,
+    drive: u8,
+    tx_frequency: f64,
+    cw_keyer_speed: u8,
+    status_sequence: u32,
+    mic_sequence: u32,
+    tx_iq_packets: u32,
+}
+
+impl Protocol2Simulator {
+
+    pub fn new(address: String) -> Self {
+        let board: u8 = 5; // Orion2
+        let version: u8 = 21;
+        let mac: [u8; 6] = [0x00, 0x1C, 0xC0, 0xA2, 0x14, 0xDD];
+        let carriers = vec![Carrier::new(14200000.0, -73.0), Carrier::new(7100000.0, -93.0)];
+        let noise_level: f64 = -120.0;
+        let supply_volts: u16 = 3200;
+        let running = false;
+        let client = None;
+        let ddc_enabled = vec![false; MAX_RECEIVERS];
+        let ddc_sample_rate = vec![48000; MAX_RECEIVERS];
+        let ddc_sequence = vec![0; MAX_RECEIVERS];
+        let ddc_samples_sent = vec![0; MAX_RECEIVERS];
+        let mut generators = Vec::new();
+        for i in 0..MAX_RECEIVERS {
+            generators.push(SignalGenerator::new(14200000.0, 48000.0, (i + 1) as u64));
+        }
+        let ptt = false;
+        let drive: u8 = 0;
+        let tx_frequency: f64 = 0.0;
+        let cw_keyer_speed: u8 = 0;
+        let status_sequence: u32 = 0;
+        let mic_sequence: u32 = 0;
+        let tx_iq_packets: u32 = 0;
+        Protocol2Simulator {
+            address,
+            board,
+            version,
+            mac,
+            carriers,
+            noise_level,
+            supply_volts,
+            running,
+            client,
+            ddc_enabled,
+            ddc_sample_rate,
+            ddc_sequence,
+            ddc_samples_sent,
+            generators,
+            ptt,
+            drive,
+            tx_frequency,
+            cw_keyer_speed,
+            status_sequence,
+            mic_sequence,
+            tx_iq_packets,
+        }
+    }
+
+    pub fn set_board(&mut self, board: u8) {
+        self.board = board;
+    }
+
+    fn bind(&self, port: u16) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind(format!("{}:{}", self.address, port))?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+
+    pub fn run(&mut self, stop_flag: Arc<AtomicBool>) -> io::Result<()> {
+        // the radio sends each stream from its own port so we need a socket per port
+        let general_socket = self.bind(P2_GENERAL_PORT)?;
+        general_socket.set_broadcast(true)?;
+        let receive_specific_socket = self.bind(P2_RECEIVE_SPECIFIC_PORT)?;
+        let transmit_specific_socket = self.bind(P2_TRANSMIT_SPECIFIC_PORT)?;
+        let high_priority_socket = self.bind(P2_HIGH_PRIORITY_PORT)?;
+        let audio_socket = self.bind(P2_AUDIO_PORT)?;
+        let tx_iq_socket = self.bind(P2_TX_IQ_PORT)?;
+        let mut ddc_sockets = Vec::new();
+        for i in 0..MAX_RECEIVERS {
+            ddc_sockets.push(self.bind(P2_DDC_PORT + i as u16)?);
+        }
+        eprintln!("Protocol2Simulator: listening on {}", self.address);
+
+        let mut buffer = vec![0u8; 2048];
+        let mut start_time = Instant::now();
+        let mut last_status = Instant::now();
+        let mut mic_samples_sent: u64 = 0;
+
+        while !stop_flag.load(Ordering::Relaxed) {
+            let mut idle = true;
+
+            while let Ok((size, src)) = general_socket.recv_from(&mut buffer) {
+                idle = false;
+                self.process_general(&general_socket, &buffer[..size], src);
+            }
+            while let Ok((size, _src)) = receive_specific_socket.recv_from(&mut buffer) {
+                idle = false;
+                self.process_receive_specific(&buffer[..size]);
+            }
+            while let Ok((size, _src)) = transmit_specific_socket.recv_from(&mut buffer) {
+                idle = false;
+                self.process_transmit_specific(&buffer[..size]);
+            }
+            while let Ok((size, src)) = high_priority_socket.recv_from(&mut buffer) {
+                idle = false;
+                let was_running = self.running;
+                self.process_high_priority(&buffer[..size], src);
+                if self.running && !was_running {
+                    start_time = Instant::now();
+                    mic_samples_sent = 0;
+                    for i in 0..MAX_RECEIVERS {
+                        self.ddc_samples_sent[i] = 0;
+                        self.ddc_sequence[i] = 0;
+                    }
+                }
+            }
+            while let Ok((_size, _src)) = audio_socket.recv_from(&mut buffer) {
+                idle = false;
+            }
+            while let Ok((_size, _src)) = tx_iq_socket.recv_from(&mut buffer) {
+                idle = false;
+                self.tx_iq_packets = self.tx_iq_packets.wrapping_add(1);
+            }
+
+            // only while running and once the general packet has given us the client
+            let client = if self.running { self.client } else { None };
+            if let Some(client) = client {
+                let elapsed = start_time.elapsed().as_secs_f64();
+
+                // IQ samples for each enabled DDC
+                for (ddc, ddc_socket) in ddc_sockets.iter().enumerate() {
+                    if !self.ddc_enabled[ddc] {
+                        continue;
+                    }
+                    let expected = (elapsed * self.ddc_sample_rate[ddc] as f64) as u64;
+                    while self.ddc_samples_sent[ddc] + P2_DDC_SAMPLES as u64 <= expected {
+                        let packet = self.build_ddc_packet(ddc);
+                        if let Err(e) = ddc_socket.send_to(&packet, client) {
+                            eprintln!("Protocol2Simulator: DDC{} send error {}", ddc, e);
+                        }
+                        self.ddc_samples_sent[ddc] += P2_DDC_SAMPLES as u64;
+                        idle = false;
+                    }
+                }
+
+                // microphone samples at 48kHz
+                let expected = (elapsed * 48000.0) as u64;
+                while mic_samples_sent + P2_MIC_SAMPLES as u64 <= expected {
+                    let packet = self.build_mic_packet();
+                    if let Err(e) = transmit_specific_socket.send_to(&packet, client) {
+                        eprintln!("Protocol2Simulator: mic send error {}", e);
+                    }
+                    mic_samples_sent += P2_MIC_SAMPLES as u64;
+                }
+
+                // high priority status
+                if last_status.elapsed() >= P2_STATUS_INTERVAL {
+                    last_status = Instant::now();
+                    let packet = self.build_status_packet();
+                    if let Err(e) = receive_specific_socket.send_to(&packet, client) {
+                        eprintln!("Protocol2Simulator: status send error {}", e);
+                    }
+                }
+            }
+
+            if idle {
+                std::thread::sleep(Duration::from_micros(200));
+            }
+        }
+        eprintln!("Protocol2Simulator: exiting");
+        Ok(())
+    }
+
+    fn process_general(&mut self, socket: &UdpSocket, buffer: &[u8], src: SocketAddr) {
+        if buffer.len() < 60 {
+            return;
+        }
+        match buffer[4] {
+            0x00 => { // general packet
+                if DEBUG_SIMULATOR {eprintln!("Protocol2Simulator: general packet from {}", src);}
+                self.client = Some(src);
+                },
+            0x02 => { // discovery
+                let mut reply = [0u8; 60];
+                reply[4] = if self.running { 3 } else { 2 };
+                reply[5..11].copy_from_slice(&self.mac);
+                reply[11] = self.board;
+                reply[12] = 38; // protocol version
+                reply[13] = self.version;
+                reply[20] = MAX_RECEIVERS as u8;
+                if DEBUG_SIMULATOR {eprintln!("Protocol2Simulator: discovery from {}", src);}
+                if let Err(e) = socket.send_to(&reply, src) {
+                    eprintln!("Protocol2Simulator: discovery reply error {}", e);
+                }
+                },
+            _ => {
+                if DEBUG_SIMULATOR {eprintln!("Protocol2Simulator: unexpected general command {}", buffer[4]);}
+                },
+        }
+    }
+
+    fn process_receive_specific(&mut self, buffer: &[u8]) {
+        if buffer.len() < 17 + (MAX_RECEIVERS * 6) {
+            return;
+        }
+        for ddc in 0..MAX_RECEIVERS {
+            self.ddc_enabled[ddc] = (buffer[7] >> ddc) & 0x01 == 0x01;
+            let rate = u16::from_be_bytes([buffer[18+(ddc*6)], buffer[19+(ddc*6)]]) as i32;
+            if rate != 0 && rate * 1000 != self.ddc_sample_rate[ddc] {
+                if DEBUG_SIMULATOR {eprintln!("Protocol2Simulator: DDC{} sample_rate={}", ddc, rate * 1000);}
+                self.ddc_sample_rate[ddc] = rate * 1000;
+                self.generators[ddc].sample_rate = (rate * 1000) as f64;
+                self.ddc_samples_sent[ddc] = 0;
+            }
+        }
+    }
+
+    fn process_transmit_specific(&mut self, buffer: &[u8]) {
+        if buffer.len() < 60 {
+            return;
+        }
+        self.cw_keyer_speed = buffer[9];
+    }
+
+    fn process_high_priority(&mut self, buffer: &[u8], src: SocketAddr) {
+        if buffer.len() < 1444 {
+            return;
+        }
+        let running = buffer[4] & 0x01 == 0x01;
+        if running != self.running {
+            eprintln!("Protocol2Simulator: {} from {}", if running { "start" } else { "stop" }, src);
+        }
+        self.running = running;
+        self.ptt = buffer[4] & 0x02 == 0x02;
+        for ddc in 0..MAX_RECEIVERS {
+            let phase = u32::from_be_bytes([buffer[9+(ddc*4)], buffer[10+(ddc*4)], buffer[11+(ddc*4)], buffer[12+(ddc*4)]]);
+            self.generators[ddc].frequency = (phase as f64 * 122880000.0) / 4294967296.0;
+        }
+        let phase = u32::from_be_bytes([buffer[329], buffer[330], buffer[331], buffer[332]]);
+        self.tx_frequency = (phase as f64 * 122880000.0) / 4294967296.0;
+        self.drive = buffer[345];
+    }
+
+    fn build_ddc_packet(&mut self, ddc: usize) -> Vec<u8> {
+        let mut packet = vec![0u8; 16 + (P2_DDC_SAMPLES * 6)];
+        packet[0..4].copy_from_slice(&self.ddc_sequence[ddc].to_be_bytes());
+        self.ddc_sequence[ddc] = self.ddc_sequence[ddc].wrapping_add(1);
+        packet[4..12].copy_from_slice(&self.ddc_samples_sent[ddc].to_be_bytes()); // timestamp
+        packet[12..14].copy_from_slice(&24u16.to_be_bytes()); // bits per sample
+        packet[14..16].copy_from_slice(&(P2_DDC_SAMPLES as u16).to_be_bytes());
+        let mut b = 16;
+        for _s in 0..P2_DDC_SAMPLES {
+            let (i_sample, q_sample) = self.generators[ddc].next_sample(&self.carriers, self.noise_level);
+            put_24_bit(&mut packet, b, i_sample);
+            b += 3;
+            put_24_bit(&mut packet, b, q_sample);
+            b += 3;
+        }
+        packet
+    }
+
+    fn build_mic_packet(&mut self) -> Vec<u8> {
+        // silent microphone
+        let mut packet = vec![0u8; 4 + (P2_MIC_SAMPLES * 2)];
+        packet[0..4].copy_from_slice(&self.mic_sequence.to_be_bytes());
+        self.mic_sequence = self.mic_sequence.wrapping_add(1);
+        packet
+    }
+
+    fn build_status_packet(&mut self) -> Vec<u8> {
+        let mut packet = vec![0u8; 60];
+        packet[0..4].copy_from_slice(&self.status_sequence.to_be_bytes());
+        self.status_sequence = self.status_sequence.wrapping_add(1);
+        if self.ptt {
+            packet[4] |= 0x01;
+        }
+        packet[5] |= 0x04; // PLL locked
+        // forward and reverse power follow the drive level when transmitting
+        let forward: u16 = if self.ptt { self.drive as u16 * 8 } else { 0 };
+        let reverse: u16 = if self.ptt { self.drive as u16 / 4 } else { 0 };
+        packet[6..8].copy_from_slice(&forward.to_be_bytes()); // exciter power
+        packet[14..16].copy_from_slice(&forward.to_be_bytes());
+        packet[22..24].copy_from_slice(&reverse.to_be_bytes());
+        packet[49..51].copy_from_slice(&self.supply_volts.to_be_bytes());
+        packet
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const P1_TEST_ADDRESS: &str = "127.0.0.1:51024";
+
+    fn get_24_bit(buffer: &[u8], offset: usize) -> f64 {
+        let value = i32::from_be_bytes([buffer[offset], buffer[offset+1], buffer[offset+2], 0]) >> 8;
+        value as f64 / 8388607.0
+    }
+
+    // average phase step between IQ samples converted to Hz
+    fn frequency_offset(samples: &[(f64, f64)], sample_rate: f64) -> f64 {
+        let mut re = 0.0;
+        let mut im = 0.0;
+        for s in 1..samples.len() {
+            let (i0, q0) = samples[s-1];
+            let (i1, q1) = samples[s];
+            re += i1 * i0 + q1 * q0;
+            im += q1 * i0 - i1 * q0;
+        }
+        im.atan2(re) * sample_rate / (2.0 * PI)
+    }
+
+    fn level(samples: &[(f64, f64)]) -> f64 {
+        let power: f64 = samples.iter().map(|(i, q)| (i * i) + (q * q)).sum::<f64>() / samples.len() as f64;
+        10.0 * power.log10()
+    }
+
+    // control bytes laid out as Protocol1::send_ozy_buffer sends them
+    fn ozy_buffer(buffer: &mut [u8], c0: u8, c1: u8, c2: u8, c3: u8, c4: u8) {
+        buffer[0] = SYNC;
+        buffer[1] = SYNC;
+        buffer[2] = SYNC;
+        buffer[3] = c0;
+        buffer[4] = c1;
+        buffer[5] = c2;
+        buffer[6] = c3;
+        buffer[7] = c4;
+    }
+
+    fn ep2_frame(sequence: u32, first: [u8; 5], second: [u8; 5]) -> Vec<u8> {
+        let mut frame = vec![0u8; METIS_BUFFER_SIZE];
+        frame[0] = 0xEF;
+        frame[1] = 0xFE;
+        frame[2] = 0x01;
+        frame[3] = 0x02; // EP2
+        frame[4..8].copy_from_slice(&sequence.to_be_bytes());
+        ozy_buffer(&mut frame[8..520], first[0], first[1], first[2], first[3], first[4]);
+        ozy_buffer(&mut frame[520..1032], second[0], second[1], second[2], second[3], second[4]);
+        frame
+    }
+
+    fn frequency_command(c0: u8, frequency: i32) -> [u8; 5] {
+        let f = frequency.to_be_bytes();
+        [c0, f[0], f[1], f[2], f[3]]
+    }
+
+    // keep sending the discovery packet until the simulator thread is listening
+    fn discover(socket: &UdpSocket, address: &str, packet: &[u8], buffer: &mut [u8]) -> usize {
+        for _i in 0..50 {
+            socket.send_to(packet, address).unwrap();
+            if let Ok((size, _src)) = socket.recv_from(buffer) {
+                return size;
+            }
+        }
+        panic!("no discovery reply from {}", address);
+    }
+
+    #[test]
+    fn protocol1_discovery_start_and_iq() {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let simulator_stop = stop_flag.clone();
+        let simulator = thread::spawn(move || {
+            let mut simulator = Protocol1Simulator::new(P1_TEST_ADDRESS.to_string());
+            simulator.run(simulator_stop)
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut buffer = vec![0u8; 2048];
+
+        let mut discovery = [0u8; 63];
+        discovery[0] = 0xEF;
+        discovery[1] = 0xFE;
+        discovery[2] = 0x02;
+        let size = discover(&socket, P1_TEST_ADDRESS, &discovery, &mut buffer);
+        assert_eq!(size, 60);
+        assert_eq!(buffer[0..3], [0xEF, 0xFE, 0x02]);
+        assert_eq!(buffer[3..9], [0x00, 0x1C, 0xC0, 0xA2, 0x13, 0xDD]);
+        assert_eq!(buffer[10], 1); // Hermes
+
+        // 48kHz, 2 receivers, RX1 1kHz below the 14.2MHz carrier and RX2 2kHz above the 7.1MHz carrier
+        let config = [0x00, 0x00, 0x00, 0x00, 0x01 << 3];
+        socket.send_to(&ep2_frame(0, config, frequency_command(0x04, 14199000)), P1_TEST_ADDRESS).unwrap();
+        socket.send_to(&ep2_frame(1, config, frequency_command(0x06, 7102000)), P1_TEST_ADDRESS).unwrap();
+        let mut start = [0u8; 64];
+        start[0] = 0xEF;
+        start[1] = 0xFE;
+        start[2] = 0x04;
+        start[3] = 0x01;
+        socket.send_to(&start, P1_TEST_ADDRESS).unwrap();
+
+        let iq_samples = (OZY_BUFFER_SIZE - 8) / ((2 * 6) + 2);
+        let mut rx1: Vec<(f64, f64)> = Vec::new();
+        let mut rx2: Vec<(f64, f64)> = Vec::new();
+        for sequence in 0..20u32 {
+            let (size, _src) = socket.recv_from(&mut buffer).expect("no IQ from the simulator");
+            assert_eq!(size, METIS_BUFFER_SIZE);
+            assert_eq!(buffer[0..4], [0xEF, 0xFE, 0x01, 0x06]);
+            assert_eq!(u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]), sequence);
+            for offset in [8, 520] {
+                assert_eq!(buffer[offset..offset+3], [SYNC, SYNC, SYNC]);
+                let mut b = offset + 8;
+                for _s in 0..iq_samples {
+                    rx1.push((get_24_bit(&buffer, b), get_24_bit(&buffer, b+3)));
+                    rx2.push((get_24_bit(&buffer, b+6), get_24_bit(&buffer, b+9)));
+                    b += 14;
+                }
+            }
+        }
+
+        stop_flag.store(true, Ordering::Relaxed);
+        simulator.join().unwrap().unwrap();
+
+        assert!((frequency_offset(&rx1, 48000.0) - 1000.0).abs() < 5.0);
+        assert!((level(&rx1) + 73.0).abs() < 1.0);
+        assert!((frequency_offset(&rx2, 48000.0) + 2000.0).abs() < 5.0);
+        assert!((level(&rx2) + 93.0).abs() < 1.0);
+    }
+
+    fn phase(frequency: f64) -> [u8; 4] {
+        (((4294967296.0 * frequency) / 122880000.0) as u32).to_be_bytes()
+    }
+
+    #[test]
+    fn protocol2_ddc_ports_and_high_priority_status() {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let simulator_stop = stop_flag.clone();
+        let simulator = thread::spawn(move || {
+            let mut simulator = Protocol2Simulator::new("127.0.0.1".to_string());
+            simulator.run(simulator_stop)
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut buffer = vec![0u8; 2048];
+
+        let general_address = format!("127.0.0.1:{}", P2_GENERAL_PORT);
+        let mut discovery = [0u8; 60];
+        discovery[4] = 0x02;
+        let size = discover(&socket, &general_address, &discovery, &mut buffer);
+        assert_eq!(size, 60);
+        assert_eq!(buffer[4], 0x02);
+        assert_eq!(buffer[5..11], [0x00, 0x1C, 0xC0, 0xA2, 0x14, 0xDD]);
+        assert_eq!(buffer[11], 5); // Orion2
+
+        // packets laid out as Protocol2::send_general, send_receive_specific and send_high_priority send them
+        let general = [0u8; 60];
+        socket.send_to(&general, &general_address).unwrap();
+
+        let mut receive_specific = [0u8; 1444];
+        receive_specific[7] = 0x03; // DDC0 and DDC1
+        for ddc in 0..2 {
+            receive_specific[19+(ddc*6)] = 48; // 48kHz
+            receive_specific[22+(ddc*6)] = 24;
+        }
+        socket.send_to(&receive_specific, format!("127.0.0.1:{}", P2_RECEIVE_SPECIFIC_PORT)).unwrap();
+
+        let mut high_priority = [0u8; 1444];
+        high_priority[4] = 0x03; // running and PTT
+        high_priority[9..13].copy_from_slice(&phase(14199000.0));
+        high_priority[13..17].copy_from_slice(&phase(14201500.0));
+        high_priority[329..333].copy_from_slice(&phase(14200000.0));
+        high_priority[345] = 200; // drive
+        socket.send_to(&high_priority, format!("127.0.0.1:{}", P2_HIGH_PRIORITY_PORT)).unwrap();
+
+        let mut ddc0: Vec<(f64, f64)> = Vec::new();
+        let mut ddc1: Vec<(f64, f64)> = Vec::new();
+        let mut status = None;
+        let mut packets = 0;
+        while (ddc0.len() < 2000 || ddc1.len() < 2000 || status.is_none()) && packets < 1000 {
+            let (size, src) = socket.recv_from(&mut buffer).expect("no data from the simulator");
+            packets += 1;
+            match src.port() {
+                P2_RECEIVE_SPECIFIC_PORT => {
+                    assert_eq!(size, 60);
+                    status = Some(buffer[..size].to_vec());
+                    },
+                P2_TRANSMIT_SPECIFIC_PORT => {
+                    assert_eq!(size, 4 + (P2_MIC_SAMPLES * 2));
+                    },
+                port if port == P2_DDC_PORT || port == P2_DDC_PORT + 1 => {
+                    assert_eq!(size, 16 + (P2_DDC_SAMPLES * 6));
+                    assert_eq!(u16::from_be_bytes([buffer[12], buffer[13]]), 24);
+                    assert_eq!(u16::from_be_bytes([buffer[14], buffer[15]]) as usize, P2_DDC_SAMPLES);
+                    let samples = if port == P2_DDC_PORT { &mut ddc0 } else { &mut ddc1 };
+                    let mut b = 16;
+                    for _s in 0..P2_DDC_SAMPLES {
+                        samples.push((get_24_bit(&buffer, b), get_24_bit(&buffer, b+3)));
+                        b += 6;
+                    }
+                    },
+                port => panic!("unexpected packet from port {}", port),
+            }
+        }
+
+        stop_flag.store(true, Ordering::Relaxed);
+        simulator.join().unwrap().unwrap();
+
+        assert!((frequency_offset(&ddc0, 48000.0) - 1000.0).abs() < 5.0);
+        assert!((frequency_offset(&ddc1, 48000.0) + 1500.0).abs() < 5.0);
+
+        let status = status.expect("no high priority status");
+        assert_eq!(status[4] & 0x01, 0x01); // PTT
+        assert_eq!(status[5] & 0x04, 0x04); // PLL locked
+        assert_eq!(u16::from_be_bytes([status[14], status[15]]), 200 * 8);
+        assert_eq!(u16::from_be_bytes([status[22], status[23]]), 200 / 4);
+        assert_eq!(u16::from_be_bytes([status[49], status[50]]), 3200);
+    }
+}
@@ -17,9 +17,12 @@
 
 use gtk::cairo::{Context, Format, ImageSurface, LineCap, LineJoin, LinearGradient};
 
+use crate::bands::SegmentKind;
 use crate::radio::RadioMutex;
 use crate::util::*;
 
+const SEGMENT_BAR_HEIGHT: i32 = 6;
+
 #[derive(Clone)]
 pub struct Spectrum {
     rx: usize,
@@ -113,6 +116,9 @@ impl Spectrum {
             cr.move_to((width/2).into(), 0.0);
             cr.line_to((width/2).into(), spectrum_height.into());
             cr.stroke().unwrap();
+            if r.transmit_out_of_band() {
+                draw_out_of_band(&cr, (width/2).into(), spectrum_height);
+            }
         } else {
             let b = r.receiver[self.rx].band.to_usize();
             let dbm_per_line: f32 = spectrum_height as f32/(r.receiver[self.rx].band_info[b].spectrum_high-r.receiver[self.rx].band_info[b].spectrum_low);
@@ -160,6 +166,22 @@ impl Spectrum {
                                _ => 25000.0,
                       };
 
+            // shade outside the band plan allocation
+            let band_low = r.receiver[self.rx].band_info[b].low;
+            let band_high = r.receiver[self.rx].band_info[b].high;
+            if r.receiver[self.rx].band_info[b].enabled && band_low < band_high {
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.4);
+                if display_frequency_low < band_low {
+                    let x = ((band_low - display_frequency_low) / display_hz_per_pixel).min(width as f64);
+                    cr.rectangle(0.0, 0.0, x, spectrum_height.into());
+                }
+                if display_frequency_high > band_high {
+                    let x = ((band_high - display_frequency_low) / display_hz_per_pixel).max(0.0);
+                    cr.rectangle(x, 0.0, width as f64 - x, spectrum_height.into());
+                }
+                let _ = cr.fill();
+            }
+
             // draw the band limits
             cr.set_source_rgb(1.0, 0.0, 0.0);
             let dashes = [4.0, 4.0];
@@ -264,6 +286,18 @@ impl Spectrum {
                 f += step;
             }
 
+            // draw the band plan segments along the frequency axis
+            for segment in &r.receiver[self.rx].band_info[b].segments {
+                if segment.high > display_frequency_low && segment.low < display_frequency_high {
+                    let left = ((segment.low - display_frequency_low) / display_hz_per_pixel).max(0.0);
+                    let right = ((segment.high - display_frequency_low) / display_hz_per_pixel).min(width as f64);
+                    let (red, green, blue) = segment_color(segment.kind);
+                    cr.set_source_rgb(red, green, blue);
+                    cr.rectangle(left, (spectrum_height - SEGMENT_BAR_HEIGHT).into(), right - left, SEGMENT_BAR_HEIGHT.into());
+                    let _ = cr.fill();
+                }
+            }
+
             // draw any active notches
             for i in 0..r.notch {
                 let notch = r.notches[i as usize];
@@ -285,6 +319,9 @@ impl Spectrum {
                 cr.move_to(x.into(), 0.0);
                 cr.line_to(x.into(), spectrum_height.into());
                 cr.stroke().unwrap();
+                if r.is_transmitting() && r.transmit_receiver() == self.rx && r.transmit_out_of_band() {
+                    draw_out_of_band(&cr, x, spectrum_height);
+                }
             }
 
         }
@@ -299,3 +336,27 @@ impl Spectrum {
         }
     }
 }
+
+fn segment_color(kind: SegmentKind) -> (f64, f64, f64) {
+    match kind {
+        SegmentKind::Cw => (0.0, 0.6, 1.0),
+        SegmentKind::Digital => (0.0, 0.8, 0.0),
+        SegmentKind::Phone => (1.0, 0.65, 0.0),
+        SegmentKind::Mixed => (0.7, 0.4, 0.9),
+        SegmentKind::Beacon => (1.0, 0.0, 0.0),
+        SegmentKind::Broadcast => (0.6, 0.6, 0.6),
+    }
+}
+
+// highlight the transmit cursor when the transmitted signal is outside the allocation
+fn draw_out_of_band(cr: &Context, x: f64, spectrum_height: i32) {
+    cr.set_source_rgb(1.0, 0.0, 1.0);
+    cr.set_line_width(4.0);
+    cr.move_to(x, 0.0);
+    cr.line_to(x, spectrum_height.into());
+    cr.stroke().unwrap();
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    cr.set_font_size(20.0);
+    cr.move_to(x + 5.0, 40.0);
+    let _ = cr.show_text("OUT OF BAND");
+}
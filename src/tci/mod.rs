@@ -7,12 +7,19 @@ use tokio_tungstenite::accept_async;
 use tokio_tungstenite::tungstenite::protocol::Message;
 use tokio::sync::Semaphore;
 use tokio::sync::Mutex;
+use tokio::sync::broadcast;
 use futures::{StreamExt, SinkExt};
 
 use crate::radio::RadioMutex;
 use crate::discovery::device_name;
 use crate::modes::Modes;
 
+const MAX_TCI_CLIENTS: usize = 8;
+const TCI_STATE_POLL_MS: u64 = 100;
+const TCI_SENSORS_INTERVAL_MS: u64 = 200;
+const TCI_TX_AUDIO_SAMPLES: u32 = 1024;
+const TCI_TX_CHRONO_MS: u64 = 20;
+
 #[derive(Clone, Debug)]
 pub struct TCI {
     addr: String,
@@ -22,15 +29,64 @@ pub enum TCIMessage {
     ClientConnected(),
     ClientDisconnected(),
     UpdateMox(bool),
-    UpdateDDS(f64),
-    UpdateFrequencyA(f64),
+    UpdateDDS(usize, f64),
+    UpdateVFO(usize, f64),
     UpdateAFGain(f32),
     IQStart(usize),
+    UpdateMode(usize, usize),
+    UpdateDrive(f32),
+    UpdateSplit(bool),
+    UpdateSquelch(usize, bool),
+    UpdateSquelchLevel(usize, f64),
+    UpdateANF(usize, bool),
 }
 
+// receiver id and samples
 pub enum TCIDataMessage {
-    IQData(Vec<f64>),
-    AudioData(Vec<f64>),
+    IQData(usize, Vec<f64>),
+    AudioData(usize, Vec<f64>),
+}
+
+// sent to every connected client
+#[derive(Clone)]
+enum TCIBroadcast {
+    Text(String),
+    IQ(usize, Vec<u8>),
+    Audio(usize, Vec<u8>),
+}
+
+// per client settings
+struct TCIClient {
+    iq: Vec<bool>,
+    audio: Vec<bool>,
+    rx_sensors: bool,
+    tx_sensors: bool,
+    sensors_interval: u64,
+    tx_audio: bool,
+}
+
+// radio state watched so changes from any source reach all clients
+#[derive(Clone, PartialEq)]
+struct TCIReceiverState {
+    dds: f64,
+    vfo: f64,
+    mode: usize,
+    filter_low: i32,
+    filter_high: i32,
+    sql_enable: bool,
+    sql_level: i32,
+    anf: bool,
+    cw_decoder_count: usize,
+    cw_decoder_text: String,
+}
+
+#[derive(Clone, PartialEq)]
+struct TCIState {
+    receivers: Vec<TCIReceiverState>,
+    trx: bool,
+    drive: i32,
+    split: bool,
+    cw_sending: bool,
 }
 
 impl Default for TCIMessage {
@@ -59,9 +115,25 @@ impl TCI {
     
         eprintln!("TCI WebSocket Server run {}", &self.addr);
         let listener = TcpListener::bind(&self.addr).await?;
-        let semaphore = Arc::new(Semaphore::new(1));
+        let semaphore = Arc::new(Semaphore::new(MAX_TCI_CLIENTS));
+        let (broadcast_tx, _) = broadcast::channel::<TCIBroadcast>(256);
         eprintln!("TCI WebSocket Server listening on: ws://{}", self.addr);
 
+        // IQ and audio from the receivers go to all clients
+        let data_broadcast = broadcast_tx.clone();
+        let data_stop_flag = stop_flag.clone();
+        tokio::spawn(async move {
+            forward_data(rx, data_broadcast, data_stop_flag).await;
+        });
+
+        // state changes from the GUI, CAT, MIDI or a client go to all clients
+        let state_radio = radio_mutex.clone();
+        let state_broadcast = broadcast_tx.clone();
+        let state_stop_flag = stop_flag.clone();
+        tokio::spawn(async move {
+            watch_state(state_radio, state_broadcast, state_stop_flag).await;
+        });
+
         loop {
             // Check if we should even start this iteration
             if stop_flag.load(Ordering::SeqCst) {
@@ -72,22 +144,47 @@ impl TCI {
                 // 1. Wait for a new connection
                 accept_result = listener.accept() => {
                     match accept_result {
-                        Ok((stream, _addr)) => {
+                        Ok((stream, addr)) => {
                             stream.set_nodelay(true)?;
                             let radio_handle = radio_mutex.clone();
                             let permit_pool = semaphore.clone();
                             let tx_clone = tx.clone();
-                            let rx_clone = rx.clone();
+                            let broadcast_rx = broadcast_tx.subscribe();
                             tokio::spawn(async move {
-                                // Try to acquire the permit for a single-client limit
+                                // Try to acquire a permit for the client limit
                                 match permit_pool.try_acquire() {
-                                    Ok(_permit) => {
-                                        if let Err(e) = handle_connection(stream, radio_handle, tx_clone, rx_clone).await {
+                                    Ok(permit) => {
+                                        eprintln!("TCI Client {} connected.", addr);
+                                        let receivers = radio_handle.radio.lock().unwrap().receivers as usize;
+                                        let mut client = TCIClient {
+                                            iq: vec![false; receivers],
+                                            audio: vec![false; receivers],
+                                            rx_sensors: false,
+                                            tx_sensors: false,
+                                            sensors_interval: TCI_SENSORS_INTERVAL_MS,
+                                            tx_audio: false,
+                                        };
+                                        if let Err(e) = handle_connection(stream, radio_handle.clone(), tx_clone.clone(), broadcast_rx, &mut client).await {
                                             eprintln!("TCI Connection closed: {}", e);
                                         }
+                                        if client.tx_audio {
+                                            // the client feeding transmit audio has gone so stop transmitting
+                                            release_tx_audio(&radio_handle);
+                                            if tx_clone.send(TCIMessage::UpdateMox(false)).await.is_err() {
+                                                eprintln!("TCI tx (trx): Main thread receiver was dropped.");
+                                            }
+                                        }
+                                        drop(permit);
+                                        eprintln!("TCI Client {} disconnected.", addr);
+                                        if permit_pool.available_permits() == MAX_TCI_CLIENTS {
+                                            // last client has gone
+                                            if tx_clone.send(TCIMessage::ClientDisconnected()).await.is_err() {
+                                                eprintln!("TCI tx (client disconnected): Main thread receiver was dropped.");
+                                            }
+                                        }
                                     }
                                     Err(_) => {
-                                        eprintln!("TCI Connection rejected: A client is already connected.");
+                                        eprintln!("TCI Connection rejected: limit of {} clients reached.", MAX_TCI_CLIENTS);
                                         // Stream is dropped here, closing the socket
                                     }
                                 }
@@ -113,14 +210,265 @@ impl TCI {
     }
 }
 
-async fn handle_connection(stream: TcpStream, radio: RadioMutex, tx: mpsc::Sender<TCIMessage>, rx: Arc<Mutex<mpsc::Receiver<TCIDataMessage>>>) -> Result<(), Box<dyn std::error::Error>> {
+// build a TCI binary stream frame, 64 byte header followed by f32 samples
+fn stream_packet(receiver_id: u32, sample_rate: u32, length: u32, stream_type: TCIStreamType, channels: u32, samples: &[f32]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64+(samples.len()*4));
+    let format: u32 = 3; //float 32
+    let codec: u32 = 0;
+    let crc: u32 = 0;
+    let reserve: u32 = 0;
+
+    packet.extend_from_slice(&receiver_id.to_le_bytes());
+    packet.extend_from_slice(&sample_rate.to_le_bytes());
+    packet.extend_from_slice(&format.to_le_bytes());
+    packet.extend_from_slice(&codec.to_le_bytes());
+    packet.extend_from_slice(&crc.to_le_bytes());
+    packet.extend_from_slice(&length.to_le_bytes());
+    packet.extend_from_slice(&(stream_type as u32).to_le_bytes());
+    packet.extend_from_slice(&channels.to_le_bytes());
+    for _ in 0..8 {
+        packet.extend_from_slice(&reserve.to_le_bytes());
+    }
+    for sample in samples {
+        packet.extend_from_slice(&sample.to_le_bytes());
+    }
+    packet
+}
+
+async fn forward_data(rx: Arc<Mutex<mpsc::Receiver<TCIDataMessage>>>, broadcast_tx: broadcast::Sender<TCIBroadcast>, stop_flag: Arc<AtomicBool>) {
+    let mut rx = rx.lock().await;
+    while !stop_flag.load(Ordering::SeqCst) {
+        tokio::select! {
+            msg = rx.recv() => {
+                match msg {
+                    Some(TCIDataMessage::IQData(id, data)) => {
+                        // data should be 1024 I/Q samples = 2048 f64 samples
+                        // send 256 I/Q samples per frame
+                        let chunk_size = 256*2;
+                        for chunk in data.chunks(chunk_size) {
+                            let mut samples = Vec::with_capacity(chunk.len());
+                            for iq_pair in chunk.chunks_exact(2) {
+                                // SWAP: Push Q then I
+                                samples.push(iq_pair[1] as f32);
+                                samples.push(iq_pair[0] as f32);
+                            }
+                            let packet = stream_packet(id as u32, 192000, 256, TCIStreamType::IQ_STREAM, 2, &samples);
+                            let _ = broadcast_tx.send(TCIBroadcast::IQ(id, packet));
+                        }
+                    }
+                    Some(TCIDataMessage::AudioData(id, data)) => {
+                        let samples: Vec<f32> = data.iter().map(|audio| *audio as f32).collect();
+                        let packet = stream_packet(id as u32, 48000, (data.len()/2) as u32, TCIStreamType::RX_AUDIO_STREAM, 2, &samples);
+                        let _ = broadcast_tx.send(TCIBroadcast::Audio(id, packet));
+                    }
+                    None => break,
+                }
+            }
+            _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+            }
+        }
+    }
+}
+
+async fn watch_state(radio: RadioMutex, broadcast_tx: broadcast::Sender<TCIBroadcast>, stop_flag: Arc<AtomicBool>) {
+    let mut last_state = get_state(&radio);
+    while !stop_flag.load(Ordering::SeqCst) {
+        tokio::time::sleep(std::time::Duration::from_millis(TCI_STATE_POLL_MS)).await;
+        let state = get_state(&radio);
+        if state != last_state {
+            for message in state_changes(&last_state, &state) {
+                let _ = broadcast_tx.send(TCIBroadcast::Text(message));
+            }
+            last_state = state;
+        }
+    }
+}
+
+fn get_state(radio: &RadioMutex) -> TCIState {
+    let r = radio.radio.lock().unwrap();
+    let mut receivers = Vec::new();
+    for i in 0..r.receivers as usize {
+        let rx = &r.receiver[i];
+        let sql_enable = if rx.mode == Modes::FMN.to_usize() { rx.fm_squelch } else { rx.am_squelch };
+        let sql_threshold = if rx.mode == Modes::FMN.to_usize() { rx.fm_squelch_threshold } else { rx.am_squelch_threshold };
+        receivers.push(TCIReceiverState {
+            dds: rx.frequency,
+            vfo: if rx.ctun { rx.ctun_frequency } else { rx.frequency },
+            mode: rx.mode,
+            filter_low: rx.filter_low as i32,
+            filter_high: rx.filter_high as i32,
+            sql_enable,
+            sql_level: (sql_threshold - 140.0) as i32,
+            anf: rx.anf,
+            cw_decoder_count: rx.cw_decoder_state.count,
+            cw_decoder_text: if rx.cw_decoder { rx.cw_decoder_state.text.clone() } else { String::new() },
+        });
+    }
+    TCIState {
+        receivers,
+        trx: r.is_transmitting(),
+        drive: r.transmitter.drive as i32,
+        split: r.split,
+        cw_sending: r.cw_sender.is_active(),
+    }
+}
+
+fn state_changes(last: &TCIState, state: &TCIState) -> Vec<String> {
+    let mut messages = Vec::new();
+    for (i, rx) in state.receivers.iter().enumerate() {
+        let old = last.receivers.get(i);
+        if old.map(|o| o.dds) != Some(rx.dds) {
+            messages.push(format!("dds:{},{};", i, rx.dds));
+        }
+        if old.map(|o| o.vfo) != Some(rx.vfo) {
+            messages.push(format!("vfo:{},0,{};", i, rx.vfo));
+        }
+        if old.map(|o| o.mode) != Some(rx.mode) {
+            messages.push(format!("modulation:{},{};", i, mode_to_tci(rx.mode)));
+        }
+        if old.map(|o| (o.filter_low, o.filter_high)) != Some((rx.filter_low, rx.filter_high)) {
+            messages.push(format!("rx_filter_band:{},{},{};", i, rx.filter_low, rx.filter_high));
+        }
+        if old.map(|o| o.sql_enable) != Some(rx.sql_enable) {
+            messages.push(format!("sql_enable:{},{};", i, rx.sql_enable));
+        }
+        if old.map(|o| o.sql_level) != Some(rx.sql_level) {
+            messages.push(format!("sql_level:{},{};", i, rx.sql_level));
+        }
+        if old.map(|o| o.anf) != Some(rx.anf) {
+            messages.push(format!("rx_anf_enable:{},{};", i, rx.anf));
+        }
+        if let Some(o) = old {
+            if rx.cw_decoder_count > o.cw_decoder_count && !rx.cw_decoder_text.is_empty() {
+                let new = (rx.cw_decoder_count - o.cw_decoder_count).min(rx.cw_decoder_text.len());
+                let text = &rx.cw_decoder_text[rx.cw_decoder_text.len() - new..];
+                // keep the decoded text from breaking the command framing
+                let text: String = text.chars().map(|c| if c == ',' || c == ';' || c == ':' { '^' } else { c }).collect();
+                messages.push(format!("rx_cw_decoder:{},{};", i, text));
+            }
+        }
+    }
+    if last.trx != state.trx {
+        messages.push(format!("trx:0,{};", state.trx));
+    }
+    if last.drive != state.drive {
+        messages.push(format!("drive:0,{};", state.drive));
+    }
+    if last.split != state.split {
+        messages.push(format!("split_enable:0,{};", state.split));
+    }
+    if last.cw_sending && !state.cw_sending {
+        messages.push("cw_macros_empty:0;".to_string());
+    }
+    messages
+}
+
+fn mode_to_tci(mode: usize) -> String {
+    let name = match Modes::from_usize(mode).unwrap_or(Modes::USB) {
+        Modes::LSB => "lsb",
+        Modes::USB => "usb",
+        Modes::DSB => "dsb",
+        Modes::CWL | Modes::CWU => "cw",
+        Modes::FMN => "nfm",
+        Modes::AM => "am",
+        Modes::DIGU => "digu",
+        Modes::SPEC => "spec",
+        Modes::DIGL => "digl",
+        Modes::SAM => "sam",
+        Modes::DRM => "drm",
+    };
+    name.to_string()
+}
+
+fn mode_from_tci(name: &str, frequency: f64) -> Option<usize> {
+    let mode = match name.to_uppercase().as_str() {
+        // CW follows the usual sideband for the frequency
+        "CW" => if frequency < 10000000.0 { Modes::CWL } else { Modes::CWU },
+        "LSB" => Modes::LSB,
+        "USB" => Modes::USB,
+        "DSB" => Modes::DSB,
+        "CWL" => Modes::CWL,
+        "CWU" => Modes::CWU,
+        "FM" | "NFM" | "FMN" => Modes::FMN,
+        "AM" => Modes::AM,
+        "DIGU" => Modes::DIGU,
+        "SPEC" => Modes::SPEC,
+        "DIGL" => Modes::DIGL,
+        "SAM" => Modes::SAM,
+        "DRM" => Modes::DRM,
+        _ => return None,
+    };
+    Some(mode.to_usize())
+}
+
+fn get_sensors(radio: &RadioMutex, client: &TCIClient) -> Vec<String> {
+    let r = radio.radio.lock().unwrap();
+    let mut messages = Vec::new();
+    if client.rx_sensors {
+        for i in 0..r.receivers as usize {
+            let dbm = r.receiver_level(i);
+            messages.push(format!("rx_sensors:{},{:.1};", i, dbm));
+        }
+    }
+    if client.tx_sensors && r.is_transmitting() {
+        let (power, swr) = r.transmitter.power_and_swr();
+        let mic = if r.transmitter.input_level > 0.0 { 20.0 * r.transmitter.input_level.log10() } else { -100.0 };
+        messages.push(format!("tx_sensors:0,{:.1},{:.1},{:.1},{:.1};", mic, power, power, swr));
+    }
+    messages
+}
+
+fn release_tx_audio(radio: &RadioMutex) {
+    let mut r = radio.radio.lock().unwrap();
+    r.transmitter.tci_input = false;
+    r.transmitter.tci_samples.clear();
+}
+
+// decode a TX_AUDIO_STREAM frame to mono samples
+fn tx_audio_samples(data: &[u8], sample_rate: u32) -> Option<Vec<f32>> {
+    if data.len() < 64 {
+        return None;
+    }
+    let header = |i: usize| u32::from_le_bytes([data[i*4], data[i*4+1], data[i*4+2], data[i*4+3]]);
+    if header(6) != TCIStreamType::TX_AUDIO_STREAM as u32 {
+        return None;
+    }
+    if header(1) != sample_rate {
+        eprintln!("TCI: TX audio sample rate {} not supported", header(1));
+        return None;
+    }
+    let channels = header(7).max(1) as usize;
+    let size = match header(2) {
+        0 => 2, // int16
+        1 => 3, // int24
+        2 => 4, // int32
+        3 => 4, // float32
+        _ => return None,
+    };
+    let payload = &data[64..];
+    let mut samples = Vec::with_capacity(payload.len() / (size * channels));
+    for frame in payload.chunks_exact(size * channels) {
+        let mut sample = 0.0;
+        for s in frame.chunks_exact(size) {
+            sample += match header(2) {
+                0 => i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0,
+                1 => (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8388608.0,
+                2 => i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0,
+                _ => f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+            };
+        }
+        samples.push(sample / channels as f32);
+    }
+    Some(samples)
+}
+
+async fn handle_connection(stream: TcpStream, radio: RadioMutex, tx: mpsc::Sender<TCIMessage>, mut broadcast_rx: broadcast::Receiver<TCIBroadcast>, client: &mut TCIClient) -> Result<(), Box<dyn std::error::Error>> {
     let ws_stream = accept_async(stream).await?;
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
     if tx.send(TCIMessage::ClientConnected()).await.is_err() {
         eprintln!("TCI tx (client connected): Main thread receiver was dropped.");
     }
-    eprintln!("TCI Client connected.");
 
     // TCI Initialization commands
     ws_sender.send(Message::Text(get_protocol(radio.clone().into()))).await?;
@@ -132,19 +480,21 @@ async fn handle_connection(stream: TcpStream, radio: RadioMutex, tx: mpsc::Sende
     ws_sender.send(Message::Text(get_if_limits(radio.clone().into()))).await?;
     ws_sender.send(Message::Text(get_modulations_list(radio.clone().into()))).await?;
 
-    ws_sender.send(Message::Text(get_dds(radio.clone().into()))).await?;
+    ws_sender.send(Message::Text(get_dds(radio.clone().into(), 0))).await?;
     ws_sender.send(Message::Text(get_if(radio.clone().into()))).await?;
     ws_sender.send(Message::Text("ready;".into())).await?;
-    ws_sender.send(Message::Text(get_vfo(radio.clone().into()))).await?;
+    ws_sender.send(Message::Text(get_vfo(radio.clone().into(), 0))).await?;
     ws_sender.send(Message::Text(get_mode(radio.clone().into()))).await?;
     ws_sender.send(Message::Text(get_filter_band(radio.clone().into()))).await?;
     ws_sender.send(Message::Text(get_ctun(radio.clone().into()))).await?;
     ws_sender.send(Message::Text("start;".into())).await?;
     ws_sender.send(Message::Text("ready;".into())).await?;
     
-    
+    let mut sensors_interval = client.sensors_interval;
+    let mut sensors = tokio::time::interval(std::time::Duration::from_millis(sensors_interval));
+    // tx_chrono asks the client for the next block of transmit audio
+    let mut tx_chrono = tokio::time::interval(std::time::Duration::from_millis(TCI_TX_CHRONO_MS));
 
-    let rx_owned = Arc::clone(&rx);
     loop {
         tokio::select! {
 
@@ -160,11 +510,23 @@ async fn handle_connection(stream: TcpStream, radio: RadioMutex, tx: mpsc::Sende
                             for command in incoming_text.split(';').filter(|s| !s.is_empty()) {
 eprintln!("TCI Server <<< {}",command);
                                 let tx_clone = tx.clone();
-                                if let Some(response) = process_tci_command(command, &radio, tx_clone).await {
+                                if let Some(response) = process_tci_command(command, &radio, tx_clone, client).await {
 eprintln!("TCI Server >>> {}",response);
                                     ws_sender.send(Message::Text(response.into())).await?;
                                 }
                             }
+                            if client.sensors_interval != sensors_interval {
+                                sensors_interval = client.sensors_interval;
+                                sensors = tokio::time::interval(std::time::Duration::from_millis(sensors_interval));
+                            }
+                        } else if let Message::Binary(data) = &msg {
+                            if client.tx_audio {
+                                let mut r = radio.radio.lock().unwrap();
+                                let sample_rate = r.transmitter.sample_rate as u32;
+                                if let Some(samples) = tx_audio_samples(data, sample_rate) {
+                                    r.transmitter.add_tci_samples(&samples);
+                                }
+                            }
                         }
                     }
                     Some(Err(e)) => {
@@ -177,108 +539,58 @@ eprintln!("TCI Server >>> {}",response);
                 }
             }
 
-            _ = tokio::time::sleep(std::time::Duration::from_millis(10)) => {
-                // Periodically check the mutex-protected receiver
-                let mut rx = rx_owned.lock().await;
-                while let Ok(msg) = rx.try_recv() {
-                    match msg {
-                        TCIDataMessage::IQData(data) => {
-                            // data should be 1024 I/Q samples = 2048 f64 samples
-eprintln!("IQData {}", data.len());
-                            // send 256 I/Q samples
-                            let chunk_size = 256*2;
-                            for chunk in data.chunks(chunk_size) {
-                                // 64 byte header plus 256*2*4 samples (256 f64 I and Q samples converted to f32)
-                                let mut packet = Vec::with_capacity(64+(chunk_size*4));
-
-                                let receiver_id: u32 = 0;
-                                let sample_rate: u32 = 192000;
-                                let format: u32 = 3; //float 32
-                                let codec: u32 = 0;
-                                let crc: u32 = 0;
-                                let length: u32 = 256;
-                                let stream_type: u32 = TCIStreamType::IQ_STREAM as u32;
-                                let channels: u32 = 2;
-                                let reserve:u32 = 0;
-              
-                                packet.extend_from_slice(&receiver_id.to_le_bytes());
-                                packet.extend_from_slice(&sample_rate.to_le_bytes());
-                                packet.extend_from_slice(&format.to_le_bytes());
-                                packet.extend_from_slice(&codec.to_le_bytes());
-                                packet.extend_from_slice(&crc.to_le_bytes());
-                                packet.extend_from_slice(&length.to_le_bytes());
-                                packet.extend_from_slice(&stream_type.to_le_bytes());
-                                packet.extend_from_slice(&channels.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                packet.extend_from_slice(&reserve.to_le_bytes());
-                                for iq_pair in chunk.chunks_exact(2) {
-                                    let i_sample = iq_pair[0] as f32;
-                                    let q_sample = iq_pair[1] as f32;
-                                    // SWAP: Push Q then I
-                                    packet.extend_from_slice(&q_sample.to_le_bytes());
-                                    packet.extend_from_slice(&i_sample.to_le_bytes());
-                                }
-                                ws_sender.send(Message::Binary(packet.clone())).await?;
-                                ws_sender.flush().await?;
-                            }
+            // state changes and receiver streams shared by all clients
+            broadcast_result = broadcast_rx.recv() => {
+                match broadcast_result {
+                    Ok(TCIBroadcast::Text(text)) => {
+                        ws_sender.send(Message::Text(text)).await?;
+                    }
+                    Ok(TCIBroadcast::IQ(id, packet)) => {
+                        if client.iq.get(id).copied().unwrap_or(false) {
+                            ws_sender.send(Message::Binary(packet)).await?;
+                            ws_sender.flush().await?;
                         }
-                        TCIDataMessage::AudioData(data) => {
-                            let mut packet = Vec::with_capacity(64+(data.len()*4));
-eprintln!("AudioData {}", data.len());
-                            let receiver_id: u32 = 0;
-                            let sample_rate: u32 = 48000;
-                            let format: u32 = 3; //float 32
-                            let codec: u32 = 0;
-                            let crc: u32 = 0;
-                            let length: u32 = (data.len()/2) as u32;
-                            let stream_type: u32 = TCIStreamType::RX_AUDIO_STREAM as u32;
-                            let channels: u32 = 2;
-                            let reserve:u32 = 0;
-
-                            packet.extend_from_slice(&receiver_id.to_le_bytes());
-                            packet.extend_from_slice(&sample_rate.to_le_bytes());
-                            packet.extend_from_slice(&format.to_le_bytes());
-                            packet.extend_from_slice(&codec.to_le_bytes());
-                            packet.extend_from_slice(&crc.to_le_bytes());
-                            packet.extend_from_slice(&length.to_le_bytes());
-                            packet.extend_from_slice(&stream_type.to_le_bytes());
-                            packet.extend_from_slice(&channels.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            packet.extend_from_slice(&reserve.to_le_bytes());
-                            for audio in data {
-                                let audio_sample = audio as f32;
-                                packet.extend_from_slice(&audio_sample.to_le_bytes());
-                            }
-                            ws_sender.send(Message::Binary(packet.clone())).await?;
+                    }
+                    Ok(TCIBroadcast::Audio(id, packet)) => {
+                        if client.audio.get(id).copied().unwrap_or(false) {
+                            ws_sender.send(Message::Binary(packet)).await?;
                             ws_sender.flush().await?;
-
                         }
-                        _ => {}
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        eprintln!("TCI client lagged, {} messages dropped", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        break;
+                    }
+                }
+            }
+
+            _ = tx_chrono.tick() => {
+                if client.tx_audio {
+                    let (sample_rate, queued) = {
+                        let r = radio.radio.lock().unwrap();
+                        (r.transmitter.sample_rate as u32, r.transmitter.tci_samples_queued() as u32)
+                    };
+                    // keep about two blocks queued ahead of the transmitter
+                    if queued < TCI_TX_AUDIO_SAMPLES * 2 {
+                        let packet = stream_packet(0, sample_rate, TCI_TX_AUDIO_SAMPLES * 2, TCIStreamType::TX_CHRONO, 2, &[]);
+                        ws_sender.send(Message::Binary(packet)).await?;
                     }
                 }
             }
+
+            _ = sensors.tick() => {
+                for message in get_sensors(&radio, client) {
+                    ws_sender.send(Message::Text(message)).await?;
+                }
+            }
         }
     }
-    if tx.send(TCIMessage::ClientDisconnected()).await.is_err() {
-        eprintln!("TCI tx (client connected): Main thread receiver was dropped.");
-    }
-    eprintln!("TCI Client disconnected.");
     Ok(())
 }
 
-async fn process_tci_command(full_cmd: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<TCIMessage>) -> Option<String> {
+async fn process_tci_command(full_cmd: &str, radio_mutex: &RadioMutex, tx: mpsc::Sender<TCIMessage>, client: &mut TCIClient) -> Option<String> {
     let parts: Vec<&str> = full_cmd.split(':').collect();
     let cmd_name = parts[0].to_lowercase();
     let args = if parts.len() > 1 { parts[1] } else { "" };
@@ -299,63 +611,245 @@ async fn process_tci_command(full_cmd: &str, radio_mutex: &RadioMutex, tx: mpsc:
             }
         }
         "iq_start" => {
-            // param 0 is the receiver - assuming 0 for now
-            if tx.send(TCIMessage::IQStart(0)).await.is_err() {
-                eprintln!("TCI tx (trx): Main thread receiver was dropped.");
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= client.iq.len() {
+                return None;
             }
-            None
+            client.iq[rx] = true;
+            if tx.send(TCIMessage::IQStart(rx)).await.is_err() {
+                eprintln!("TCI tx (iq_start): Main thread receiver was dropped.");
+            }
+            Some(format!("iq_start:{};", rx))
+        }
+        "iq_stop" => {
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx < client.iq.len() {
+                client.iq[rx] = false;
+            }
+            Some(format!("iq_stop:{};", rx))
+        }
+        "audio_start" => {
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= client.audio.len() {
+                return None;
+            }
+            client.audio[rx] = true;
+            if tx.send(TCIMessage::IQStart(rx)).await.is_err() {
+                eprintln!("TCI tx (audio_start): Main thread receiver was dropped.");
+            }
+            Some(format!("audio_start:{};", rx))
+        }
+        "audio_stop" => {
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx < client.audio.len() {
+                client.audio[rx] = false;
+            }
+            Some(format!("audio_stop:{};", rx))
         }
         "rx_sensors_enable" => {
-            None
+            client.rx_sensors = params[0] == "true";
+            if let Some(Ok(interval)) = params.get(1).map(|i| i.parse::<u64>()) {
+                client.sensors_interval = interval.max(30);
+            }
+            Some(format!("rx_sensors_enable:{},{};", client.rx_sensors, client.sensors_interval))
         }
         "tx_sensors_enable" => {
-            None
+            client.tx_sensors = params[0] == "true";
+            if let Some(Ok(interval)) = params.get(1).map(|i| i.parse::<u64>()) {
+                client.sensors_interval = interval.max(30);
+            }
+            Some(format!("tx_sensors_enable:{},{};", client.tx_sensors, client.sensors_interval))
         }
         "split_enable" => {
-            None
+            if params.len() >= 2 {
+                if tx.send(TCIMessage::UpdateSplit(params[1] == "true")).await.is_err() {
+                    eprintln!("TCI tx (split_enable): Main thread receiver was dropped.");
+                }
+                None
+            } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                Some(format!("split_enable:0,{};", r.split))
+            }
         }
         "sql_enable" => {
-            None
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= client.iq.len() {
+                return None;
+            }
+            if params.len() >= 2 {
+                if tx.send(TCIMessage::UpdateSquelch(rx, params[1] == "true")).await.is_err() {
+                    eprintln!("TCI tx (sql_enable): Main thread receiver was dropped.");
+                }
+                None
+            } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                let receiver = r.receiver.get(rx)?;
+                let sql = if receiver.mode == Modes::FMN.to_usize() { receiver.fm_squelch } else { receiver.am_squelch };
+                Some(format!("sql_enable:{},{};", rx, sql))
+            }
         }
         "sql_level" => {
-            None
+            // level in dB -140..0
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= client.iq.len() {
+                return None;
+            }
+            if params.len() >= 2 {
+                if let Ok(level) = params[1].parse::<f64>() {
+                    if tx.send(TCIMessage::UpdateSquelchLevel(rx, level)).await.is_err() {
+                        eprintln!("TCI tx (sql_level): Main thread receiver was dropped.");
+                    }
+                }
+                None
+            } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                let receiver = r.receiver.get(rx)?;
+                let threshold = if receiver.mode == Modes::FMN.to_usize() { receiver.fm_squelch_threshold } else { receiver.am_squelch_threshold };
+                Some(format!("sql_level:{},{};", rx, (threshold - 140.0) as i32))
+            }
         }
         "rx_anf_enable" => {
-            None
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= client.iq.len() {
+                return None;
+            }
+            if params.len() >= 2 {
+                if tx.send(TCIMessage::UpdateANF(rx, params[1] == "true")).await.is_err() {
+                    eprintln!("TCI tx (rx_anf_enable): Main thread receiver was dropped.");
+                }
+                None
+            } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                let receiver = r.receiver.get(rx)?;
+                Some(format!("rx_anf_enable:{},{};", rx, receiver.anf))
+            }
         }
         "drive" => {
-            None
+            // drive:trx,value to set, drive:trx to read
+            if params.len() >= 2 {
+                if let Ok(drive) = params[1].parse::<f32>() {
+                    if tx.send(TCIMessage::UpdateDrive(drive.clamp(0.0, 100.0))).await.is_err() {
+                        eprintln!("TCI tx (drive): Main thread receiver was dropped.");
+                    }
+                }
+                None
+            } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                Some(format!("drive:0,{};", r.transmitter.drive as i32))
+            }
         }
         "rx_smeter" => {
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            let r = radio_mutex.radio.lock().unwrap();
+            r.receiver.get(rx)?;
+            let dbm = r.receiver_level(rx);
+            Some(format!("rx_smeter:{},0,{:.1};", rx, dbm).to_string())
+        }
+        "cw_macros" => {
+            // cw_macros:trx,text sends the text with {CALL}, {RST} and {NR} substituted
+            let text = params.get(1..).map(|p| p.join(",")).unwrap_or_default();
+            let mut r = radio_mutex.radio.lock().unwrap();
+            if !r.cw_send_text(&format!("{} ", text)) {
+                eprintln!("TCI cw_macros: refused");
+            }
+            None
+        }
+        "cw_msg" => {
+            // cw_msg:trx,before,callsign,after
+            let mut r = radio_mutex.radio.lock().unwrap();
+            if let Some(call) = params.get(2) {
+                r.cw_call = call.trim().to_uppercase();
+            }
+            let text: Vec<&str> = params.iter().skip(1).map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+            if !r.cw_send_text(&format!("{} ", text.join(" "))) {
+                eprintln!("TCI cw_msg: refused");
+            }
+            None
+        }
+        "cw_macros_speed" => {
+            let mut r = radio_mutex.radio.lock().unwrap();
+            if let Ok(speed) = params[0].parse::<i32>() {
+                r.cw_keyer_speed = speed.clamp(1, 60);
+                r.updated = true;
+            }
+            Some(format!("cw_macros_speed:{};", r.cw_keyer_speed))
+        }
+        "cw_macros_stop" => {
             let mut r = radio_mutex.radio.lock().unwrap();
-            Some(format!("rx_smeter:{},{};", 0, r.s_meter_dbm).to_string())
+            r.cw_abort();
+            None
         }
         "trx" => {
-            if tx.send(TCIMessage::UpdateMox(params[1]=="true")).await.is_err() {
+            // trx:0,true,tci transmits audio sent by the client
+            let state = params.get(1) == Some(&"true");
+            if state {
+                let mut r = radio_mutex.radio.lock().unwrap();
+                if !r.check_transmit() {
+                    // refused, tell the client we are still receiving
+                    return Some("trx:0,false;".to_string());
+                }
+            }
+            let tci = state && params.get(2) == Some(&"tci");
+            if tci || client.tx_audio {
+                {
+                    let mut r = radio_mutex.radio.lock().unwrap();
+                    r.transmitter.tci_samples.clear();
+                    r.transmitter.tci_input = tci;
+                }
+                client.tx_audio = tci;
+            }
+            if tx.send(TCIMessage::UpdateMox(state)).await.is_err() {
                 eprintln!("TCI tx (trx): Main thread receiver was dropped.");
             }
             None
         }
+        "audio_samplerate" => {
+            // transmit and receive audio is fixed at the protocol rate
+            let r = radio_mutex.radio.lock().unwrap();
+            Some(format!("audio_samplerate:{};", r.transmitter.sample_rate))
+        }
         "dds" => {
-            if let Ok(freq) = params[1].parse::<f64>() {
-                if tx.send(TCIMessage::UpdateDDS(freq)).await.is_err() {
-                    eprintln!("TCI tx (vfo): Main thread receiver was dropped.");
+            // dds:trx,frequency to set, dds:trx to read
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if rx >= radio_mutex.radio.lock().unwrap().receivers as usize {
+                return None;
+            }
+            match params.get(1) {
+                Some(frequency) => {
+                    if let Ok(freq) = frequency.parse::<f64>() {
+                        if tx.send(TCIMessage::UpdateDDS(rx, freq)).await.is_err() {
+                            eprintln!("TCI tx (dds): Main thread receiver was dropped.");
+                        }
+                        eprintln!("TCI: DDS {} set to {}", rx, freq);
+                    }
+                    None
                 }
-                eprintln!("TCI: VFO set to {}", freq);
+                None => Some(get_dds(radio_mutex.clone(), rx)),
             }
-            None
         }
         "vfo" => {
-            if let Ok(freq) = params[2].parse::<f64>() {
-                if tx.send(TCIMessage::UpdateFrequencyA(freq)).await.is_err() {
-                    eprintln!("TCI tx (vfo): Main thread receiver was dropped.");
+            // vfo:trx,channel,frequency to set, vfo:trx,channel to read
+            // each receiver has a single VFO, channel 0
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            let channel = params.get(1).and_then(|c| c.parse::<usize>().ok()).unwrap_or(0);
+            if channel != 0 || rx >= radio_mutex.radio.lock().unwrap().receivers as usize {
+                return None;
+            }
+            match params.get(2) {
+                Some(frequency) => {
+                    if let Ok(freq) = frequency.parse::<f64>() {
+                        if tx.send(TCIMessage::UpdateVFO(rx, freq)).await.is_err() {
+                            eprintln!("TCI tx (vfo): Main thread receiver was dropped.");
+                        }
+                        eprintln!("TCI: VFO {} set to {}", rx, freq);
+                    }
+                    None
                 }
-                eprintln!("TCI: VFO set to {}", freq);
+                None => Some(get_vfo(radio_mutex.clone(), rx)),
             }
-            None
         }
         "rx_volume" => {
-            if params.len() == 2 {
+            if params.len() <= 2 {
                 let mut r = radio_mutex.radio.lock().unwrap();
                 let gain = r.receiver[0].afgain;
                 let norm_gain = gain / 100.0;
@@ -363,7 +857,7 @@ async fn process_tci_command(full_cmd: &str, radio_mutex: &RadioMutex, tx: mpsc:
                 let actual_gain = -60.0 + (linear_gain * (0.0 - (-60.0)));
                 Some(format!("rx_volume:{},{};", 0, actual_gain))
             } else {
-                if let Ok(volume) = params[2].parse::<f32>() {
+                if let Some(Ok(volume)) = params.get(2).map(|v| v.parse::<f32>()) {
                     let gain = volume;
                     let norm_gain = (gain - (-60.0)) / (0.0 - (-60.0));
                     let log_gain = norm_gain.powf(2.0);
@@ -377,11 +871,24 @@ async fn process_tci_command(full_cmd: &str, radio_mutex: &RadioMutex, tx: mpsc:
             }
         }
         "modulation" => {
-            if params.len() == 2 {
-                // set the modulation
+            let rx = params[0].parse::<usize>().unwrap_or(0);
+            if params.len() >= 2 {
+                let frequency = {
+                    let r = radio_mutex.radio.lock().unwrap();
+                    let receiver = r.receiver.get(rx)?;
+                    if receiver.ctun { receiver.ctun_frequency } else { receiver.frequency }
+                };
+                if let Some(mode) = mode_from_tci(params[1], frequency) {
+                    if tx.send(TCIMessage::UpdateMode(rx, mode)).await.is_err() {
+                        eprintln!("TCI tx (modulation): Main thread receiver was dropped.");
+                    }
+                }
+                None
             } else {
+                let r = radio_mutex.radio.lock().unwrap();
+                let receiver = r.receiver.get(rx)?;
+                Some(format!("modulation:{},{};", rx, mode_to_tci(receiver.mode)))
             }
-            None
         }
         _ => {
             // Log unknown commands to help debugging protocol sync
@@ -401,19 +908,19 @@ eprintln!("TCI Server >>> {}", message);
     message
 }
 
-fn get_dds(radio: RadioMutex) -> String {
+fn get_dds(radio: RadioMutex, rx: usize) -> String {
     let r = radio.radio.lock().unwrap();
-    let message = format!("dds:0,{};",r.receiver[0].frequency);
+    let message = format!("dds:{},{};", rx, r.receiver[rx].frequency);
 eprintln!("TCI Server >>> {}", message);
     message
 }
 
-fn get_vfo(radio: RadioMutex) -> String {
+fn get_vfo(radio: RadioMutex, rx: usize) -> String {
     let r = radio.radio.lock().unwrap();
-    let message = format!("vfo:0,0,{};", if r.receiver[0].ctun {
-                                r.receiver[0].ctun_frequency
+    let message = format!("vfo:{},0,{};", rx, if r.receiver[rx].ctun {
+                                r.receiver[rx].ctun_frequency
                             } else {
-                                r.receiver[0].frequency
+                                r.receiver[rx].frequency
                             });
 eprintln!("TCI Server >>> {}", message);
     message
@@ -426,18 +933,14 @@ eprintln!("TCI Server >>> {}", message);
 }
 
 fn get_modulations_list(radio: RadioMutex) -> String {
-    let message = format!("modulations_list:LSB,USB,DSB,CWL,CWU,FMN,AM,DIGU,SPEC,DIGL,SAM,DRM;");
+    let message = "modulations_list:lsb,usb,dsb,cw,nfm,am,digu,spec,digl,sam,drm;".to_string();
 eprintln!("TCI Server >>> {}", message);
     message
 }
 
 fn get_mode(radio: RadioMutex) -> String {
     let r = radio.radio.lock().unwrap();
-    let message = if r.receiver[0].mode == Modes::CWL.to_usize() || r.receiver[0].mode == Modes::CWU.to_usize() {
-        format!("modulation:0,{};", "cw")
-    } else {
-        format!("modulation:0,{:?};", Modes::from_usize(r.receiver[0].mode).unwrap())
-    };
+    let message = format!("modulation:0,{};", mode_to_tci(r.receiver[0].mode));
 eprintln!("TCI Server >>> {}", message);
     message
 }
@@ -17,6 +17,7 @@
 
 
 use std::cmp::{max, min};
+use std::collections::VecDeque;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_int};
 
@@ -26,6 +27,12 @@ use crate::discovery::Boards;
 use crate::modes::Modes;
 use crate::wdsp::*;
 
+// feedback samples collected before they are handed to pscc
+const PS_BUFFER_SIZE: usize = 1024;
+
+// PureSignal calibration states reported in GetPSInfo info[15]
+const PS_STATES: [&str; 10] = ["Reset", "Wait", "MOX Delay", "Setup", "Collect", "MOX Check", "Calculate", "Delay", "Stay On", "Turn Off"];
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transmitter {
     pub protocol: u8,
@@ -78,6 +85,34 @@ pub struct Transmitter {
     pub input_level: f32,
 #[serde(skip_serializing, skip_deserializing)]
     pub max_level: f32,
+#[serde(skip_serializing, skip_deserializing)]
+    pub tci_input: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub tci_samples: VecDeque<f32>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ctcss: bool,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ctcss_frequency: f64,
+#[serde(default)]
+    pub puresignal: bool,
+#[serde(default = "default_ps_auto")]
+    pub ps_auto: bool,
+#[serde(default)]
+    pub ps_hw_peak: f64, // 0.0 from an older configuration, replaced by the board default
+#[serde(skip_serializing, skip_deserializing)]
+    pub ps_feedback_rate: i32,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ps_tx_feedback: Vec<f64>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ps_rx_feedback: Vec<f64>,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ps_samples: usize,
+#[serde(skip_serializing, skip_deserializing)]
+    pub ps_info: Vec<i32>,
+}
+
+fn default_ps_auto() -> bool {
+    true
 }
 
 impl Transmitter {
@@ -151,6 +186,18 @@ impl Transmitter {
         let alc = 0.0;
         let input_level = 0.0;
         let max_level = 0.0;
+        let tci_input = false;
+        let tci_samples = VecDeque::new();
+        let ctcss = false;
+        let ctcss_frequency = 100.0;
+        let puresignal = false;
+        let ps_auto = default_ps_auto();
+        let ps_hw_peak = Transmitter::default_ps_hw_peak(protocol, board);
+        let ps_feedback_rate = 192000;
+        let ps_tx_feedback = vec![0.0f64; PS_BUFFER_SIZE * 2];
+        let ps_rx_feedback = vec![0.0f64; PS_BUFFER_SIZE * 2];
+        let ps_samples = 0;
+        let ps_info = vec![0; 16];
 
         Transmitter{ protocol,
             board,
@@ -193,6 +240,18 @@ impl Transmitter {
             alc,
             input_level,
             max_level,
+            tci_input,
+            tci_samples,
+            ctcss,
+            ctcss_frequency,
+            puresignal,
+            ps_auto,
+            ps_hw_peak,
+            ps_feedback_rate,
+            ps_tx_feedback,
+            ps_rx_feedback,
+            ps_samples,
+            ps_info,
         }
     }
 
@@ -205,6 +264,7 @@ impl Transmitter {
         self.max_level = 0.0;
 
         self.init_wdsp();
+        self.init_puresignal();
 
         let id_string = String::from("TX");
         let c_string = CString::new(id_string).expect("CString::new failed");
@@ -368,12 +428,230 @@ impl Transmitter {
         }
     }
 
+    // CW sender key, the tone is silenced rather than stopped between elements
+    pub fn set_cw_key(&self, key_down: bool, cw_keyer_sidetone_frequency: i32) {
+        let mut frequency = cw_keyer_sidetone_frequency as f64;
+        if self.mode == Modes::CWL.to_usize() {
+            frequency = -frequency;
+        }
+        unsafe {
+            SetTXAPostGenToneFreq(self.channel, frequency);
+            SetTXAPostGenToneMag(self.channel, if key_down { 0.99999 } else { 0.0 });
+            SetTXAPostGenMode(self.channel, 0); // Tone
+            SetTXAPostGenRun(self.channel, 1);
+        }
+    }
+
+    // boards with the DAC feedback path PureSignal needs
+    pub fn puresignal_supported(&self) -> bool {
+        !matches!(self.board, Boards::Metis | Boards::HermesLite | Boards::HermesLite2 | Boards::Unknown)
+    }
+
+    // feedback level of the HW peak at full drive, depends on the protocol and the board
+    pub fn default_ps_hw_peak(protocol: u8, board: Boards) -> f64 {
+        if protocol == 2 {
+            match board {
+                Boards::Orion2 | Boards::Saturn => 0.6121,
+                _ => 0.2899,
+            }
+        } else {
+            0.4067
+        }
+    }
+
+    fn init_puresignal(&mut self) {
+        if self.ps_hw_peak <= 0.0 {
+            self.ps_hw_peak = Transmitter::default_ps_hw_peak(self.protocol, self.board);
+        }
+        self.ps_tx_feedback = vec![0.0f64; PS_BUFFER_SIZE * 2];
+        self.ps_rx_feedback = vec![0.0f64; PS_BUFFER_SIZE * 2];
+        self.ps_samples = 0;
+        self.ps_info = vec![0; 16];
+        let feedback_rate = if self.protocol == 2 { 192000 } else { self.ps_feedback_rate };
+        unsafe {
+            SetPSFeedbackRate(self.channel, feedback_rate);
+            SetPSHWPeak(self.channel, self.ps_hw_peak);
+            SetPSPtol(self.channel, 0.8);
+            SetPSMoxDelay(self.channel, 0.2);
+            SetPSLoopDelay(self.channel, 0.0);
+            SetPSIntsAndSpi(self.channel, 16, 256);
+            SetPSStabilize(self.channel, 0);
+            SetPSMapMode(self.channel, 1);
+            SetPSPinMode(self.channel, 1);
+        }
+        self.set_puresignal();
+    }
+
+    // protocol 1 feedback arrives at the receiver sample rate
+    pub fn set_ps_feedback_rate(&mut self, rate: i32) {
+        self.ps_feedback_rate = rate;
+        if self.protocol == 1 {
+            unsafe {
+                SetPSFeedbackRate(self.channel, rate);
+            }
+        }
+    }
+
+    pub fn set_puresignal(&mut self) {
+        self.ps_samples = 0;
+        unsafe {
+            if self.puresignal && self.puresignal_supported() {
+                SetPSRunCal(self.channel, 1);
+                SetPSControl(self.channel, 0, 0, self.ps_auto as i32, 0);
+            } else {
+                SetPSControl(self.channel, 1, 0, 0, 0);
+                SetPSRunCal(self.channel, 0);
+            }
+        }
+    }
+
+    pub fn ps_single_cal(&self) {
+        if self.puresignal && self.puresignal_supported() {
+            unsafe {
+                SetPSControl(self.channel, 0, 1, 0, 0);
+            }
+        }
+    }
+
+    pub fn set_ps_hw_peak(&self) {
+        unsafe {
+            SetPSHWPeak(self.channel, self.ps_hw_peak);
+        }
+    }
+
+    pub fn set_ps_mox(&self, mox: bool) {
+        unsafe {
+            SetPSMox(self.channel, mox as i32);
+        }
+    }
+
+    // the radio is sending the PureSignal feedback DDCs rather than receive samples
+    pub fn ps_feedback(&self, transmitting: bool) -> bool {
+        self.puresignal && self.puresignal_supported() && transmitting
+    }
+
+    // TX (DAC) and RX (coupler) feedback sample pair from the protocol
+    pub fn add_ps_iq_samples(&mut self, tx_i: f64, tx_q: f64, rx_i: f64, rx_q: f64) {
+        let x = self.ps_samples * 2;
+        self.ps_tx_feedback[x] = tx_i;
+        self.ps_tx_feedback[x+1] = tx_q;
+        self.ps_rx_feedback[x] = rx_i;
+        self.ps_rx_feedback[x+1] = rx_q;
+        self.ps_samples += 1;
+        if self.ps_samples >= PS_BUFFER_SIZE {
+            self.ps_samples = 0;
+            let tx_ptr: *mut f64 = self.ps_tx_feedback.as_mut_ptr();
+            let rx_ptr: *mut f64 = self.ps_rx_feedback.as_mut_ptr();
+            unsafe {
+                pscc(self.channel, PS_BUFFER_SIZE as i32, tx_ptr, rx_ptr);
+            }
+        }
+    }
+
+    pub fn update_ps_info(&mut self) {
+        if self.ps_info.len() < 16 {
+            self.ps_info = vec![0; 16];
+        }
+        unsafe {
+            GetPSInfo(self.channel, self.ps_info.as_mut_ptr());
+        }
+    }
+
+    pub fn ps_feedback_level(&self) -> i32 {
+        self.ps_info.get(4).copied().unwrap_or(0)
+    }
+
+    // feedback too high (over 181), ok (over 128), low (over 90) or none
+    pub fn ps_feedback_status(&self) -> &'static str {
+        let level = self.ps_feedback_level();
+        if level > 181 {
+            "high"
+        } else if level > 128 {
+            "ok"
+        } else if level > 90 {
+            "low"
+        } else {
+            "none"
+        }
+    }
+
+    pub fn ps_correcting(&self) -> bool {
+        self.ps_info.get(14).copied().unwrap_or(0) == 1
+    }
+
+    pub fn ps_state(&self) -> &'static str {
+        let state = self.ps_info.get(15).copied().unwrap_or(0);
+        PS_STATES.get(state as usize).copied().unwrap_or("Unknown")
+    }
+
+    pub fn ps_max_tx(&self) -> f64 {
+        let mut max_tx: f64 = 0.0;
+        unsafe {
+            GetPSMaxTX(self.channel, &mut max_tx);
+        }
+        max_tx
+    }
+
     pub fn set_micgain(&self) {
         unsafe {
             SetTXAPanelGain1(self.channel,10.0_f32.powf(self.micgain / 20.0) as f64);
         }
     }
 
+    // forward power in watts and SWR from the alex power readings
+    pub fn power_and_swr(&self) -> (f32, f32) {
+        let fwd_power = self.alex_forward_power as f32;
+        let rev_power = self.alex_reverse_power as f32;
+
+        let v_fwd = (fwd_power / 4095.0) * self.c1;
+        let fwd = (v_fwd * v_fwd) / self.c2;
+
+        let v_rev = (rev_power / 4095.0) * self.c1;
+        let rev = (v_rev * v_rev) / self.c2;
+
+        let mut swr = (1.0 + (rev / fwd).sqrt())  / (1.0 - (rev / fwd).sqrt());
+        if swr < 0.0 {
+            swr = 1.0;
+        }
+        if swr.is_nan() {
+            swr = 1.0;
+        }
+        (fwd, swr)
+    }
+
+    // CTCSS tone from a memory channel, None turns it off
+    pub fn set_ctcss(&mut self, tone: Option<f64>) {
+        self.ctcss = tone.is_some();
+        if let Some(frequency) = tone {
+            self.ctcss_frequency = frequency;
+        }
+        unsafe {
+            SetTXACTCSSFreq(self.channel, self.ctcss_frequency);
+            SetTXACTCSSRun(self.channel, self.ctcss as i32);
+        }
+    }
+
+    // transmit audio received from a TCI client, read by the protocol like a local microphone
+    pub fn add_tci_samples(&mut self, samples: &[f32]) {
+        self.tci_samples.extend(samples);
+        // do not let latency build up if the client sends faster than we transmit
+        let limit = self.sample_rate as usize / 2;
+        if self.tci_samples.len() > limit {
+            let excess = self.tci_samples.len() - limit;
+            self.tci_samples.drain(..excess);
+        }
+    }
+
+    pub fn tci_samples_queued(&self) -> usize {
+        self.tci_samples.len()
+    }
+
+    pub fn read_tci_input(&mut self) -> (Vec<f32>, usize) {
+        let buffer: Vec<f32> = self.tci_samples.drain(..).collect();
+        let count = buffer.len();
+        (buffer, count)
+    }
+
     pub fn add_mic_sample(&mut self, sample: f32) -> bool {
         let mut processed = false;
         let x = self.microphone_samples * 2;
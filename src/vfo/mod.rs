@@ -26,7 +26,6 @@ use crate::modes::*;
 use crate::filters::*;
 use crate::radio::{Keyer, RadioModels, RadioMutex};
 use crate::receiver::*;
-use crate::wdsp::*;
 use crate::widgets::*;
 use crate::util::*;
 
@@ -54,9 +53,11 @@ pub fn create_vfo_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex:
     let low = r.receiver[rx].filter_low;
     let high = r.receiver[rx].filter_high;
     let cw_pitch = r.receiver[rx].cw_pitch;
+    let band_info = r.receiver[rx].band_info.clone();
+    let grid_bands = r.band_plan.grid_bands();
     drop(r);
 
-    let mut band_grid = BandGrid::new(&builder);
+    let mut band_grid = BandGrid::new(&builder, &band_info, &grid_bands);
     let mut mode_grid = ModeGrid::new(&builder);
     let mut filter_grid = FilterGrid::new(&builder);
 
@@ -68,76 +69,36 @@ pub fn create_vfo_dialog(rc_app_widgets: &Rc<RefCell<AppWidgets>>, radio_mutex:
     band_grid.set_callback(move|index| {
         let app_widgets = rc_app_widgets_clone.borrow();
         let mut r = radio_mutex_clone.radio.lock().unwrap();
-        let b = r.receiver[rx].band.to_usize();
-        if b != index { // band has changed
-            // save current band info
-            r.receiver[rx].band_info[b].current = r.receiver[rx].frequency;
-            r.receiver[rx].band_info[b].ctun = r.receiver[rx].ctun_frequency;
-            r.receiver[rx].band_info[b].mode = Modes::from_usize(r.receiver[rx].mode).expect("Invalid mode");
-            r.receiver[rx].band_info[b].filter = Filters::from_usize(r.receiver[rx].filter).expect("Invalid Filter");
-
-            // get new band info
-            r.receiver[rx].band = Bands::from_usize(index).expect("invalid band index");
-            r.receiver[rx].frequency = r.receiver[rx].band_info[index].current;
-            r.receiver[rx].ctun_frequency = r.receiver[rx].band_info[index].ctun;
-            if r.receiver[rx].ctun {
-                r.receiver[rx].set_ctun_frequency();
-            }
-
-            if !r.receiver[rx].filters_manual {
-                r.receiver[rx].filters = r.receiver[rx].band_info[index].filters;
-            }
-        }
-        filter_grid_clone.update_filter_buttons(r.receiver[rx].band_info[index].mode.to_usize());
-        filter_grid_clone.set_active_index(r.receiver[rx].band_info[index].filter.to_usize());
-
-        let f = r.receiver[rx].frequency;
-        if b != index { // band has changed
-            r.receiver[rx].mode = r.receiver[rx].band_info[index].mode.to_usize();
-            mode_grid_clone.set_active_index(r.receiver[rx].mode);
-            let (mut low, mut high) = filter_grid_clone.get_filter_values(r.receiver[rx].band_info[index].mode.to_usize(), r.receiver[rx].band_info[index].filter.to_usize());
-            filter_grid_clone.set_active_values(low, high);
-            if r.receiver[rx].mode == Modes::CWL.to_usize() {
-                low += -r.receiver[rx].cw_pitch;
-                high += -r.receiver[rx].cw_pitch;
-            } else if r.receiver[rx].mode == Modes::CWU.to_usize() {
-                low += r.receiver[rx].cw_pitch;
-                high += r.receiver[rx].cw_pitch;
-            }
-            r.receiver[rx].filter_low = low;
-            r.receiver[rx].filter_high = high;
-            r.receiver[rx].set_mode();
-
-            r.transmitter.filter_low = low;
-            r.transmitter.filter_high = high;
-            r.transmitter.mode = r.receiver[rx].band_info[index].mode.to_usize();
-            r.transmitter.set_mode();
-            r.transmitter.set_filter();
-
-            let formatted_value = format_u32_with_separators(
-                                      if r.receiver[rx].ctun {
-                                          r.receiver[rx].ctun_frequency as u32
-                                      } else {
-                                           r.receiver[rx].frequency as u32
-                                      });
-            if rx == 0 {
-                app_widgets.vfo_a_frequency.set_label(&formatted_value);
-            } else {
-                app_widgets.vfo_b_frequency.set_label(&formatted_value);
-            }
-
-            let mut b = r.receiver[rx].band.to_usize();
-            let mut attenuation = r.receiver[rx].band_info[b].attenuation;
-            if r.dev == 6 { // HEMES_LITE
-                b = r.receiver[0].band.to_usize();
-                attenuation = r.receiver[0].band_info[b].attenuation;
-            }
-            drop(r);
-            app_widgets.attenuation_adjustment.set_value(attenuation.into());
+        r.select_receiver_band(rx, index);
+
+        let mode = r.receiver[rx].mode;
+        let filter = r.receiver[rx].filter;
+        filter_grid_clone.update_filter_buttons(mode);
+        filter_grid_clone.set_active_index(filter);
+        mode_grid_clone.set_active_index(mode);
+        let (low, high) = filter_grid_clone.get_filter_values(mode, filter);
+        filter_grid_clone.set_active_values(low, high);
+
+        let formatted_value = format_u32_with_separators(
+                                  if r.receiver[rx].ctun {
+                                      r.receiver[rx].ctun_frequency as u32
+                                  } else {
+                                       r.receiver[rx].frequency as u32
+                                  });
+        if rx == 0 {
+            app_widgets.vfo_a_frequency.set_label(&formatted_value);
+        } else {
+            app_widgets.vfo_b_frequency.set_label(&formatted_value);
         }
-        unsafe {
-            RXANBPSetTuneFrequency(rx as i32, f as f64);
+
+        let mut b = r.receiver[rx].band.to_usize();
+        let mut attenuation = r.receiver[rx].band_info[b].attenuation;
+        if r.dev == 6 { // HEMES_LITE
+            b = r.receiver[0].band.to_usize();
+            attenuation = r.receiver[0].band_info[b].attenuation;
         }
+        drop(r);
+        app_widgets.attenuation_adjustment.set_value(attenuation.into());
     }, band.to_usize());
 
     let radio_mutex_clone = radio_mutex.clone();
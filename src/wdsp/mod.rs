@@ -432,6 +432,9 @@ unsafe extern "C" {
         size: ::std::os::raw::c_int,
     );
 }
+unsafe extern "C" {
+    pub fn destroy_divEXT(id: ::std::os::raw::c_int);
+}
 unsafe extern "C" {
     pub fn SetEXTDIVRun(id: ::std::os::raw::c_int, run: ::std::os::raw::c_int);
 }
@@ -15,7 +15,7 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use gtk::{Adjustment, ApplicationWindow, Builder, Button, DrawingArea, DropDown, Frame, Grid, Label, ProgressBar, ToggleButton};
+use gtk::{Adjustment, ApplicationWindow, Builder, Button, DrawingArea, DropDown, Frame, Grid, Label, ProgressBar, TextView, ToggleButton};
 
 use crate::bands::*;
 use crate::modes::*;
@@ -34,7 +34,11 @@ pub struct AppWidgets {
     pub rx2_button: ToggleButton,
     pub cat_button: ToggleButton,
     pub rigctl_button: ToggleButton,
-    //pub midi_button: ToggleButton,
+    pub midi_button: ToggleButton,
+    pub memories_button: Button,
+    pub cw_button: Button,
+    pub diversity_button: Button,
+    pub scan_button: ToggleButton,
     pub tci_button: ToggleButton,
     pub step_dropdown: DropDown,
     pub meter_1_display: DrawingArea,
@@ -49,6 +53,11 @@ pub struct AppWidgets {
     pub nb_button: ToggleButton,
     pub anf_button: ToggleButton,
     pub snb_button: ToggleButton,
+    pub cw_decoder_button: ToggleButton,
+    pub cw_decoder_1_frame: Frame,
+    pub cw_decoder_1_view: TextView,
+    pub cw_decoder_2_frame: Frame,
+    pub cw_decoder_2_view: TextView,
     pub mox_button: ToggleButton,
     pub tun_button: ToggleButton,
     pub afgain_adjustment: Adjustment,
@@ -61,6 +70,8 @@ pub struct AppWidgets {
     pub tx_power: Label,
     pub tx_swr: Label,
     pub tx_alc: Label,
+    pub ps_status: Label,
+    pub tx_lockout: Label,
     pub input_level: ProgressBar,
 }
 
@@ -115,9 +126,25 @@ impl AppWidgets {
             .object("rigctl_button")
             .expect("Could not get rigctl_button from builder");
 
-        //let midi_button: ToggleButton = builder
-        //    .object("midi_button")
-        //    .expect("Could not get midi_button from builder");
+        let midi_button: ToggleButton = builder
+            .object("midi_button")
+            .expect("Could not get midi_button from builder");
+
+        let memories_button: Button = builder
+            .object("memories_button")
+            .expect("Could not get memories_button from builder");
+
+        let cw_button: Button = builder
+            .object("cw_button")
+            .expect("Could not get cw_button from builder");
+
+        let diversity_button: Button = builder
+            .object("diversity_button")
+            .expect("Could not get diversity_button from builder");
+
+        let scan_button: ToggleButton = builder
+            .object("scan_button")
+            .expect("Could not get scan_button from builder");
 
         let tci_button: ToggleButton = builder
             .object("tci_button")
@@ -175,6 +202,26 @@ impl AppWidgets {
             .object("snb_button")
             .expect("Could not get snb_button from builder");
 
+        let cw_decoder_button: ToggleButton = builder
+            .object("cw_decoder_button")
+            .expect("Could not get cw_decoder_button from builder");
+
+        let cw_decoder_1_frame: Frame = builder
+            .object("cw_decoder_1_frame")
+            .expect("Could not get cw_decoder_1_frame from builder");
+
+        let cw_decoder_1_view: TextView = builder
+            .object("cw_decoder_1_view")
+            .expect("Could not get cw_decoder_1_view from builder");
+
+        let cw_decoder_2_frame: Frame = builder
+            .object("cw_decoder_2_frame")
+            .expect("Could not get cw_decoder_2_frame from builder");
+
+        let cw_decoder_2_view: TextView = builder
+            .object("cw_decoder_2_view")
+            .expect("Could not get cw_decoder_2_view from builder");
+
         let mox_button: ToggleButton = builder
             .object("mox_button")
             .expect("Could not get mox_button from builder");
@@ -223,6 +270,14 @@ impl AppWidgets {
             .object("tx_alc")
             .expect("Could not get tx_alc from builder");
 
+        let ps_status: Label = builder
+            .object("ps_status")
+            .expect("Could not get ps_status from builder");
+
+        let tx_lockout: Label = builder
+            .object("tx_lockout")
+            .expect("Could not get tx_lockout from builder");
+
         //let input_level: Label = builder
         let input_level: ProgressBar = builder
             .object("input_level")
@@ -241,7 +296,11 @@ impl AppWidgets {
             rx2_button,
             cat_button,
             rigctl_button,
-            //midi_button,
+            midi_button,
+            memories_button,
+            cw_button,
+            diversity_button,
+            scan_button,
             tci_button,
             step_dropdown,
             meter_1_display,
@@ -256,6 +315,11 @@ impl AppWidgets {
             nb_button,
             anf_button,
             snb_button,
+            cw_decoder_button,
+            cw_decoder_1_frame,
+            cw_decoder_1_view,
+            cw_decoder_2_frame,
+            cw_decoder_2_view,
             mox_button,
             tun_button,
             afgain_adjustment,
@@ -268,6 +332,8 @@ impl AppWidgets {
             tx_power,
             tx_swr,
             tx_alc,
+            ps_status,
+            tx_lockout,
             input_level,
         }
     }